@@ -27,7 +27,7 @@ use crate::{
     samplers::{RandomSampler, Sampler, StratifiedSampler},
     scene::Scene,
     scene::SceneBuilder,
-    shapes::Triangle,
+    shapes::TriangleMesh,
     shapes::{Shape, Sphere},
     spectra::{Spectrum, SpectrumT},
     textures::ImageTexture,
@@ -90,33 +90,37 @@ impl Context {
                     .map(|p| Point2::new(p[0], p[1]))
                     .collect::<Vec<_>>();
 
-                let mut tris = vec![Shape::Triangle(Triangle::default()); mesh.indices.len() / 3];
-                for i in 0..mesh.indices.len() / 3 {
-                    tris[i] = Shape::Triangle(Triangle::new(
-                        [
-                            vertices[mesh.indices[i * 3] as usize],
-                            vertices[mesh.indices[i * 3 + 1] as usize],
-                            vertices[mesh.indices[i * 3 + 2] as usize],
-                        ],
-                        [
-                            normals[mesh.indices[i * 3] as usize],
-                            normals[mesh.indices[i * 3 + 1] as usize],
-                            normals[mesh.indices[i * 3 + 2] as usize],
-                        ],
-                        [
-                            *(uvs.get(mesh.indices[i * 3] as usize))
-                                .unwrap_or(&Point2::new(1.0, 0.0)),
-                            *(uvs.get(mesh.indices[i * 3 + 1] as usize))
-                                .unwrap_or(&Point2::new(0.0, 1.0)),
-                            *(uvs.get(mesh.indices[i * 3 + 2] as usize))
-                                .unwrap_or(&Point2::new(0.0, 1.0)),
-                        ],
-                    ));
+                let mut mesh = TriangleMesh::new(
+                    vertices,
+                    normals,
+                    if uvs.len() == mesh.positions.len() / 3 {
+                        uvs
+                    } else {
+                        vec![]
+                    },
+                    mesh.indices.clone(),
+                );
+                if let Some(world_to_object) = world_to_object {
+                    mesh.transform(&world_to_object);
                 }
 
-                sb.primitives(tris, material, world_to_object, medium_interface);
+                sb.primitives(mesh.triangles(), material, None, medium_interface);
             };
 
+        // the environment most scenes are lit by, turned so the key light is in front
+        let studio = || {
+            Light::Environment(Box::new(
+                Environment::new(SpectralTexture::Image(ImageTexture::from_path(Path::new(
+                    "assets/material_test/christmas_photo_studio_07.exr",
+                ))))
+                .with_transform(Transform3::rotate(Point3::new(
+                    0.0,
+                    core::f32::consts::FRAC_PI_3,
+                    0.0,
+                ))),
+            ))
+        };
+
         let scene = match 7 {
             0 => {
                 let mut sb = SceneBuilder::new();
@@ -150,11 +154,7 @@ impl Context {
                     MediumInterface::none(),
                 );
 
-                sb.light(Light::Environment(Environment::new(
-                    SpectralTexture::Image(ImageTexture::from_path(Path::new(
-                        "assets/material_test/christmas_photo_studio_07.exr",
-                    ))),
-                )));
+                sb.light(studio());
 
                 sb.build()
             }
@@ -312,8 +312,8 @@ impl Context {
                         SpectralTexture::Constant(ConstantTexture::new(Spectrum::from_rgb(
                             0.4, 0.4, 0.95,
                         ))),
-                        ior::POLYPROPYLENE,
                         ior::AIR,
+                        ior::POLYPROPYLENE,
                         0.5,
                     )))),
                     None,
@@ -321,12 +321,8 @@ impl Context {
                     MediumInterface::none(),
                 );
 
-                sb.light(Light::Environment(Environment::new(
-                    SpectralTexture::Image(ImageTexture::from_path(Path::new(
-                        "assets/material_test/christmas_photo_studio_07.exr",
-                        // "assets/venice_sunset_4k.exr",
-                    ))),
-                )));
+                // or "assets/venice_sunset_4k.exr"
+                sb.light(studio());
 
                 sb.build()
             }
@@ -399,11 +395,7 @@ impl Context {
                     ),
                 );
 
-                sb.light(Light::Environment(Environment::new(
-                    SpectralTexture::Image(ImageTexture::from_path(Path::new(
-                        "assets/material_test/christmas_photo_studio_07.exr",
-                    ))),
-                )));
+                sb.light(studio());
 
                 sb.build()
             }
@@ -418,14 +410,14 @@ impl Context {
                 );
 
                 sb.area_light(AreaLight::new(
-                    Primitive {
-                        shape: Shape::Sphere(Sphere::new(0.5)),
-                        material_index: 0,
-                        area_light_index: None,
-                        world_to_object: Some(Transform3::translate(Vector3::new(-0.5, 7.5, -1.0))),
-                        medium_interface: MediumInterface::none(),
-                    },
-                    Spectrum::splat(10.0),
+                    Primitive::new(
+                        Shape::Sphere(Sphere::new(0.5)),
+                        0,
+                        None,
+                        Some(Transform3::translate(Vector3::new(-0.5, 7.5, -1.0))),
+                        MediumInterface::none(),
+                    ),
+                    SpectralTexture::Constant(ConstantTexture::new(Spectrum::splat(10.0))),
                 ));
                 // sb.light(Light::Environment(Environment::new(
                 //     SpectralTexture::Constant(ConstantTexture::new(Spectrum::splat(1.0))),
@@ -530,11 +522,7 @@ impl Context {
                     Transform3::translate(Vector3::new(-size, 0.0, size * 0.5)),
                 );
 
-                sb.light(Light::Environment(Environment::new(
-                    SpectralTexture::Image(ImageTexture::from_path(Path::new(
-                        "assets/material_test/christmas_photo_studio_07.exr",
-                    ))),
-                )));
+                sb.light(studio());
 
                 sb.build()
             }
@@ -636,11 +624,7 @@ impl Context {
                     ),
                 );
 
-                sb.light(Light::Environment(Environment::new(
-                    SpectralTexture::Image(ImageTexture::from_path(Path::new(
-                        "assets/material_test/christmas_photo_studio_07.exr",
-                    ))),
-                )));
+                sb.light(studio());
 
                 sb.build()
             }
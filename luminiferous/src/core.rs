@@ -8,3 +8,8 @@ mod display_reporter;
 pub use display_reporter::*;
 
 pub mod log;
+
+#[cfg(test)]
+mod chi2;
+#[cfg(test)]
+pub(crate) use chi2::*;
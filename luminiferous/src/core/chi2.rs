@@ -0,0 +1,257 @@
+// Statistical tests for sampling routines, adapted from mitsuba's `ChiSquareTest`.
+
+use crate::prelude::*;
+use crate::spectra::{Spectrum, SpectrumT};
+
+/// Chi-square goodness of fit between directions drawn by a sampling routine and the density that is
+/// supposed to describe them, on a histogram over (cos theta, phi) so every bin has the same solid angle.
+pub(crate) struct ChiSquareTest {
+    pub cos_theta_res: usize,
+    pub phi_res: usize,
+    pub sample_count: usize,
+    pub significance: f64,
+    pub seed: u64,
+}
+
+impl Default for ChiSquareTest {
+    fn default() -> Self {
+        Self {
+            cos_theta_res: 16,
+            phi_res: 32,
+            sample_count: 200_000,
+            significance: 0.01,
+            seed: 0x5eed,
+        }
+    }
+}
+
+impl ChiSquareTest {
+    /// Runs the test, `sample` returns `None` for invalid samples, these are expected to be missing from
+    /// the integral of `pdf` as well. `test_count` is used to correct the significance level
+    /// when several tests are run together.
+    pub fn run(
+        &self,
+        mut sample: impl FnMut(f32, Point2) -> Option<Vector3>,
+        pdf: impl Fn(Vector3) -> f32,
+        test_count: usize,
+    ) -> Result<(), String> {
+        let bins = self.cos_theta_res * self.phi_res;
+        let mut rng = oorandom::Rand32::new(self.seed);
+
+        let mut observed = vec![0.0f64; bins];
+        for _ in 0..self.sample_count {
+            let u1 = rng.rand_float();
+            let u2 = Point2::new(rng.rand_float(), rng.rand_float());
+
+            let Some(wo) = sample(u1, u2) else {
+                continue;
+            };
+            if !wo.is_finite() {
+                return Err(format!("sampled a non finite direction {wo}"));
+            }
+            if (wo.length() - 1.0).abs() > 1e-3 {
+                return Err(format!("sampled a non normalized direction {wo}"));
+            }
+
+            observed[self.bin(wo.normalize())] += 1.0;
+        }
+
+        // integrate the density over each bin with the midpoint rule
+        const SUBDIVISIONS: usize = 64;
+        let cos_theta_step = 2.0 / self.cos_theta_res as f32;
+        let phi_step = core::f32::consts::TAU / self.phi_res as f32;
+        let sub_cos_theta_step = cos_theta_step / SUBDIVISIONS as f32;
+        let sub_phi_step = phi_step / SUBDIVISIONS as f32;
+
+        let mut expected = vec![0.0f64; bins];
+        for i in 0..self.cos_theta_res {
+            for j in 0..self.phi_res {
+                let mut integral = 0.0f64;
+                for si in 0..SUBDIVISIONS {
+                    for sj in 0..SUBDIVISIONS {
+                        let cos_theta = -1.0
+                            + i as f32 * cos_theta_step
+                            + (si as f32 + 0.5) * sub_cos_theta_step;
+                        let phi = j as f32 * phi_step + (sj as f32 + 0.5) * sub_phi_step;
+
+                        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                        let (sin_phi, cos_phi) = phi.sin_cos();
+                        let wo = Vector3::new(cos_phi * sin_theta, sin_phi * sin_theta, cos_theta);
+
+                        let p = pdf(wo);
+                        if !p.is_finite() || p < 0.0 {
+                            return Err(format!("invalid pdf {p} for {wo}"));
+                        }
+                        integral += p as f64;
+                    }
+                }
+
+                expected[i * self.phi_res + j] = integral
+                    * (sub_cos_theta_step * sub_phi_step) as f64
+                    * self.sample_count as f64;
+            }
+        }
+
+        let total = expected.iter().sum::<f64>() / self.sample_count as f64;
+        if total > 1.01 {
+            return Err(format!("pdf integrates to {total}, more than one"));
+        }
+
+        // pool the bins with too few expected samples so the chi-square approximation holds
+        let mut order = (0..bins).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| expected[a].total_cmp(&expected[b]));
+
+        let (mut pooled_observed, mut pooled_expected) = (0.0, 0.0);
+        let mut chi2 = 0.0;
+        let mut dof = 0usize;
+        for index in order {
+            let (o, e) = (observed[index], expected[index]);
+            if e == 0.0 {
+                // a few samples are fine, the integration can miss a sliver of a bin at a discontinuity
+                if o > self.sample_count as f64 * 1e-4 {
+                    return Err(format!("{o} samples in bin {index} where the pdf is zero"));
+                }
+            } else if e < 5.0 {
+                pooled_observed += o;
+                pooled_expected += e;
+            } else if pooled_expected > 0.0 && pooled_expected < 5.0 {
+                // not enough in the pool yet, merge this bin in too
+                pooled_observed += o;
+                pooled_expected += e;
+            } else {
+                chi2 += (o - e) * (o - e) / e;
+                dof += 1;
+            }
+        }
+        if pooled_expected > 0.0 {
+            chi2 += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
+            dof += 1;
+        }
+        // the totals are fixed, which takes away one degree of freedom
+        let Some(dof) = dof.checked_sub(1).filter(|&dof| dof > 0) else {
+            return Err(format!(
+                "only {dof} bins have enough expected samples, increase the sample count"
+            ));
+        };
+
+        let p_value = gamma_q(dof as f64 / 2.0, chi2 / 2.0);
+        // Šidák correction
+        let alpha = 1.0 - (1.0 - self.significance).powf(1.0 / test_count as f64);
+        if p_value < alpha || !p_value.is_finite() {
+            return Err(format!(
+                "rejected the null hypothesis, chi2 = {chi2}, dof = {dof}, p = {p_value} < {alpha}"
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn bin(&self, wo: Vector3) -> usize {
+        let cos_theta = wo.z.clamp(-1.0, 1.0);
+        let mut phi = wo.y.atan2(wo.x);
+        if phi < 0.0 {
+            phi += core::f32::consts::TAU;
+        }
+
+        let i = (((cos_theta + 1.0) * 0.5 * self.cos_theta_res as f32) as usize)
+            .min(self.cos_theta_res - 1);
+        let j =
+            ((phi / core::f32::consts::TAU * self.phi_res as f32) as usize).min(self.phi_res - 1);
+        i * self.phi_res + j
+    }
+}
+
+/// Monte carlo estimate of the directional albedo, the mean of the sampled weights.
+pub(crate) fn albedo(
+    mut sample: impl FnMut(f32, Point2) -> Spectrum,
+    sample_count: usize,
+) -> [f32; 3] {
+    let mut rng = oorandom::Rand32::new(0xa1bed0);
+    let mut sum = [0.0f64; 3];
+    for _ in 0..sample_count {
+        let s = sample(
+            rng.rand_float(),
+            Point2::new(rng.rand_float(), rng.rand_float()),
+        )
+        .to_rgb();
+        for c in 0..3 {
+            sum[c] += s[c] as f64;
+        }
+    }
+
+    sum.map(|s| (s / sample_count as f64) as f32)
+}
+
+fn ln_gamma(x: f64) -> f64 {
+    // Lanczos approximation
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    let mut y = x;
+    for c in COEFFICIENTS {
+        y += 1.0;
+        series += c / y;
+    }
+
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Regularized upper incomplete gamma function Q(a, x).
+fn gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0.0 || a <= 0.0 {
+        return 1.0;
+    }
+
+    if x < a + 1.0 {
+        // series expansion of P
+        let mut ap = a;
+        let mut delta = 1.0 / a;
+        let mut sum = delta;
+        for _ in 0..1000 {
+            ap += 1.0;
+            delta *= x / ap;
+            sum += delta;
+            if delta.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+
+        1.0 - sum * (-x + a * x.ln() - ln_gamma(a)).exp()
+    } else {
+        // continued fraction of Q with Lentz's method
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+
+        (-x + a * x.ln() - ln_gamma(a)).exp() * h
+    }
+}
@@ -6,6 +6,9 @@ pub use assimp_loader::*;
 mod pbrt_loader;
 pub use pbrt_loader::*;
 
+mod hair_loader;
+pub use hair_loader::*;
+
 use crate::prelude::*;
 
 use crate::scene::{Scene, SceneBuilder};
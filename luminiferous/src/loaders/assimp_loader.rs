@@ -3,7 +3,6 @@ use std::path::Path;
 use russimp::{
     mesh::{Mesh, PrimitiveType},
     scene::PostProcess,
-    Vector3D,
 };
 
 use crate::prelude::*;
@@ -17,7 +16,7 @@ use crate::{
     media::MediumInterface,
     rfilters::{RFilter, TentFilter},
     scene::SceneBuilder,
-    shapes::{Shape, Triangle},
+    shapes::TriangleMesh,
     spectra::{Spectrum, SpectrumT},
     textures::{ConstantTexture, SpectralTexture},
 };
@@ -31,98 +30,66 @@ impl AssimpLoader {}
 
 //TODO: the unhappy path is awful here.. need proper error handling
 
-pub(crate) fn shapes_from_russimp_mesh(mesh: &Mesh) -> Vec<Shape> {
+pub(crate) fn triangle_mesh_from_russimp_mesh(mesh: &Mesh) -> Option<TriangleMesh> {
     if (mesh.primitive_types & PrimitiveType::Triangle) != PrimitiveType::Triangle as u32 {
         warnln!(
             "mesh '{}' is non triangular and won't be loaded, it is {}.",
             mesh.name,
             mesh.primitive_types
         );
-        return vec![];
+        return None;
     }
 
     // assimp doesn't like blender normals, so we have this hack
     // https://github.com/assimp/assimp/issues/816
-    let bad_normals = |n: &Vector3D| !(n.x.is_normal() && n.x.is_normal() && n.x.is_normal());
+    let bad_normal = |n: Normal3| !(n.is_finite() && n.length_squared() > 0.0);
+    let normals = mesh
+        .normals
+        .iter()
+        .map(|n| Normal3::new(n.x, n.y, n.z))
+        .collect::<Vec<_>>();
 
-    if mesh.normals.iter().any(bad_normals) {
+    if normals.iter().copied().any(bad_normal) {
         warnln!(
             "mesh '{}' has invalid normals, some or all will be shaded flat.",
             mesh.name
         );
     }
 
-    // let material = &imp_scene.materials[mesh.material_index as usize];
-    let default_uvs = [
-        Point2::new(0.0, 0.0),
-        Point2::new(1.0, 0.0),
-        Point2::new(1.0, 1.0),
-    ];
-
-    mesh.faces
-        .iter()
-        .filter_map(|face| {
-            let indices = &face.0;
-            if face.0.is_empty() {
-                return None;
-            }
-            assert_eq!(face.0.len(), 3);
-
-            let vertices = [
-                mesh.vertices[indices[0] as usize],
-                mesh.vertices[indices[1] as usize],
-                mesh.vertices[indices[2] as usize],
-            ]
-            .map(|v| Vector3::new(v.x, v.y, v.z));
-
-            let mut normals = if !mesh.normals.is_empty() {
-                [
-                    mesh.normals[indices[0] as usize],
-                    mesh.normals[indices[1] as usize],
-                    mesh.normals[indices[2] as usize],
-                ]
+    // the triangles around a zeroed normal are shaded flat
+    let normals = normals
+        .into_iter()
+        .map(|n| {
+            if bad_normal(n) {
+                Normal3::ZERO
             } else {
-                [Vector3D {
-                    x: 0.0,
-                    y: 0.0,
-                    z: 0.0,
-                }; 3]
-            };
-
-            if normals.iter().any(bad_normals) {
-                normals = [(vertices[1] - vertices[0])
-                    .cross(vertices[2] - vertices[0])
-                    .normalize(); 3]
-                    .map(|n| Vector3D {
-                        x: n.x,
-                        y: n.y,
-                        z: n.z,
-                    })
+                n.normalize()
             }
-
-            let normals = normals.map(|n| Normal3::new(n.x, n.y, n.z).normalize());
-
-            Some(Shape::Triangle(Triangle::new(
-                vertices,
-                normals,
-                if mesh.texture_coords.is_empty() {
-                    default_uvs
-                } else {
-                    [
-                        (mesh.texture_coords[0].as_ref(), 0),
-                        (mesh.texture_coords[0].as_ref(), 1),
-                        (mesh.texture_coords[0].as_ref(), 2),
-                    ]
-                    .map(|(uv, i)| {
-                        uv.map(|uv| {
-                            Point2::new(uv[indices[i] as usize].x, uv[indices[i] as usize].y)
-                        })
-                        .unwrap_or(default_uvs[i])
-                    })
-                },
-            )))
         })
-        .collect()
+        .collect();
+    let uvs = mesh
+        .texture_coords
+        .first()
+        .and_then(|uvs| uvs.as_ref())
+        .map(|uvs| uvs.iter().map(|uv| Point2::new(uv.x, uv.y)).collect())
+        .unwrap_or_default();
+
+    Some(TriangleMesh::new(
+        mesh.vertices
+            .iter()
+            .map(|v| Point3::new(v.x, v.y, v.z))
+            .collect(),
+        normals,
+        uvs,
+        mesh.faces
+            .iter()
+            .filter(|face| !face.0.is_empty())
+            .flat_map(|face| {
+                assert_eq!(face.0.len(), 3);
+                face.0.iter().copied()
+            })
+            .collect(),
+    ))
 }
 
 impl Loader for AssimpLoader {
@@ -189,82 +156,9 @@ impl Loader for AssimpLoader {
         }
 
         for mesh in imp_scene.meshes {
-            if (mesh.primitive_types & PrimitiveType::Triangle) != PrimitiveType::Triangle as u32 {
-                warnln!(
-                    "mesh `{}` is non triangular and won't be loaded, it is {}",
-                    mesh.name,
-                    mesh.primitive_types
-                );
+            let Some(mesh) = triangle_mesh_from_russimp_mesh(&mesh) else {
                 continue;
-            }
-
-            // assimp doesn't like blender normals, so we have this hack
-            // https://github.com/assimp/assimp/issues/816
-            let bad_normals = mesh
-                .normals
-                .iter()
-                .any(|n| !(n.x.is_normal() && n.x.is_normal() && n.x.is_normal()));
-
-            if bad_normals {
-                warnln!("mesh `{}` has invalid normals, shading flat.", mesh.name);
-            }
-
-            // let material = &imp_scene.materials[mesh.material_index as usize];
-            let default_uvs = [
-                Point2::new(0.0, 0.0),
-                Point2::new(1.0, 0.0),
-                Point2::new(1.0, 1.0),
-            ];
-
-            let triangles = mesh
-                .faces
-                .iter()
-                .map(|face| {
-                    let indices = &face.0;
-                    assert!(face.0.len() == 3);
-
-                    let vertices = [
-                        mesh.vertices[indices[0] as usize],
-                        mesh.vertices[indices[1] as usize],
-                        mesh.vertices[indices[2] as usize],
-                    ]
-                    .map(|v| Vector3::new(v.x, v.y, v.z));
-
-                    Shape::Triangle(Triangle::new(
-                        vertices,
-                        if mesh.normals.is_empty() || bad_normals {
-                            [(vertices[1] - vertices[0])
-                                .cross(vertices[2] - vertices[0])
-                                .normalize(); 3]
-                        } else {
-                            [
-                                mesh.normals[indices[0] as usize],
-                                mesh.normals[indices[1] as usize],
-                                mesh.normals[indices[2] as usize],
-                            ]
-                            .map(|n| Normal3::new(n.x, n.y, n.z).normalize())
-                        },
-                        if mesh.texture_coords.is_empty() {
-                            default_uvs
-                        } else {
-                            [
-                                (mesh.texture_coords[0].as_ref(), 0),
-                                (mesh.texture_coords[0].as_ref(), 1),
-                                (mesh.texture_coords[0].as_ref(), 2),
-                            ]
-                            .map(|(uv, i)| {
-                                uv.map(|uv| {
-                                    Point2::new(
-                                        uv[indices[i] as usize].x,
-                                        uv[indices[i] as usize].y,
-                                    )
-                                })
-                                .unwrap_or(default_uvs[i])
-                            })
-                        },
-                    ))
-                })
-                .collect();
+            };
 
             // NOTE: materials aren't exported from blender yet shrug
             let material = Material::Direct(DirectMaterial::new(Bsdf::Lambertian(
@@ -272,7 +166,7 @@ impl Loader for AssimpLoader {
                     Spectrum::from_rgb(0.6, 0.3, 0.6),
                 ))),
             )));
-            sb.primitives(triangles, material, None, MediumInterface::none());
+            sb.primitives(mesh.triangles(), material, None, MediumInterface::none());
         }
     }
 }
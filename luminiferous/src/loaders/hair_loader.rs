@@ -0,0 +1,185 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use crate::prelude::*;
+
+/// A strand of hair as a polyline with a width at every point.
+#[derive(Debug, Clone)]
+pub struct HairStrand {
+    pub points: Vec<Point3>,
+    pub widths: Vec<f32>,
+}
+
+/// The `.hair` file has an array with the number of segments of every strand.
+const HAS_SEGMENTS: u32 = 1 << 0;
+/// The `.hair` file has an array with the points, it can't be loaded without it.
+const HAS_POINTS: u32 = 1 << 1;
+/// The `.hair` file has an array with the thickness at every point.
+const HAS_THICKNESS: u32 = 1 << 2;
+
+/// Loads the strands of a `.hair` file, the binary format by Cem Yuksel. Transparency and colour are
+/// ignored, the strands get their look from the material.
+pub fn load_hair_file(path: &Path) -> Result<Vec<HairStrand>, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut header = [0; 128];
+    reader.read_exact(&mut header)?;
+    if &header[0..4] != b"HAIR" {
+        Err("Invalid hair file: bad signature")?;
+    }
+
+    let u32_at = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+    let strand_count = u32_at(4) as usize;
+    let point_count = u32_at(8) as usize;
+    let flags = u32_at(12);
+    let default_segments = u32_at(16) as usize;
+    let default_thickness = f32::from_le_bytes(header[20..24].try_into().unwrap());
+
+    if flags & HAS_POINTS == 0 {
+        Err("Invalid hair file: no points")?;
+    }
+
+    let segments = if flags & HAS_SEGMENTS != 0 {
+        let mut data = vec![0; strand_count * 2];
+        reader.read_exact(&mut data)?;
+        data.chunks_exact(2)
+            .map(|s| u16::from_le_bytes([s[0], s[1]]) as usize)
+            .collect()
+    } else {
+        vec![default_segments; strand_count]
+    };
+
+    let points = read_f32s(&mut reader, point_count * 3)?
+        .chunks_exact(3)
+        .map(|p| Point3::new(p[0], p[1], p[2]))
+        .collect::<Vec<_>>();
+
+    let widths = if flags & HAS_THICKNESS != 0 {
+        read_f32s(&mut reader, point_count)?
+    } else {
+        vec![default_thickness; point_count]
+    };
+
+    if segments.iter().map(|s| s + 1).sum::<usize>() != point_count {
+        Err("Invalid hair file: the strands don't add up to the number of points")?;
+    }
+
+    let mut start = 0;
+    Ok(segments
+        .into_iter()
+        .map(|s| {
+            let range = start..start + s + 1;
+            start = range.end;
+            HairStrand {
+                points: points[range.clone()].to_vec(),
+                widths: widths[range].to_vec(),
+            }
+        })
+        .collect())
+}
+
+fn read_f32s(reader: &mut impl Read, count: usize) -> Result<Vec<f32>, Box<dyn Error>> {
+    let mut data = vec![0; count * 4];
+    reader.read_exact(&mut data)?;
+
+    Ok(data
+        .chunks_exact(4)
+        .map(|f| f32::from_le_bytes([f[0], f[1], f[2], f[3]]))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Writes a `.hair` file with `strands` strands, `flags` and the arrays after the header to a
+    /// temporary file.
+    fn write_hair_file(
+        name: &str,
+        strands: u32,
+        flags: u32,
+        segments: &[u16],
+        points: &[f32],
+        widths: &[f32],
+    ) -> std::path::PathBuf {
+        let mut header = [0u8; 128];
+        header[0..4].copy_from_slice(b"HAIR");
+        header[4..8].copy_from_slice(&strands.to_le_bytes());
+        header[8..12].copy_from_slice(&(points.len() as u32 / 3).to_le_bytes());
+        header[12..16].copy_from_slice(&flags.to_le_bytes());
+        header[16..20].copy_from_slice(&1u32.to_le_bytes());
+        header[20..24].copy_from_slice(&0.5f32.to_le_bytes());
+
+        let mut data = header.to_vec();
+        data.extend(segments.iter().flat_map(|s| s.to_le_bytes()));
+        data.extend(points.iter().flat_map(|p| p.to_le_bytes()));
+        data.extend(widths.iter().flat_map(|w| w.to_le_bytes()));
+
+        let path =
+            std::env::temp_dir().join(format!("luminiferous_{name}_{}.hair", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn loads_strands() {
+        let points = (0..15).map(|i| i as f32).collect::<Vec<_>>();
+        let path = write_hair_file(
+            "strands",
+            2,
+            HAS_SEGMENTS | HAS_POINTS | HAS_THICKNESS,
+            &[2, 1],
+            &points,
+            &[0.1, 0.2, 0.3, 0.4, 0.5],
+        );
+        let strands = load_hair_file(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(strands.len(), 2);
+        assert_eq!(strands[0].points.len(), 3);
+        assert_eq!(strands[0].points[2], Point3::new(6.0, 7.0, 8.0));
+        assert_eq!(strands[0].widths, [0.1, 0.2, 0.3]);
+        assert_eq!(
+            strands[1].points,
+            [Point3::new(9.0, 10.0, 11.0), Point3::new(12.0, 13.0, 14.0)]
+        );
+        assert_eq!(strands[1].widths, [0.4, 0.5]);
+    }
+
+    #[test]
+    fn uses_the_defaults() {
+        // every strand has the default of one segment and width of a half
+        let points = (0..12).map(|i| i as f32).collect::<Vec<_>>();
+        let path = write_hair_file("defaults", 2, HAS_POINTS, &[], &points, &[]);
+        let strands = load_hair_file(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(strands.len(), 2);
+        assert!(strands
+            .iter()
+            .all(|s| s.points.len() == 2 && s.widths == [0.5, 0.5]));
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let points = [0.0; 9];
+        let no_points = write_hair_file("no_points", 1, HAS_SEGMENTS, &[2], &points, &[]);
+        let mismatched = write_hair_file(
+            "mismatched",
+            1,
+            HAS_SEGMENTS | HAS_POINTS,
+            &[3],
+            &points,
+            &[],
+        );
+
+        assert!(load_hair_file(&no_points).is_err());
+        assert!(load_hair_file(&mismatched).is_err());
+        std::fs::remove_file(no_points).unwrap();
+        std::fs::remove_file(mismatched).unwrap();
+    }
+}
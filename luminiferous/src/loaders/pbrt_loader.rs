@@ -6,26 +6,39 @@
 // FIXME: all of this
 
 use core::panic;
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
 
-use crate::lights::AreaLight;
 use crate::prelude::*;
 
-use crate::primitive::Primitive;
-use crate::shapes::Triangle;
 use crate::{
-    bsdfs::{ior, Bsdf, ConductorBsdf, Dielectric, Lambertian, NullBsdf, PlasticBsdf},
+    bsdfs::{
+        ior, Bsdf, ConductorBsdf, Dielectric, DiffuseTransmissionBsdf, HairBsdf, Lambertian,
+        NullBsdf, PlasticBsdf, SheenBsdf, ThinDielectricBsdf,
+    },
     cameras::{Camera, PerspectiveCamera},
     film::Film,
-    lights::{DistantLight, Environment, Light, PointLight},
-    loaders::shapes_from_russimp_mesh,
-    materials::{DirectMaterial, Material},
+    lights::{
+        DistantLight, Environment, GoniometricLight, IesProfile, Light, PointLight, ProjectionLight,
+        Sky, Spotlight,
+    },
+    loaders::load_hair_file,
+    materials::{
+        BlendMaterial, DirectMaterial, EmissiveMaterial, Material, NormalModifier,
+        SubsurfaceMaterial, TwoSidedMaterial,
+    },
     media::MediumInterface,
     rfilters::{RFilter, TentFilter},
     scene::SceneBuilder,
-    shapes::{Shape, Sphere},
+    shapes::{
+        Cone, Curve, CurveKind, Cylinder, Disk, Quad, Shape, Sphere, SubdivisionMesh,
+        SubdivisionScheme, TriangleMesh,
+    },
     spectra::{Spectrum, SpectrumT},
-    textures::{ConstantTexture, ImageTexture, SpectralTexture},
+    textures::{ConstantTexture, ImageTexture, SpectralTexture, Texture},
 };
 
 use super::{Loader, SceneCreationParams};
@@ -98,6 +111,15 @@ impl ParameterValue {
         }
     }
 
+    #[inline]
+    pub fn unwrap_bool_or(&self, default: bool) -> bool {
+        if let ParameterValue::Bool(b) = self {
+            *b
+        } else {
+            default
+        }
+    }
+
     #[inline]
     pub fn unwrap_string(&self) -> String {
         if let ParameterValue::String(s) = self {
@@ -116,6 +138,17 @@ impl ParameterValue {
         }
     }
 
+    #[inline]
+    pub fn unwrap_int_or(&self, default: i32) -> i32 {
+        if let ParameterValue::Integer(i) = self {
+            if i.len() == 1 {
+                return i[0];
+            }
+        }
+
+        default
+    }
+
     #[inline]
     pub fn unwrap_ints(&self) -> &Vec<i32> {
         if let ParameterValue::Integer(f) = self {
@@ -129,10 +162,10 @@ impl ParameterValue {
 #[derive(Debug, Clone)]
 enum UntypedTexture {
     Spectral(SpectralTexture),
-    // Float(Texture<f32>),
+    Float(Texture<f32>),
 }
 
-fn load_ply_to_shape_vec(path: &Path) -> Vec<Shape> {
+fn load_ply_to_mesh(path: &Path) -> TriangleMesh {
     let p = ply_rs::parser::Parser::<ply_rs::ply::DefaultElement>::new();
     let mut f = std::fs::File::open(path).unwrap();
 
@@ -158,55 +191,136 @@ fn load_ply_to_shape_vec(path: &Path) -> Vec<Shape> {
             )
         })
         .collect::<Vec<_>>();
-    ply.payload["face"]
+    let indices = ply.payload["face"]
         .iter()
         .flat_map(|face| {
             let idxs = &face["vertex_indices"];
             match idxs {
                 ply_rs::ply::Property::ListUInt(list) => {
                     if list.len() == 3 {
-                        return vec![[list[0] as usize, list[1] as usize, list[2] as usize]];
+                        return vec![list[0], list[1], list[2]];
                     }
 
-                    vec![
-                        [list[0] as usize, list[1] as usize, list[2] as usize],
-                        [list[0] as usize, list[2] as usize, list[3] as usize],
-                    ]
+                    vec![list[0], list[1], list[2], list[0], list[2], list[3]]
                 }
                 ply_rs::ply::Property::ListInt(list) => {
+                    let list = list.iter().map(|&i| i as u32).collect::<Vec<_>>();
                     if list.len() == 3 {
-                        return vec![[list[0] as usize, list[1] as usize, list[2] as usize]];
+                        return list;
                     }
 
-                    vec![
-                        [list[0] as usize, list[1] as usize, list[2] as usize],
-                        [list[0] as usize, list[2] as usize, list[3] as usize],
-                    ]
+                    vec![list[0], list[1], list[2], list[0], list[2], list[3]]
                 }
                 _ => panic!("oof"),
             }
         })
-        .map(|face| {
-            let v1 = vertices[face[0]];
-            let v2 = vertices[face[1]];
-            let v3 = vertices[face[2]];
-
-            let mut n = [v1.1, v2.1, v3.1];
-            if n[0] == Normal3::ZERO && n[1] == Normal3::ZERO && n[2] == Normal3::ZERO {
-                n = [(v2.0 - v1.0).cross(v3.0 - v1.0); 3];
-            }
-            n = n.map(|n| n.normalize());
-            Shape::Triangle(Triangle::new([v1.0, v2.0, v3.0], n, [v1.2, v2.2, v3.2]))
+        .collect();
+
+    // files without normals are shaded flat
+    let normals = if vertices.iter().all(|v| v.1 == Normal3::ZERO) {
+        vec![]
+    } else {
+        vertices.iter().map(|v| v.1.normalize()).collect()
+    };
+
+    TriangleMesh::new(
+        vertices.iter().map(|v| v.0).collect(),
+        normals,
+        vertices.iter().map(|v| v.2).collect(),
+        indices,
+    )
+}
+
+/// Loads a pbrt curve made of cubic Bézier segments, ribbons need a normal at the start and end of
+/// every segment.
+fn load_curve_to_shape_vec(
+    control_points: &[Point3],
+    width: [f32; 2],
+    kind: &str,
+    normals: &[Normal3],
+    split_depth: u32,
+) -> Vec<Shape> {
+    if control_points.len() < 4 || control_points.len() % 3 != 1 {
+        warnln!(
+            "curve with {} control points is not a cubic bezier curve and won't be loaded.",
+            control_points.len()
+        );
+        return vec![];
+    }
+
+    let num_segments = (control_points.len() - 1) / 3;
+    let ribbon = kind == "ribbon";
+    if ribbon && normals.len() != num_segments + 1 {
+        warnln!(
+            "ribbon curve with {num_segments} segments needs {} normals, not {}. Loading it as a flat curve.",
+            num_segments + 1,
+            normals.len()
+        );
+    }
+
+    (0..num_segments)
+        .flat_map(|i| {
+            let kind = match kind {
+                "ribbon" if normals.len() == num_segments + 1 => {
+                    CurveKind::Ribbon([normals[i], normals[i + 1]])
+                }
+                "cylinder" => CurveKind::Cylinder,
+                _ => CurveKind::Flat,
+            };
+            let w = |i: usize| {
+                let t = i as f32 / num_segments as f32;
+                (1.0 - t) * width[0] + t * width[1]
+            };
+            let cp = &control_points[i * 3..i * 3 + 4];
+
+            Curve::split([cp[0], cp[1], cp[2], cp[3]], [w(i), w(i + 1)], kind, split_depth)
         })
+        .map(Shape::Curve)
         .collect()
 }
 
 struct GraphicsState {
     pub transform: Transform3,
+    /// The transform at the end time, things move from `transform` to it while the shutter is open.
+    pub end_transform: Transform3,
+    /// Which of `transform` and `end_transform` the transform directives change.
+    pub active_transforms: [bool; 2],
+    pub transform_times: (f32, f32),
     pub material: Material,
     pub named_materials: HashMap<String, Material>,
     pub named_textures: HashMap<String, UntypedTexture>,
-    pub area_light: Option<Spectrum>,
+    /// The files of the float image textures that are read as luminance, their alpha is used instead
+    /// when they are the `alpha` of a shape.
+    pub float_texture_files: HashMap<String, PathBuf>,
+    /// The alpha textures of the files in `float_texture_files`, loaded when they are first used.
+    pub alpha_textures: HashMap<String, Texture<f32>>,
+    pub area_light: Option<EmissiveMaterial>,
+    /// Light group of the area lights, from the non standard `lightgroup` parameter.
+    pub light_group: Option<String>,
+}
+
+impl GraphicsState {
+    /// Changes the active transforms by `f`.
+    fn update_transforms(&mut self, f: impl Fn(Transform3) -> Transform3) {
+        if self.active_transforms[0] {
+            self.transform = f(self.transform);
+        }
+        if self.active_transforms[1] {
+            self.end_transform = f(self.end_transform);
+        }
+    }
+
+    /// The motion from the start to the end transform, `None` if they are the same.
+    fn motion(&self) -> Option<AnimatedTransform> {
+        let motion = AnimatedTransform::new(
+            self.transform,
+            self.transform_times.0,
+            self.end_transform,
+            self.transform_times.1,
+        );
+
+        motion.is_animated().then_some(motion)
+    }
 }
 
 impl Loader for PbrtLoader {
@@ -215,6 +329,9 @@ impl Loader for PbrtLoader {
 
         let mut state = GraphicsState {
             transform: Transform3::identity(),
+            end_transform: Transform3::identity(),
+            active_transforms: [true, true],
+            transform_times: (0.0, 1.0),
             material: Material::Direct(DirectMaterial::new(Bsdf::Lambertian(Lambertian::new(
                 SpectralTexture::Constant(ConstantTexture::new(Spectrum::from_rgb(0.8, 0.8, 0.8))),
             )))),
@@ -225,7 +342,10 @@ impl Loader for PbrtLoader {
                     Spectrum::from_rgb(0.1, 0.5, 0.1),
                 ))),
             )]),
+            float_texture_files: HashMap::new(),
+            alpha_textures: HashMap::new(),
             area_light: None,
+            light_group: None,
         };
 
         let path_prefix = path.parent().unwrap();
@@ -384,9 +504,62 @@ impl Loader for PbrtLoader {
             }};
         }
 
+        macro_rules! parse_normal_modifier {
+            ($l:ident, $params:ident) => {{
+                if let Some(filename) = $params.get("normalmap") {
+                    let filename = filename.unwrap_string();
+                    let filename = filename.trim().trim_matches('"');
+                    let filename = if !Path::new(filename).is_absolute() {
+                        path_prefix.join(filename)
+                    } else {
+                        Path::new(filename).to_path_buf()
+                    };
+                    NormalModifier::NormalMap(SpectralTexture::Image(
+                        ImageTexture::from_path_linear(&filename),
+                    ))
+                } else if let Some(displacement) = $params.get("displacement") {
+                    if let ParameterValue::Float(_) = displacement {
+                        NormalModifier::Bump(Texture::Constant(ConstantTexture::new(
+                            displacement.unwrap_float_or(0.0),
+                        )))
+                    } else {
+                        let texture_name = displacement.unwrap_string();
+                        let texture_name = texture_name.trim().trim_matches('"');
+                        match state.named_textures.get(texture_name) {
+                            Some(UntypedTexture::Float(texture)) => {
+                                NormalModifier::Bump(texture.clone())
+                            }
+                            _ => panic!("expected float texture for displacement at line {}", $l),
+                        }
+                    }
+                } else {
+                    NormalModifier::None
+                }
+            }};
+        }
+
+        macro_rules! parse_spectral_texture {
+            ($l:ident, $params:ident, $name:expr, $default:expr) => {{
+                match $params.get($name) {
+                    Some(ParameterValue::String(texture_name)) => {
+                        let texture_name = texture_name.trim().trim_matches('"');
+                        match state.named_textures.get(texture_name) {
+                            Some(UntypedTexture::Spectral(texture)) => texture.clone(),
+                            _ => panic!("expected spectral texture for {} at line {}", $name, $l),
+                        }
+                    }
+                    Some(value) => {
+                        SpectralTexture::Constant(ConstantTexture::new(value.unwrap_spectrum()))
+                    }
+                    None => SpectralTexture::Constant(ConstantTexture::new($default)),
+                }
+            }};
+        }
+
         macro_rules! parse_material {
             ($l:ident, $kind:expr, $params:ident) => {{
-                match $kind {
+                let normal = parse_normal_modifier!($l, $params);
+                let material = match $kind {
                     "\"diffuse\"" => {
                         let reflectance =
                             $params.get("reflectance").unwrap_or(&ParameterValue::None);
@@ -471,51 +644,257 @@ impl Loader for PbrtLoader {
                             Spectrum::splat(1.0),
                         )),
                     ))),
+                    "\"diffusetransmission\"" => Some(Material::Direct(DirectMaterial::new(
+                        Bsdf::DiffuseTransmission(DiffuseTransmissionBsdf::new(
+                            parse_spectral_texture!($l, $params, "reflectance", Spectrum::splat(0.25)),
+                            parse_spectral_texture!(
+                                $l,
+                                $params,
+                                "transmittance",
+                                Spectrum::splat(0.25)
+                            ),
+                        )),
+                    ))),
+                    "\"thindielectric\"" => Some(Material::Direct(DirectMaterial::new(
+                        Bsdf::ThinDielectric(ThinDielectricBsdf::new(
+                            $params
+                                .get("eta")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_float_or(1.5),
+                        )),
+                    ))),
                     "\"null\"" => Some(Material::Direct(DirectMaterial::new(Bsdf::Null(
                         NullBsdf::new(),
                     )))),
+                    "\"hair\"" => {
+                        let get_float = |name: &str, default: f32| {
+                            $params
+                                .get(name)
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_float_or(default)
+                        };
+                        let eta = get_float("eta", 1.55);
+                        let beta_m = get_float("beta_m", 0.3);
+                        let beta_n = get_float("beta_n", 0.3);
+                        let alpha = get_float("alpha", 2.0);
+
+                        let bsdf = if let Some(sigma_a) = $params.get("sigma_a") {
+                            HairBsdf::new(sigma_a.unwrap_spectrum(), eta, beta_m, beta_n, alpha)
+                        } else if let Some(reflectance) = $params.get("reflectance") {
+                            HairBsdf::from_reflectance(
+                                reflectance.unwrap_spectrum(),
+                                eta,
+                                beta_m,
+                                beta_n,
+                                alpha,
+                            )
+                        } else {
+                            HairBsdf::from_melanin(
+                                get_float("eumelanin", 1.3),
+                                get_float("pheomelanin", 0.0),
+                                eta,
+                                beta_m,
+                                beta_n,
+                                alpha,
+                            )
+                        };
+
+                        Some(Material::Direct(DirectMaterial::new(Bsdf::Hair(bsdf))))
+                    }
+                    "\"subsurface\"" => {
+                        let reflectance =
+                            $params.get("reflectance").unwrap_or(&ParameterValue::None);
+
+                        let texture = if matches!(
+                            reflectance,
+                            ParameterValue::Spectrum(_) | ParameterValue::Rgb(_)
+                        ) {
+                            UntypedTexture::Spectral(SpectralTexture::Constant(
+                                ConstantTexture::new(reflectance.unwrap_spectrum()),
+                            ))
+                        } else {
+                            let texture_name = reflectance
+                                .unwrap_string_or("__default_spectral_texture".to_owned());
+                            let texture_name = texture_name.trim().trim_matches('"');
+                            state.named_textures.get(texture_name).unwrap().clone()
+                        };
+                        let mfp = match $params.get("mfp") {
+                            Some(mfp) => mfp.unwrap_spectrum(),
+                            None => Spectrum::splat(1.0),
+                        };
+                        match texture {
+                            UntypedTexture::Spectral(texture) => {
+                                Some(Material::Subsurface(SubsurfaceMaterial::new(
+                                    texture.clone(),
+                                    mfp,
+                                    $params
+                                        .get("eta")
+                                        .unwrap_or(&ParameterValue::None)
+                                        .unwrap_float_or(1.33),
+                                )))
+                            }
+                            _ => panic!("expected spectral texture found other at line {}", $l),
+                        }
+                    }
+                    "\"sheen\"" => {
+                        // not part of pbrt, cloth with a diffuse base if it has a `reflectance`
+                        let mut sheen = SheenBsdf::new(
+                            parse_spectral_texture!($l, $params, "color", Spectrum::splat(1.0)),
+                            Texture::Constant(ConstantTexture::new(
+                                $params
+                                    .get("roughness")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(0.3),
+                            )),
+                        );
+                        if $params.contains_key("reflectance") {
+                            sheen = sheen.with_base(Bsdf::Lambertian(Lambertian::new(
+                                parse_spectral_texture!(
+                                    $l,
+                                    $params,
+                                    "reflectance",
+                                    Spectrum::splat(0.5)
+                                ),
+                            )));
+                        }
+                        Some(Material::Direct(DirectMaterial::new(Bsdf::Sheen(sheen))))
+                    }
+                    "\"mix\"" => {
+                        // pbrt mixes two materials by `amount`, any number can be blended here with a
+                        // weight each, only materials with a single bsdf can be part of it
+                        let names = $params
+                            .get("materials")
+                            .unwrap_or(&ParameterValue::None)
+                            .unwrap_string_or(String::new());
+                        let bsdfs = names
+                            .split('"')
+                            .map(str::trim)
+                            .filter(|name| !name.is_empty())
+                            .map(|name| match state.named_materials.get(name) {
+                                Some(Material::Direct(material)) => Some(material.bsdf().clone()),
+                                _ => {
+                                    warnln!("can't mix material '{name}' at line {}", $l);
+                                    None
+                                }
+                            })
+                            .collect::<Option<Vec<_>>>()
+                            .unwrap_or_default();
+
+                        let weights = match $params.get("weights") {
+                            Some(ParameterValue::Float(weights)) => weights.clone(),
+                            _ => {
+                                let amount = $params
+                                    .get("amount")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(0.5);
+                                if bsdfs.len() == 2 {
+                                    vec![1.0 - amount, amount]
+                                } else {
+                                    vec![1.0; bsdfs.len()]
+                                }
+                            }
+                        };
+
+                        if bsdfs.is_empty() {
+                            warnln!("mix has no materials to blend at line {}", $l);
+                            None
+                        } else if weights.len() != bsdfs.len() {
+                            warnln!(
+                                "mix needs a weight for each of its {} materials at line {}",
+                                bsdfs.len(),
+                                $l
+                            );
+                            None
+                        } else {
+                            Some(Material::Blend(BlendMaterial::new(
+                                bsdfs
+                                    .into_iter()
+                                    .zip(weights)
+                                    .map(|(bsdf, weight)| {
+                                        (bsdf, Texture::Constant(ConstantTexture::new(weight)))
+                                    })
+                                    .collect(),
+                            )))
+                        }
+                    }
                     _ => {
                         warnln!("unsupported material kind {} at line {}", $kind, $l);
                         None
                     }
-                }
+                };
+
+                // not part of pbrt, makes single sided geometry look the same from the back, or like
+                // the named `backmaterial`
+                let two_sided = $params
+                    .get("twosided")
+                    .unwrap_or(&ParameterValue::None)
+                    .unwrap_bool_or(false);
+                let material = match material {
+                    Some(Material::Direct(material)) if two_sided => {
+                        let mut two_sided = TwoSidedMaterial::new(material.bsdf().clone());
+                        if let Some(name) = $params.get("backmaterial") {
+                            let name = name.unwrap_string();
+                            let name = name.trim().trim_matches('"');
+                            match state.named_materials.get(name) {
+                                Some(Material::Direct(back)) => {
+                                    two_sided = two_sided.with_back(back.bsdf().clone());
+                                }
+                                _ => warnln!(
+                                    "can't use material '{name}' as the back at line {}",
+                                    $l
+                                ),
+                            }
+                        }
+                        Some(Material::TwoSided(two_sided))
+                    }
+                    Some(material) if two_sided => {
+                        warnln!(
+                            "only materials with a single bsdf can be two sided at line {}",
+                            $l
+                        );
+                        Some(material)
+                    }
+                    material => material,
+                };
+
+                material.map(|material| material.with_normal(normal))
             }};
         }
 
         while let Some((l, t)) = token_iter.next() {
             match t {
                 // transforms
-                "Identity" => state.transform = Transform3::identity(),
+                "Identity" => state.update_transforms(|_| Transform3::identity()),
                 "Translate" => {
-                    state.transform = state.transform
-                        * Transform3::translate(Vector3::new(
-                            parse_next!(),
-                            parse_next!(),
-                            parse_next!(),
-                        ))
+                    let translate = Transform3::translate(Vector3::new(
+                        parse_next!(),
+                        parse_next!(),
+                        parse_next!(),
+                    ));
+                    state.update_transforms(|t| t * translate)
                 }
                 "Scale" => {
-                    state.transform = state.transform
-                        * Transform3::scale(Vector3::new(
-                            parse_next!(),
-                            parse_next!(),
-                            parse_next!(),
-                        ))
+                    let scale = Transform3::scale(Vector3::new(
+                        parse_next!(),
+                        parse_next!(),
+                        parse_next!(),
+                    ));
+                    state.update_transforms(|t| t * scale)
                 }
                 "Rotate" => {
                     let a = parse_next!(f32).to_radians();
-                    state.transform = state.transform
-                        * Transform3::new(Matrix4::from_axis_angle(
-                            Vector3::new(parse_next!(), parse_next!(), parse_next!()),
-                            a,
-                        ))
+                    let rotate = Transform3::new(Matrix4::from_axis_angle(
+                        Vector3::new(parse_next!(), parse_next!(), parse_next!()),
+                        a,
+                    ));
+                    state.update_transforms(|t| t * rotate)
                 }
                 "LookAt" => {
                     let eye = Point3::new(parse_next!(), parse_next!(), parse_next!());
                     let at = Point3::new(parse_next!(), parse_next!(), parse_next!());
                     let up = Vector3::new(parse_next!(), parse_next!(), parse_next!());
-                    state.transform =
-                        state.transform * Transform3::new(Matrix4::look_at_rh(eye, at, up))
+                    let look_at = Transform3::new(Matrix4::look_at_rh(eye, at, up));
+                    state.update_transforms(|t| t * look_at)
                 }
                 "Transform" => {
                     let values = next!();
@@ -523,21 +902,34 @@ impl Loader for PbrtLoader {
                         .split_whitespace()
                         .filter_map(|s| s.parse().ok())
                         .collect::<Vec<_>>();
-                    state.transform =
-                        Transform3::new(Matrix4::from_cols_array(&values.try_into().unwrap()))
+                    let transform =
+                        Transform3::new(Matrix4::from_cols_array(&values.try_into().unwrap()));
+                    state.update_transforms(|_| transform)
                 }
                 "ConcatTransform" => {
-                    state.transform = state.transform
-                        * Transform3::new(
-                            Matrix4::from_cols_array_2d(&[
-                                [parse_next!(), parse_next!(), parse_next!(), parse_next!()],
-                                [parse_next!(), parse_next!(), parse_next!(), parse_next!()],
-                                [parse_next!(), parse_next!(), parse_next!(), parse_next!()],
-                                [parse_next!(), parse_next!(), parse_next!(), parse_next!()],
-                            ])
-                            .transpose(),
-                        )
+                    let transform = Transform3::new(
+                        Matrix4::from_cols_array_2d(&[
+                            [parse_next!(), parse_next!(), parse_next!(), parse_next!()],
+                            [parse_next!(), parse_next!(), parse_next!(), parse_next!()],
+                            [parse_next!(), parse_next!(), parse_next!(), parse_next!()],
+                            [parse_next!(), parse_next!(), parse_next!(), parse_next!()],
+                        ])
+                        .transpose(),
+                    );
+                    state.update_transforms(|t| t * transform)
+                }
+                "ActiveTransform" => {
+                    state.active_transforms = match next!() {
+                        "StartTime" => [true, false],
+                        "EndTime" => [false, true],
+                        "All" => [true, true],
+                        kind => {
+                            warnln!(" unknown active transform {kind} at line {l}");
+                            state.active_transforms
+                        }
+                    }
                 }
+                "TransformTimes" => state.transform_times = (parse_next!(), parse_next!()),
                 // entities
                 "Camera" => {
                     let kind = next!();
@@ -565,7 +957,25 @@ impl Loader for PbrtLoader {
                                     .unwrap_or(&ParameterValue::None)
                                     .unwrap_float_or(0.0),
                                 None,
-                            )));
+                            )
+                            .with_shutter(
+                                params
+                                    .get("shutteropen")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(0.0),
+                                params
+                                    .get("shutterclose")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(1.0),
+                            )
+                            .with_motion(state.motion().map(|_| {
+                                AnimatedTransform::new(
+                                    state.transform.inverse(),
+                                    state.transform_times.0,
+                                    state.end_transform.inverse(),
+                                    state.transform_times.1,
+                                )
+                            }))));
                         }
                         _ => warnln!(" unsupported camera kind {kind} at line {l}"),
                     }
@@ -573,25 +983,98 @@ impl Loader for PbrtLoader {
                 "LightSource" => {
                     let kind = next!();
                     let params = parse_params!();
+                    let light_group = params
+                        .get("lightgroup")
+                        .map(|g| g.unwrap_string().trim().trim_matches('"').to_owned());
+                    sb.light_group(light_group.as_deref());
                     match kind {
                         "\"infinite\"" => {
-                            if params.contains_key("L") {
-                                sb.light(Light::Environment(Environment::new(
-                                    SpectralTexture::Constant(ConstantTexture::new(
-                                        params["L"].unwrap_spectrum(),
-                                    )),
-                                )));
-                            } else if params.contains_key("filename") {
-                                let filename = &params["filename"].unwrap_string();
+                            let scale = params
+                                .get("scale")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_float_or(1.0);
+
+                            // pbrt maps have their poles on z, the poles here are on y
+                            let z_up = Transform3::new(Matrix4::from_mat3(Matrix3::from_cols(
+                                Vector3::new(-1.0, 0.0, 0.0),
+                                Vector3::Z,
+                                Vector3::Y,
+                            )));
+
+                            let sky = params
+                                .get("sky")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_bool_or(false);
+                            let environment = if sky {
+                                // not part of pbrt, daylight with the sun towards `sundirection`
+                                // in the space of the light, the sky is in kcd/m^2 so it usually
+                                // needs a `scale`
+                                let sun_direction = params
+                                    .get("sundirection")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_point3_or(Point3::new(0.0, 1.0, 1.0));
+                                let ground_albedo = params
+                                    .get("groundalbedo")
+                                    .map(|g| g.unwrap_spectrum())
+                                    .unwrap_or(Spectrum::splat(0.3));
+                                let sky = Sky::new(z_up.transform_vector(sun_direction))
+                                    .with_turbidity(
+                                        params
+                                            .get("turbidity")
+                                            .unwrap_or(&ParameterValue::None)
+                                            .unwrap_float_or(3.0),
+                                    )
+                                    .with_ground_albedo(ground_albedo)
+                                    .with_transform(state.transform * z_up)
+                                    .with_scale(scale);
+
+                                if let Some(sun) = sky.sun() {
+                                    sb.light(Light::Distant(sun));
+                                }
+                                sky.build()
+                            } else if let Some(filename) = params.get("filename") {
+                                let filename = filename.unwrap_string();
+                                let filename = filename.trim().trim_matches('"');
                                 let filename = if !Path::new(filename).is_absolute() {
                                     path_prefix.join(filename)
                                 } else {
                                     Path::new(filename).to_path_buf()
                                 };
-                                sb.light(Light::Environment(Environment::new(
-                                    SpectralTexture::Image(ImageTexture::from_path(&filename)),
-                                )));
+                                // pbrt doesn't allow both, `L` is used if the map is missing
+                                let fallback = params
+                                    .get("L")
+                                    .map(|l| l.unwrap_spectrum())
+                                    .unwrap_or(Spectrum::zero());
+                                Environment::from_path_or(&filename, fallback)
+                            } else {
+                                let radiance = params
+                                    .get("L")
+                                    .map(|l| l.unwrap_spectrum())
+                                    .unwrap_or(Spectrum::splat(1.0));
+                                Environment::new(SpectralTexture::Constant(ConstantTexture::new(
+                                    radiance,
+                                )))
+                            };
+
+                            let mut environment = environment
+                                .with_transform(state.transform * z_up)
+                                .with_scale(scale);
+
+                            // pbrt takes a single portal, more are allowed here, four corners each
+                            if let Some(portal) = params.get("portal") {
+                                let corners = portal.unwrap_point3s_or(Point3::ZERO);
+                                if corners.len() % 4 != 0 {
+                                    warnln!("portal needs four corners, got {}", corners.len());
+                                }
+                                for corners in corners.chunks_exact(4) {
+                                    environment = environment.with_portal(
+                                        [corners[0], corners[1], corners[2], corners[3]]
+                                            .map(|p| state.transform.transform_point(p)),
+                                    );
+                                }
                             }
+
+                            sb.light(Light::Environment(Box::new(environment)));
                         }
                         "\"distant\"" => {
                             let to = params["to"].unwrap_point3_or(Point3::new(0.0, 0.0, 1.0));
@@ -613,17 +1096,213 @@ impl Loader for PbrtLoader {
                                     .unwrap_spectrum(),
                             )));
                         }
+                        "\"spot\"" => {
+                            let from = params
+                                .get("from")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_point3_or(Point3::ZERO);
+                            let to = params
+                                .get("to")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_point3_or(Point3::Z);
+                            let get_float = |name: &str, default: f32| {
+                                params
+                                    .get(name)
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(default)
+                            };
+                            let cone_angle = get_float("coneangle", 30.0);
+                            let cone_delta = get_float("conedeltaangle", 5.0);
+                            let intensity = params
+                                .get("I")
+                                .map(|i| i.unwrap_spectrum())
+                                .unwrap_or(Spectrum::splat(1.0));
+
+                            // the cone is along z in the space of the light, the spotlight turns
+                            // directions with its transform and puts its position through it
+                            let p = state.transform.transform_point(from);
+                            let axis = state
+                                .transform
+                                .transform_vector(to - from)
+                                .try_normalize()
+                                .unwrap_or(Vector3::Z);
+                            let rotation = Transform3::new(Matrix4::from_quat(
+                                glam::Quat::from_rotation_arc(axis, Vector3::Z),
+                            ));
+                            let mut light = Spotlight::new(
+                                rotation.transform_point_inv(p),
+                                intensity * get_float("scale", 1.0),
+                                cone_angle,
+                                cone_angle - cone_delta,
+                                Some(rotation),
+                            );
+
+                            // not part of pbrt, an ies profile on top of the falloff of the cone
+                            if let Some(filename) = params.get("profile") {
+                                let filename = filename.unwrap_string();
+                                let filename = filename.trim().trim_matches('"');
+                                let filename = if !Path::new(filename).is_absolute() {
+                                    path_prefix.join(filename)
+                                } else {
+                                    Path::new(filename).to_path_buf()
+                                };
+                                match IesProfile::load_from_file(&filename) {
+                                    Ok(profile) => light = light.with_profile(profile),
+                                    Err(e) => {
+                                        warnln!(" could not load {filename:?} at line {l}: {e}")
+                                    }
+                                }
+                            }
+
+                            sb.light(Light::Spot(light));
+                        }
+                        "\"projection\"" => {
+                            let filename = params
+                                .get("filename")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_string_or(String::new());
+                            let filename = filename.trim().trim_matches('"');
+                            let image = if filename.is_empty() {
+                                SpectralTexture::Constant(ConstantTexture::new(Spectrum::splat(
+                                    1.0,
+                                )))
+                            } else if !Path::new(filename).is_absolute() {
+                                SpectralTexture::Image(ImageTexture::from_path(
+                                    &path_prefix.join(filename),
+                                ))
+                            } else {
+                                SpectralTexture::Image(ImageTexture::from_path(Path::new(filename)))
+                            };
+
+                            let scale = params
+                                .get("scale")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_float_or(1.0);
+                            let intensity = params
+                                .get("I")
+                                .map(|i| i.unwrap_spectrum())
+                                .unwrap_or(Spectrum::splat(1.0));
+                            let mut light = ProjectionLight::new(
+                                state.transform,
+                                image,
+                                intensity * scale,
+                                params
+                                    .get("fov")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(90.0),
+                            );
+                            if let Some(power) = params.get("power") {
+                                light = light.with_power(power.unwrap_float_or(1.0));
+                            }
+                            sb.light(Light::Projection(light));
+                        }
+                        "\"goniometric\"" => {
+                            let filename = params
+                                .get("filename")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_string_or(String::new());
+                            let filename = filename.trim().trim_matches('"');
+                            let filename = if !Path::new(filename).is_absolute() {
+                                path_prefix.join(filename)
+                            } else {
+                                Path::new(filename).to_path_buf()
+                            };
+
+                            // only ies profiles are supported, not pbrt's own images
+                            if !filename
+                                .extension()
+                                .is_some_and(|e| e.eq_ignore_ascii_case("ies"))
+                            {
+                                warnln!(
+                                    " unsupported goniometric profile {filename:?} at line {l}"
+                                );
+                            } else {
+                                match IesProfile::load_from_file(&filename) {
+                                    Ok(profile) => {
+                                        let scale = params
+                                            .get("scale")
+                                            .unwrap_or(&ParameterValue::None)
+                                            .unwrap_float_or(1.0);
+                                        let intensity = params
+                                            .get("I")
+                                            .map(|i| i.unwrap_spectrum())
+                                            .unwrap_or(Spectrum::splat(1.0));
+                                        sb.light(Light::Goniometric(GoniometricLight::new(
+                                            state.transform,
+                                            intensity * scale,
+                                            profile,
+                                        )));
+                                    }
+                                    Err(e) => {
+                                        warnln!(" could not load {filename:?} at line {l}: {e}")
+                                    }
+                                }
+                            }
+                        }
                         _ => warnln!(" unsupported light source kind {kind} at line {l}"),
                     }
+                    sb.light_group(None);
                 }
                 "AreaLightSource" => {
                     let _type = next!();
                     let params = parse_params!();
-                    state.area_light = Some(params.get("L").unwrap().unwrap_spectrum());
+                    let scale = params
+                        .get("scale")
+                        .unwrap_or(&ParameterValue::None)
+                        .unwrap_float_or(1.0);
+                    let radiance = if let Some(filename) = params.get("filename") {
+                        let filename = filename.unwrap_string();
+                        let filename = filename.trim().trim_matches('"');
+                        let filename = if !Path::new(filename).is_absolute() {
+                            path_prefix.join(filename)
+                        } else {
+                            Path::new(filename).to_path_buf()
+                        };
+                        if scale != 1.0 {
+                            warnln!(" scale is ignored for image area lights at line {l}");
+                        }
+                        SpectralTexture::Image(ImageTexture::from_path(&filename))
+                    } else {
+                        let emitted = params
+                            .get("L")
+                            .map(|l| l.unwrap_spectrum())
+                            .unwrap_or(Spectrum::splat(1.0));
+                        SpectralTexture::Constant(ConstantTexture::new(emitted * scale))
+                    };
+
+                    state.light_group = params
+                        .get("lightgroup")
+                        .map(|g| g.unwrap_string().trim().trim_matches('"').to_owned());
+                    let mut emissive = EmissiveMaterial::new(radiance).with_two_sided(
+                        params
+                            .get("twosided")
+                            .unwrap_or(&ParameterValue::None)
+                            .unwrap_bool_or(false),
+                    );
+
+                    // not part of pbrt, an ies profile with its nadir along the normal
+                    if let Some(profile) = params.get("profile") {
+                        let filename = profile.unwrap_string();
+                        let filename = filename.trim().trim_matches('"');
+                        let filename = if !Path::new(filename).is_absolute() {
+                            path_prefix.join(filename)
+                        } else {
+                            Path::new(filename).to_path_buf()
+                        };
+                        match IesProfile::load_from_file(&filename) {
+                            Ok(profile) => emissive = emissive.with_profile(profile),
+                            Err(e) => warnln!(" could not load {filename:?} at line {l}: {e}"),
+                        }
+                    }
+                    state.area_light = Some(emissive);
                 }
                 "Shape" => {
                     let kind = next!();
                     let params = parse_params!();
+                    // moving shapes get their whole transform from the motion, meshes that don't
+                    // move are transformed once for all their triangles
+                    let motion = state.motion();
+                    let mut world_to_object = motion.is_none().then_some(state.transform);
                     let shapes = match kind {
                         "\"sphere\"" => {
                             //
@@ -644,7 +1323,11 @@ impl Loader for PbrtLoader {
                                 Path::new(filename).to_path_buf()
                             };
 
-                            load_ply_to_shape_vec(filename.as_path())
+                            let mut mesh = load_ply_to_mesh(filename.as_path());
+                            if let Some(transform) = world_to_object.take() {
+                                mesh.transform(&transform);
+                            }
+                            mesh.triangles()
                         }
                         "\"trianglemesh\"" => {
                             let uvs = params
@@ -666,47 +1349,393 @@ impl Loader for PbrtLoader {
                                 .unwrap_or(&ParameterValue::None)
                                 .unwrap_ints();
 
-                            indices
-                                .chunks(3)
-                                .map(|idxs| {
-                                    let idxs: [i32; 3] = idxs.try_into().unwrap();
-                                    Shape::Triangle(Triangle::new(
-                                        idxs.map(|i| ps[i as usize]),
-                                        idxs.map(|i| ns[i as usize]),
-                                        idxs.map(|i| uvs[i as usize]),
-                                    ))
-                                })
-                                .collect()
+                            // normals and uvs that are missing come back as a single default
+                            let ns = if ns.len() == ps.len() {
+                                ns.into_iter().map(|n| n.normalize()).collect()
+                            } else {
+                                vec![]
+                            };
+                            let uvs = if uvs.len() == ps.len() { uvs } else { vec![] };
+
+                            let mut mesh = TriangleMesh::new(
+                                ps,
+                                ns,
+                                uvs,
+                                indices.iter().map(|&i| i as u32).collect(),
+                            );
+                            if let Some(transform) = world_to_object.take() {
+                                mesh.transform(&transform);
+                            }
+                            mesh.triangles()
+                        }
+                        "\"disk\"" => {
+                            vec![Shape::Disk(Disk::new(
+                                params
+                                    .get("height")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(0.0),
+                                params
+                                    .get("radius")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(1.0),
+                                params
+                                    .get("innerradius")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(0.0),
+                                params
+                                    .get("phimax")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(360.0)
+                                    .to_radians(),
+                            ))]
+                        }
+                        "\"cylinder\"" => {
+                            vec![Shape::Cylinder(Cylinder::new(
+                                params
+                                    .get("radius")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(1.0),
+                                params
+                                    .get("zmin")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(-1.0),
+                                params
+                                    .get("zmax")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(1.0),
+                                params
+                                    .get("phimax")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(360.0)
+                                    .to_radians(),
+                            ))]
+                        }
+                        "\"cone\"" => {
+                            vec![Shape::Cone(Cone::new(
+                                params
+                                    .get("height")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(1.0),
+                                params
+                                    .get("radius")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(1.0),
+                                params
+                                    .get("phimax")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(360.0)
+                                    .to_radians(),
+                            ))]
+                        }
+                        // `catmullclark` is not in pbrt, it has the number of vertices of every
+                        // face in `nvertices` and is made of quads without it
+                        "\"loopsubdiv\"" | "\"catmullclark\"" => {
+                            let ps = params
+                                .get("P")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_point3s_or(Point3::ZERO);
+                            let uvs = params
+                                .get("uv")
+                                .map(|uv| uv.unwrap_point2s_or(Point2::ZERO))
+                                .unwrap_or_default();
+                            let indices = params
+                                .get("indices")
+                                .map(|i| i.unwrap_ints().clone())
+                                .unwrap_or_default();
+                            let (scheme, face_size) = if kind == "\"loopsubdiv\"" {
+                                (SubdivisionScheme::Loop, 3)
+                            } else {
+                                (SubdivisionScheme::CatmullClark, 4)
+                            };
+                            let face_sizes = params
+                                .get("nvertices")
+                                .map(|n| n.unwrap_ints().clone())
+                                .unwrap_or_else(|| {
+                                    vec![face_size; indices.len() / face_size as usize]
+                                });
+
+                            let index_count = face_sizes
+                                .iter()
+                                .try_fold(0usize, |sum, &n| Some(sum + usize::try_from(n).ok()?));
+                            if index_count != Some(indices.len())
+                                || indices.iter().any(|&i| !(0..ps.len() as i32).contains(&i))
+                                || (!uvs.is_empty() && uvs.len() != ps.len())
+                            {
+                                warnln!(
+                                    " skipping subdivision surface with inconsistent faces or uvs at line {l}"
+                                );
+                                vec![]
+                            } else {
+                                let mut start = 0;
+                                let faces = face_sizes
+                                    .iter()
+                                    .map(|&n| {
+                                        let face = indices[start..start + n as usize]
+                                            .iter()
+                                            .map(|&i| i as u32)
+                                            .collect();
+                                        start += n as usize;
+                                        face
+                                    })
+                                    .collect();
+
+                                let mut subdivision = SubdivisionMesh::new(ps, uvs, faces, scheme)
+                                    .with_levels(
+                                        params
+                                            .get("levels")
+                                            .unwrap_or(&ParameterValue::None)
+                                            .unwrap_int_or(3)
+                                            .max(0) as u32,
+                                    );
+                                let scale = params
+                                    .get("displacementscale")
+                                    .unwrap_or(&ParameterValue::None)
+                                    .unwrap_float_or(1.0);
+                                match params.get("displacement") {
+                                    Some(ParameterValue::String(texture_name)) => {
+                                        let texture_name = texture_name.trim().trim_matches('"');
+                                        match state.named_textures.get(texture_name) {
+                                            Some(UntypedTexture::Float(texture)) => {
+                                                subdivision = subdivision
+                                                    .with_displacement(texture.clone(), scale)
+                                            }
+                                            _ => warnln!(
+                                                " expected float texture for displacement at line {l}"
+                                            ),
+                                        }
+                                    }
+                                    Some(displacement) => {
+                                        subdivision = subdivision.with_displacement(
+                                            Texture::Constant(ConstantTexture::new(
+                                                displacement.unwrap_float_or(0.0),
+                                            )),
+                                            scale,
+                                        )
+                                    }
+                                    None => {}
+                                }
+
+                                let mut mesh = subdivision.build();
+                                if let Some(transform) = world_to_object.take() {
+                                    mesh.transform(&transform);
+                                }
+                                mesh.triangles()
+                            }
+                        }
+                        "\"bilinearmesh\"" => {
+                            let ps = params
+                                .get("P")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_point3s_or(Point3::ZERO);
+                            let indices = params
+                                .get("indices")
+                                .map(|i| i.unwrap_ints().clone())
+                                .unwrap_or_else(|| (0..ps.len() as i32).collect());
+
+                            // patches are ordered p00, p10, p01, p11, flat parallelograms are quads
+                            // and the others are split into two triangles along the p00 to p11
+                            // diagonal
+                            let mut shapes = vec![];
+                            let (mut positions, mut uvs, mut triangles) = (vec![], vec![], vec![]);
+                            for idxs in indices.chunks_exact(4) {
+                                let [p00, p10, p01, p11] =
+                                    [0, 1, 2, 3].map(|i| ps[idxs[i] as usize]);
+                                if (p10 + p01 - p00 - p11).length() <= 1e-4 * (p11 - p00).length()
+                                {
+                                    shapes.push(Shape::Quad(Quad::new(p00, p10 - p00, p01 - p00)));
+                                    continue;
+                                }
+
+                                let first = positions.len() as u32;
+                                positions.extend([p00, p10, p01, p11]);
+                                uvs.extend([
+                                    Point2::new(0.0, 0.0),
+                                    Point2::new(1.0, 0.0),
+                                    Point2::new(0.0, 1.0),
+                                    Point2::new(1.0, 1.0),
+                                ]);
+                                triangles.extend([0, 1, 3, 0, 3, 2].map(|i| first + i));
+                            }
+                            if !triangles.is_empty() {
+                                shapes.extend(
+                                    TriangleMesh::new(positions, vec![], uvs, triangles)
+                                        .triangles(),
+                                );
+                            }
+                            shapes
+                        }
+                        "\"curve\"" => {
+                            let ps = params
+                                .get("P")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_point3s_or(Point3::ZERO);
+                            let ns = params
+                                .get("N")
+                                .map(|n| n.unwrap_point3s_or(Point3::ZERO))
+                                .unwrap_or_default();
+                            let width = params
+                                .get("width")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_float_or(1.0);
+                            let curve_kind = params
+                                .get("type")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_string_or("\"flat\"".to_owned());
+                            let basis = params
+                                .get("basis")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_string_or("\"bezier\"".to_owned());
+                            let degree = params
+                                .get("degree")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_int_or(3);
+
+                            if basis.trim().trim_matches('"') != "bezier" || degree != 3 {
+                                warnln!(
+                                    " only cubic bezier curves are supported, skipping curve at line {l}"
+                                );
+                                vec![]
+                            } else {
+                                load_curve_to_shape_vec(
+                                    &ps,
+                                    [
+                                        params
+                                            .get("width0")
+                                            .unwrap_or(&ParameterValue::None)
+                                            .unwrap_float_or(width),
+                                        params
+                                            .get("width1")
+                                            .unwrap_or(&ParameterValue::None)
+                                            .unwrap_float_or(width),
+                                    ],
+                                    curve_kind.trim().trim_matches('"'),
+                                    &ns,
+                                    params
+                                        .get("splitdepth")
+                                        .unwrap_or(&ParameterValue::None)
+                                        .unwrap_int_or(3)
+                                        .max(0) as u32,
+                                )
+                            }
+                        }
+                        // not in pbrt, the strands of a `.hair` file as curves
+                        "\"hairfile\"" => {
+                            let filename = params
+                                .get("filename")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_string();
+                            let filename = filename.trim().trim_matches('"');
+                            let filename = if !Path::new(filename).is_absolute() {
+                                path_prefix.join(filename)
+                            } else {
+                                Path::new(filename).to_path_buf()
+                            };
+                            let curve_kind = match params
+                                .get("type")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_string_or("\"cylinder\"".to_owned())
+                                .trim()
+                                .trim_matches('"')
+                            {
+                                "flat" => CurveKind::Flat,
+                                _ => CurveKind::Cylinder,
+                            };
+                            let split_depth = params
+                                .get("splitdepth")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_int_or(0)
+                                .max(0) as u32;
+                            let width_scale = params
+                                .get("widthscale")
+                                .unwrap_or(&ParameterValue::None)
+                                .unwrap_float_or(1.0);
+
+                            match load_hair_file(&filename) {
+                                Ok(strands) => strands
+                                    .into_iter()
+                                    .flat_map(|strand| {
+                                        let widths = strand
+                                            .widths
+                                            .iter()
+                                            .map(|w| w * width_scale)
+                                            .collect::<Vec<_>>();
+                                        Curve::from_strand(
+                                            &strand.points,
+                                            &widths,
+                                            curve_kind,
+                                            split_depth,
+                                        )
+                                    })
+                                    .map(Shape::Curve)
+                                    .collect(),
+                                Err(e) => {
+                                    warnln!(
+                                        " couldn't load hair file {} at line {l}: {e}",
+                                        filename.display()
+                                    );
+                                    vec![]
+                                }
+                            }
                         }
                         _ => {
                             warnln!(" unsupported shape kind {kind} at line {l}");
                             vec![]
                         }
                     };
-                    if let Some(radiance) = state.area_light {
-                        sb.area_lights(AreaLight::multi_new(
-                            shapes
-                                .into_iter()
-                                .map(|s| {
-                                    Primitive::new(
-                                        s,
-                                        0,
-                                        None,
-                                        Some(state.transform),
-                                        MediumInterface::none(),
-                                    )
-                                })
-                                .collect(),
-                            radiance,
-                        ));
+                    let alpha = match params.get("alpha") {
+                        Some(ParameterValue::String(texture_name)) => {
+                            let texture_name = texture_name.trim().trim_matches('"');
+                            match state.named_textures.get(texture_name) {
+                                // images with alpha are meant as cutouts
+                                Some(UntypedTexture::Float(_))
+                                    if state.float_texture_files.contains_key(texture_name) =>
+                                {
+                                    let file = &state.float_texture_files[texture_name];
+                                    let texture = state
+                                        .alpha_textures
+                                        .entry(texture_name.to_owned())
+                                        .or_insert_with(|| {
+                                            Texture::Image(
+                                                ImageTexture::from_path_alpha_or_luminance(file),
+                                            )
+                                        });
+                                    Some(texture.clone())
+                                }
+                                Some(UntypedTexture::Float(texture)) => Some(texture.clone()),
+                                _ => {
+                                    warnln!(" expected float texture for alpha at line {l}");
+                                    None
+                                }
+                            }
+                        }
+                        Some(alpha) => {
+                            let alpha = alpha.unwrap_float_or(1.0);
+                            (alpha < 1.0).then(|| Texture::Constant(ConstantTexture::new(alpha)))
+                        }
+                        None => None,
+                    };
+
+                    sb.motion(motion);
+                    sb.alpha(alpha);
+                    if let Some(emissive) = &state.area_light {
+                        sb.light_group(state.light_group.as_deref());
+                        sb.primitives(
+                            shapes,
+                            Material::Emissive(emissive.clone()),
+                            world_to_object,
+                            MediumInterface::none(),
+                        );
+                        sb.light_group(None);
                     } else {
                         sb.primitives(
                             shapes,
                             state.material.clone(),
-                            Some(state.transform),
+                            world_to_object,
                             MediumInterface::none(),
                         );
                     }
+                    sb.motion(None);
+                    sb.alpha(None);
                 }
                 "Material" => {
                     let kind = next!();
@@ -755,20 +1784,41 @@ impl Loader for PbrtLoader {
                         class == "imagemap",
                         "imagemap is the only texture class supported currently. line {l}"
                     );
+                    let filename = params.get("filename").unwrap().unwrap_string();
+                    let filename = filename.trim().trim_matches('"');
+                    let filename = if !Path::new(filename).is_absolute() {
+                        path_prefix.join(filename)
+                    } else {
+                        Path::new(filename).to_path_buf()
+                    };
+                    // a texture that is defined again replaces the old one
+                    state.float_texture_files.remove(name);
+                    state.alpha_textures.remove(name);
                     let texture = match typ {
-                        "spectrum" => {
-                            let filename = params.get("filename").unwrap().unwrap_string();
-                            let filename = filename.trim().trim_matches('"');
-                            let filename = if !Path::new(filename).is_absolute() {
-                                path_prefix.join(filename)
-                            } else {
-                                Path::new(filename).to_path_buf()
-                            };
-                            UntypedTexture::Spectral(SpectralTexture::Image(
-                                ImageTexture::from_path(Path::new(&filename)),
-                            ))
+                        "spectrum" => UntypedTexture::Spectral(SpectralTexture::Image(
+                            ImageTexture::from_path(Path::new(&filename)),
+                        )),
+                        // the non standard `channel` "A" reads the alpha channel, by default only
+                        // the `alpha` of shapes does
+                        "float" => {
+                            let channel = params
+                                .get("channel")
+                                .map(|c| c.unwrap_string().trim().trim_matches('"').to_owned());
+                            UntypedTexture::Float(Texture::Image(match channel.as_deref() {
+                                Some("A") => ImageTexture::from_path_alpha_or_luminance(&filename),
+                                channel => {
+                                    if let Some(channel) = channel {
+                                        warnln!(
+                                            " unsupported texture channel {channel} at line {l}, using the luminance."
+                                        );
+                                    }
+                                    state
+                                        .float_texture_files
+                                        .insert(name.to_owned(), filename.clone());
+                                    ImageTexture::from_path_luminance(&filename)
+                                }
+                            }))
                         }
-                        // TODO: floats
                         _ => {
                             panic!("unsupported image type {typ} at line {l}");
                         }
@@ -778,9 +1828,23 @@ impl Loader for PbrtLoader {
                     // dbg!(name, typ, class, params);
                     // panic!();
                 }
+                // instancing, the shapes of objects keep the transform they were defined with and the
+                // transform of every instance is applied on top of it
+                "ObjectBegin" => {
+                    let name = next!().trim_matches('"');
+                    sb.begin_object(name);
+                }
+                "ObjectEnd" => {
+                    sb.end_object();
+                }
+                "ObjectInstance" => {
+                    let name = next!().trim_matches('"');
+                    sb.instance(name, state.transform);
+                }
                 "WorldBegin" => {
                     //TODO: rest of the stuff that should happen here..
-                    state.transform = Transform3::identity()
+                    state.transform = Transform3::identity();
+                    state.end_transform = Transform3::identity();
                 }
                 // "Accelerator" | "CoordinateSystem" | "CoordSysTransform" | "Include" | "Import"
                 _ => {
@@ -801,3 +1865,224 @@ impl Loader for PbrtLoader {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::aggregates::{Aggregate, AggregateT};
+    use crate::lights::LightT;
+    use crate::primitive::Interaction;
+
+    use super::*;
+
+    fn load(name: &str, contents: &str) -> SceneBuilder {
+        let path =
+            std::env::temp_dir().join(format!("luminiferous_{name}_{}.pbrt", std::process::id()));
+        fs::write(&path, contents).unwrap();
+
+        let mut sb = SceneBuilder::new();
+        PbrtLoader::load_from_file(
+            &mut sb,
+            &path,
+            SceneCreationParams {
+                extent: UExtent2::new(4, 4),
+            },
+        );
+        fs::remove_file(path).unwrap();
+        sb
+    }
+
+    #[test]
+    fn empty_objects() {
+        // area lights are skipped in objects, which leaves the second object empty too
+        let sb = load(
+            "empty_objects",
+            r#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "perspective" "float fov" [45]
+            WorldBegin
+            LightSource "point" "rgb l" [1 1 1]
+            Shape "sphere" "float radius" [1]
+            ObjectBegin "empty"
+            ObjectEnd
+            ObjectInstance "empty"
+            AreaLightSource "diffuse" "rgb L" [1 1 1]
+            ObjectBegin "lights"
+            Shape "sphere" "float radius" [1]
+            ObjectEnd
+            ObjectInstance "lights"
+            "#,
+        );
+
+        let scene = sb.build().unwrap();
+        assert!(matches!(scene.aggregate, Aggregate::Bvh(_)));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vector3::NEG_Z);
+        assert!(scene.aggregate.intersect_p(ray).0.is_some());
+    }
+
+    #[test]
+    fn mix_materials() {
+        let sb = load(
+            "mix_materials",
+            r#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "perspective" "float fov" [45]
+            WorldBegin
+            LightSource "point" "rgb l" [1 1 1]
+            MakeNamedMaterial "red" "string type" ["diffuse"] "rgb reflectance" [0.8 0.1 0.1]
+            MakeNamedMaterial "glass" "string type" ["dielectric"] "float eta" [1.5]
+            MakeNamedMaterial "blue" "string type" ["diffuse"] "rgb reflectance" [0.1 0.1 0.8]
+            Material "mix" "string materials" ["red" "glass"] "float amount" [0.3]
+            Shape "sphere" "float radius" [1]
+            Material "mix" "string materials" ["red" "glass" "blue"] "float weights" [1 2 3]
+            Shape "sphere" "float radius" [1]
+            Material "mix" "string materials" ["red" "missing"]
+            Shape "sphere" "float radius" [1]
+            "#,
+        );
+
+        // the last mix falls back to the previous material
+        let scene = sb.build().unwrap();
+        let blends = scene
+            .materials
+            .iter()
+            .filter(|material| matches!(material, Material::Blend(_)))
+            .count();
+        assert_eq!(blends, 3);
+    }
+
+    #[test]
+    fn two_sided_materials() {
+        let sb = load(
+            "two_sided_materials",
+            r#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "perspective" "float fov" [45]
+            WorldBegin
+            LightSource "point" "rgb l" [1 1 1]
+            MakeNamedMaterial "back" "string type" ["diffuse"] "rgb reflectance" [0.1 0.1 0.8]
+            Material "diffuse" "rgb reflectance" [0.8 0.1 0.1] "bool twosided" true
+            Shape "sphere" "float radius" [1]
+            Material "dielectric" "bool twosided" true "string backmaterial" ["back"]
+            Shape "sphere" "float radius" [1]
+            Material "diffuse" "rgb reflectance" [0.8 0.1 0.1]
+            Shape "sphere" "float radius" [1]
+            "#,
+        );
+
+        let scene = sb.build().unwrap();
+        let two_sided = scene
+            .materials
+            .iter()
+            .map(|material| matches!(material, Material::TwoSided(_)))
+            .collect::<Vec<_>>();
+        assert_eq!(two_sided, [true, true, false]);
+    }
+
+    #[test]
+    fn sheen_materials() {
+        let sb = load(
+            "sheen_materials",
+            r#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "perspective" "float fov" [45]
+            WorldBegin
+            LightSource "point" "rgb l" [1 1 1]
+            Material "sheen" "rgb color" [0.9 0.9 0.9] "float roughness" [0.5]
+            Shape "sphere" "float radius" [1]
+            Material "sheen" "rgb reflectance" [0.8 0.1 0.1]
+            Shape "sphere" "float radius" [1]
+            "#,
+        );
+
+        let scene = sb.build().unwrap();
+        assert_eq!(scene.materials.len(), 2);
+        for material in &scene.materials {
+            assert!(
+                matches!(material, Material::Direct(m) if matches!(m.bsdf(), Bsdf::Sheen(_))),
+                "{material:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn sky() {
+        let sb = load(
+            "sky",
+            r#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "perspective" "float fov" [45]
+            WorldBegin
+            LightSource "infinite" "bool sky" true "vector3 sundirection" [1 0 1] "float scale" [0.1]
+            LightSource "infinite" "bool sky" true "vector3 sundirection" [0 0 -1]
+            Shape "sphere" "float radius" [1]
+            "#,
+        );
+
+        // a sky with its sun, and a sky after sunset without one
+        let scene = sb.build().unwrap();
+        assert_eq!(scene.lights.len(), 3);
+        assert!(matches!(scene.lights[0], Light::Distant(_)));
+        assert!(scene.lights[0].l_e(Vector3::new(1.0, 0.0, 1.0).normalize()).y() > 0.0);
+        assert!(matches!(scene.lights[1], Light::Environment(_)));
+        assert!(matches!(scene.lights[2], Light::Environment(_)));
+
+        // z is up in the space of pbrt lights, so the ground is below the xy plane
+        let l_e = |x, y, z| scene.lights[1].l_e(Vector3::new(x, y, z).normalize()).y();
+        let ground = l_e(0.6, 0.3, -0.5);
+        assert!((l_e(0.2, -0.3, -0.9) - ground).abs() < 1e-3 * ground);
+        assert!((l_e(0.2, -0.3, 0.9) - l_e(0.6, 0.3, 0.5)).abs() > 1e-2 * ground);
+
+        // the sun has set on the second sky
+        assert!(scene.lights[2].l_e(Vector3::Z).is_black());
+    }
+
+    #[test]
+    fn spotlights() {
+        // even up to 10 degrees from the nadir and dark beyond
+        let profile =
+            std::env::temp_dir().join(format!("luminiferous_spot_{}.ies", std::process::id()));
+        fs::write(
+            &profile,
+            "IESNA:LM-63-2002\nTILT=NONE\n1 1000 1.0 4 1 1 2 0.1 0.1 0.0\n1.0 1.0 20\n0 10 10.01 90\n0\n100 100 0 0\n",
+        )
+        .unwrap();
+
+        let sb = load(
+            "spotlights",
+            &format!(
+                r#"
+                LookAt 0 0 5  0 0 0  0 1 0
+                Camera "perspective" "float fov" [45]
+                WorldBegin
+                Translate 0 1 0
+                LightSource "spot" "point3 from" [0 0 0] "point3 to" [1 0 0] "rgb I" [1 1 1]
+                LightSource "spot" "point3 from" [0 0 0] "point3 to" [1 0 0] "rgb I" [1 1 1]
+                    "string profile" "{}"
+                Shape "sphere" "float radius" [1]
+                "#,
+                profile.display()
+            ),
+        );
+        fs::remove_file(profile).unwrap();
+
+        let scene = sb.build().unwrap();
+        assert_eq!(scene.lights.len(), 2);
+        let interaction = |x: f32, z: f32| Interaction {
+            p: Point3::new(x, 1.0, z),
+            n: Normal3::X,
+            wi: Vector3::X,
+            time: 0.0,
+        };
+
+        // the cone points along x from where it was placed, and the profile narrows it
+        for light in &scene.lights {
+            let sample = light.sample_li(&interaction(2.0, 0.0), Point2::ZERO);
+            assert!(sample.li.y() > 0.0);
+            assert!(sample.visibility.end.distance(Point3::Y) < 1e-4);
+            assert!(light.sample_li(&interaction(0.0, 2.0), Point2::ZERO).li.is_black());
+        }
+        let off_axis = interaction(2.0, 2.0 * 20f32.to_radians().tan());
+        assert!(scene.lights[0].sample_li(&off_axis, Point2::ZERO).li.y() > 0.0);
+        assert!(scene.lights[1].sample_li(&off_axis, Point2::ZERO).li.is_black());
+    }
+}
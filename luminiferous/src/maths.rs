@@ -10,7 +10,13 @@ pub use bounds::*;
 mod transform;
 pub use transform::*;
 
+mod animated_transform;
+pub use animated_transform::*;
+
 mod frame;
 pub use frame::*;
 
+mod distribution;
+pub use distribution::*;
+
 pub mod warp;
@@ -0,0 +1,155 @@
+use glam::Quat;
+
+use super::{linear_types::*, Bounds3, Transform3};
+
+/// Number of times a moving box is sampled at to find the bounds of its motion.
+const MOTION_BOUNDS_STEPS: usize = 64;
+
+/// A transform moving from `start` at `start_time` to `end` at `end_time`. In between the two are
+/// interpolated by their translation, rotation and scale, so the transforms can't have any shear.
+#[derive(Debug, Clone, Copy)]
+pub struct AnimatedTransform {
+    start: Transform3,
+    end: Transform3,
+    start_time: f32,
+    end_time: f32,
+    /// The scale, rotation and translation of both transforms.
+    decomposed: [(Vector3, Quat, Vector3); 2],
+}
+
+impl AnimatedTransform {
+    pub fn new(start: Transform3, start_time: f32, end: Transform3, end_time: f32) -> Self {
+        Self {
+            start,
+            end,
+            start_time,
+            end_time,
+            decomposed: [start, end].map(|t| t.matrix().to_scale_rotation_translation()),
+        }
+    }
+
+    /// Whether the transform changes over time at all.
+    #[inline]
+    pub fn is_animated(&self) -> bool {
+        self.end_time > self.start_time && self.start.matrix() != self.end.matrix()
+    }
+
+    /// The transform at the start of the motion.
+    #[inline]
+    pub fn start(&self) -> Transform3 {
+        self.start
+    }
+
+    /// The transform at `time`, which stays at the keyframes outside of them.
+    pub fn at(&self, time: f32) -> Transform3 {
+        if !self.is_animated() || time <= self.start_time {
+            return self.start;
+        }
+        if time >= self.end_time {
+            return self.end;
+        }
+
+        let t = (time - self.start_time) / (self.end_time - self.start_time);
+        let [(s0, r0, t0), (s1, r1, t1)] = self.decomposed;
+
+        Transform3::new(Matrix4::from_scale_rotation_translation(
+            s0.lerp(s1, t),
+            r0.slerp(r1, t),
+            t0.lerp(t1, t),
+        ))
+    }
+
+    /// The bounds of `bounds` over the whole motion. The box is bounded at a number of times, and the
+    /// bounds are padded by how far the points can stray from the straight lines between those times, so
+    /// they hold for rotations too.
+    pub fn motion_bounds(&self, bounds: Bounds3) -> Bounds3 {
+        if !self.is_animated() {
+            return self.start.transform_bounds(bounds);
+        }
+
+        let sampled =
+            (1..=MOTION_BOUNDS_STEPS).fold(self.start.transform_bounds(bounds), |b, i| {
+                let time = self.start_time
+                    + (self.end_time - self.start_time) * i as f32 / MOTION_BOUNDS_STEPS as f32;
+                b.union(self.at(time).transform_bounds(bounds))
+            });
+
+        let [(s0, r0, _), (s1, r1, _)] = self.decomposed;
+        let half_step_angle = 0.5 * r0.angle_between(r1) / MOTION_BOUNDS_STEPS as f32;
+        if half_step_angle == 0.0 {
+            // the translation and scale move every point along a straight line
+            return sampled;
+        }
+
+        // A point `x` is at `T(t) + R(t) S(t) x`. The translation is linear, between two of the times
+        // the rotated point is at most the sagitta of its arc from its chord, and the change of scale
+        // adds `(R1 - R0)(v1 - v0) / 4` on top.
+        let (mut radius, mut scale_change) = (0.0f32, 0.0f32);
+        for x in (0..8).map(|i| bounds.corner(i)) {
+            radius = radius.max((s0.abs().max(s1.abs()) * x).length());
+            scale_change = scale_change.max(((s1 - s0) * x).length() / MOTION_BOUNDS_STEPS as f32);
+        }
+        let padding =
+            radius * (1.0 - half_step_angle.cos()) + 0.5 * half_step_angle.sin() * scale_change;
+
+        sampled.pad(padding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    use super::*;
+
+    #[test]
+    fn interpolates_between_keyframes() {
+        let motion = AnimatedTransform::new(
+            Transform3::identity(),
+            1.0,
+            Transform3::translate(Vector3::new(2.0, 0.0, 0.0))
+                * Transform3::rotate(Point3::new(0.0, 0.0, FRAC_PI_2)),
+            3.0,
+        );
+
+        let p = motion.at(2.0).transform_point(Point3::X);
+        let expected = Point3::new(1.0 + FRAC_PI_4.cos(), FRAC_PI_4.sin(), 0.0);
+        assert!(p.distance(expected) < 1e-5, "{p} != {expected}");
+
+        // the keyframes are held outside of the motion
+        assert!(
+            motion
+                .at(0.0)
+                .transform_point(Point3::X)
+                .distance(Point3::X)
+                < 1e-5
+        );
+        let end = Point3::new(2.0, 1.0, 0.0);
+        assert!(motion.at(4.0).transform_point(Point3::X).distance(end) < 1e-5);
+    }
+
+    #[test]
+    fn motion_bounds_contain_rotations() {
+        let bounds = Bounds3::new(Point3::new(1.0, -0.1, -0.1), Point3::new(1.2, 0.1, 0.1));
+        let motion = AnimatedTransform::new(
+            Transform3::scale(Vector3::splat(0.5)),
+            0.0,
+            Transform3::translate(Vector3::new(0.0, 0.0, 1.0))
+                * Transform3::rotate(Point3::new(0.0, 0.0, PI - 0.01))
+                * Transform3::scale(Vector3::splat(2.0)),
+            1.0,
+        );
+        let motion_bounds = motion.motion_bounds(bounds);
+
+        for i in 0..=10_000 {
+            let transform = motion.at(i as f32 / 10_000.0);
+            for corner in 0..8 {
+                let p = transform.transform_point(bounds.corner(corner));
+                assert!(
+                    p.cmpge(motion_bounds.min).all() && p.cmple(motion_bounds.max).all(),
+                    "{p} is outside of {motion_bounds:?}"
+                );
+            }
+        }
+    }
+}
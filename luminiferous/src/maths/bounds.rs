@@ -64,6 +64,15 @@ impl Bounds3 {
         }
     }
 
+    /// One of the 8 corners, bit `c` of `i` picks between min and max on axis `c`.
+    pub fn corner(&self, i: usize) -> Point3 {
+        Point3::select(
+            glam::BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+            self.max,
+            self.min,
+        )
+    }
+
     pub fn centroid(&self) -> Point3 {
         (self.min + self.max) * 0.5
     }
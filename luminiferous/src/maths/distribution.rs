@@ -0,0 +1,310 @@
+use crate::prelude::*;
+
+/// A piecewise constant distribution over `[0, 1)`, or over the indices of its values when sampled
+/// discretely.
+#[derive(Debug, Clone)]
+pub struct Distribution1d {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    func_int: f32,
+}
+
+impl Distribution1d {
+    /// Creates a distribution proportional to the absolute value of `func`, which falls back to a uniform
+    /// distribution if `func` is zero everywhere.
+    pub fn new(func: &[f32]) -> Self {
+        let n = func.len();
+        let func = func.iter().map(|f| f.abs()).collect::<Vec<_>>();
+
+        let mut cdf = vec![0.0; n + 1];
+        for i in 1..=n {
+            cdf[i] = cdf[i - 1] + func[i - 1] / n as f32;
+        }
+
+        let func_int = cdf[n];
+        if func_int == 0.0 {
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f32 / n as f32;
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= func_int;
+            }
+        }
+
+        Self {
+            func,
+            cdf,
+            func_int,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.func.is_empty()
+    }
+
+    /// The integral of the function the distribution was created from.
+    #[inline]
+    pub fn integral(&self) -> f32 {
+        self.func_int
+    }
+
+    /// Finds the last index whose cdf is at most `u`.
+    #[inline]
+    fn find_interval(&self, u: f32) -> usize {
+        self.cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.len() - 1)
+    }
+
+    /// Samples an index, returns the index and its probability.
+    pub fn sample_discrete(&self, u: f32) -> (usize, f32) {
+        let i = self.find_interval(u);
+        (i, self.discrete_pmf(i))
+    }
+
+    /// Probability of sampling index `i` with `sample_discrete`.
+    #[inline]
+    pub fn discrete_pmf(&self, i: usize) -> f32 {
+        self.cdf[i + 1] - self.cdf[i]
+    }
+
+    /// Samples a point in `[0, 1)`, returns the point, its density and the index of the segment it is in.
+    pub fn sample_continuous(&self, u: f32) -> (f32, f32, usize) {
+        let i = self.find_interval(u);
+
+        let mut du = u - self.cdf[i];
+        let width = self.cdf[i + 1] - self.cdf[i];
+        if width > 0.0 {
+            du /= width;
+        }
+
+        let x = ((i as f32 + du) / self.len() as f32).min(1.0 - f32::EPSILON);
+        (x, self.pdf_segment(i), i)
+    }
+
+    /// Density of sampling `x` with `sample_continuous`.
+    #[inline]
+    pub fn pdf(&self, x: f32) -> f32 {
+        let i = ((x * self.len() as f32) as usize).min(self.len() - 1);
+        self.pdf_segment(i)
+    }
+
+    #[inline]
+    fn pdf_segment(&self, i: usize) -> f32 {
+        if self.func_int > 0.0 {
+            self.func[i] / self.func_int
+        } else {
+            1.0
+        }
+    }
+}
+
+/// A piecewise constant distribution over `[0, 1)^2`, sampled by picking a row from the marginal
+/// distribution and then a column from the distribution of that row.
+#[derive(Debug, Clone)]
+pub struct Distribution2d {
+    conditional: Vec<Distribution1d>,
+    marginal: Distribution1d,
+}
+
+impl Distribution2d {
+    /// Creates a distribution from `width * height` values stored row by row.
+    pub fn new(func: &[f32], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+
+        let conditional = func
+            .chunks_exact(width)
+            .map(Distribution1d::new)
+            .collect::<Vec<_>>();
+        let marginal =
+            Distribution1d::new(&conditional.iter().map(|c| c.integral()).collect::<Vec<_>>());
+
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Samples a point, returns the point and its density.
+    pub fn sample_continuous(&self, u: Point2) -> (Point2, f32) {
+        let (y, pdf_y, row) = self.marginal.sample_continuous(u.y);
+        let (x, pdf_x, _) = self.conditional[row].sample_continuous(u.x);
+
+        (Point2::new(x, y), pdf_x * pdf_y)
+    }
+
+    /// Density of sampling `p` with `sample_continuous`.
+    pub fn pdf(&self, p: Point2) -> f32 {
+        let row = ((p.y * self.marginal.len() as f32) as usize).min(self.marginal.len() - 1);
+        self.marginal.pdf(p.y) * self.conditional[row].pdf(p.x)
+    }
+}
+
+/// A piecewise constant distribution over `[0, 1)^2` that can be sampled within any rectangle of it. The
+/// function is integrated over the rectangle with a summed area table, so it doesn't need to be rebuilt for
+/// every window.
+#[derive(Debug, Clone)]
+pub struct WindowedDistribution2d {
+    func: Vec<f32>,
+    /// Integral of the function from the origin to every grid point, `(width + 1) * (height + 1)` values.
+    sum: Vec<f64>,
+    width: usize,
+    height: usize,
+}
+
+impl WindowedDistribution2d {
+    /// Creates a distribution from `width * height` values stored row by row.
+    pub fn new(func: &[f32], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+
+        let func = func.iter().map(|f| f.abs()).collect::<Vec<_>>();
+        let cell_area = 1.0 / (width * height) as f64;
+        let stride = width + 1;
+
+        let mut sum = vec![0.0f64; stride * (height + 1)];
+        for y in 0..height {
+            for x in 0..width {
+                sum[(y + 1) * stride + x + 1] = func[y * width + x] as f64 * cell_area
+                    + sum[y * stride + x + 1]
+                    + sum[(y + 1) * stride + x]
+                    - sum[y * stride + x];
+            }
+        }
+
+        Self {
+            func,
+            sum,
+            width,
+            height,
+        }
+    }
+
+    /// Integral from the origin to `p`. The table is bilinear within each cell, so interpolating it is exact.
+    fn lookup(&self, p: Point2) -> f64 {
+        let x = p.x.clamp(0.0, 1.0) * self.width as f32;
+        let y = p.y.clamp(0.0, 1.0) * self.height as f32;
+        let (x0, y0) = (
+            (x as usize).min(self.width - 1),
+            (y as usize).min(self.height - 1),
+        );
+        let (tx, ty) = ((x - x0 as f32) as f64, (y - y0 as f32) as f64);
+
+        let stride = self.width + 1;
+        let s = |x: usize, y: usize| self.sum[y * stride + x];
+        (1.0 - ty) * ((1.0 - tx) * s(x0, y0) + tx * s(x0 + 1, y0))
+            + ty * ((1.0 - tx) * s(x0, y0 + 1) + tx * s(x0 + 1, y0 + 1))
+    }
+
+    fn window_integral(&self, min: Point2, max: Point2) -> f64 {
+        (self.lookup(max)
+            - self.lookup(Point2::new(min.x, max.y))
+            - self.lookup(Point2::new(max.x, min.y))
+            + self.lookup(min))
+        .max(0.0)
+    }
+
+    /// The integral of the function over the rectangle from `min` to `max`.
+    #[inline]
+    pub fn integral(&self, min: Point2, max: Point2) -> f32 {
+        self.window_integral(min, max) as f32
+    }
+
+    /// Samples a point within the rectangle from `min` to `max`, returns the point and its density. `None`
+    /// if the function is zero over the whole rectangle.
+    pub fn sample_continuous(&self, u: Point2, min: Point2, max: Point2) -> Option<(Point2, f32)> {
+        let total = self.window_integral(min, max);
+        if total <= 0.0 {
+            return None;
+        }
+
+        // the integral up to y over the width of the window is piecewise linear with kinks at the rows
+        let y = invert_piecewise_linear(
+            |y| self.window_integral(min, Point2::new(max.x, y)),
+            u.y as f64 * total,
+            min.y,
+            max.y,
+            self.height,
+        );
+
+        // then pick x along the row y is in
+        let row = ((y * self.height as f32) as usize).min(self.height - 1);
+        let (row_min, row_max) = (
+            row as f32 / self.height as f32,
+            (row + 1) as f32 / self.height as f32,
+        );
+        let row_integral =
+            |x: f32| self.window_integral(Point2::new(min.x, row_min), Point2::new(x, row_max));
+        let x = invert_piecewise_linear(
+            row_integral,
+            u.x as f64 * row_integral(max.x),
+            min.x,
+            max.x,
+            self.width,
+        );
+
+        let p = Point2::new(x, y);
+        Some((p, (self.eval(p) as f64 / total) as f32))
+    }
+
+    /// Density of sampling `p` with `sample_continuous` within the rectangle from `min` to `max`.
+    pub fn pdf(&self, p: Point2, min: Point2, max: Point2) -> f32 {
+        if p.cmplt(min).any() || p.cmpgt(max).any() {
+            return 0.0;
+        }
+
+        let total = self.window_integral(min, max);
+        if total > 0.0 {
+            (self.eval(p) as f64 / total) as f32
+        } else {
+            0.0
+        }
+    }
+
+    #[inline]
+    fn eval(&self, p: Point2) -> f32 {
+        let x = ((p.x * self.width as f32) as usize).min(self.width - 1);
+        let y = ((p.y * self.height as f32) as usize).min(self.height - 1);
+        self.func[y * self.width + x]
+    }
+}
+
+/// Finds where the increasing piecewise linear `f`, with kinks at multiples of `1 / n`, reaches `target`
+/// between `lo` and `hi`.
+fn invert_piecewise_linear(f: impl Fn(f32) -> f64, target: f64, lo: f32, hi: f32, n: usize) -> f32 {
+    let point = |k: usize| (k as f32 / n as f32).clamp(lo, hi);
+
+    // binary search for the first kink past the target, the last one is at hi which is always past it
+    let (mut first, mut last) = (
+        (lo * n as f32) as usize + 1,
+        (hi * n as f32).ceil() as usize,
+    );
+    last = last.max(first);
+    let start = first;
+    while first < last {
+        let mid = (first + last) / 2;
+        if f(point(mid)) >= target {
+            last = mid;
+        } else {
+            first = mid + 1;
+        }
+    }
+
+    let a = if first == start { lo } else { point(first - 1) };
+    let b = point(first);
+    let (fa, fb) = (f(a), f(b));
+    let t = if fb > fa {
+        ((target - fa) / (fb - fa)).clamp(0.0, 1.0) as f32
+    } else {
+        0.0
+    };
+
+    a + t * (b - a)
+}
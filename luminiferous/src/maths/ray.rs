@@ -4,12 +4,21 @@ use super::linear_types::*;
 pub struct Ray {
     pub o: Point3,
     pub d: Vector3,
+    /// The time the ray is traced at, for things that move while the shutter is open.
+    pub time: f32,
 }
 
 impl Ray {
+    /// A ray traced at time 0, rays that should see moving things get their time from [`Ray::with_time`].
     pub fn new(o: Point3, d: Vector3) -> Self {
-        Self { o, d }
+        Self { o, d, time: 0.0 }
     }
+
+    pub fn with_time(mut self, time: f32) -> Self {
+        self.time = time;
+        self
+    }
+
     pub fn at(&self, t: f32) -> Point3 {
         self.o + t * self.d
     }
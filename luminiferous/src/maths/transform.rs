@@ -41,6 +41,11 @@ impl Transform3 {
         Self::new(m)
     }
 
+    #[inline]
+    pub fn matrix(&self) -> Matrix4 {
+        self.matrix
+    }
+
     pub fn inverse(&self) -> Self {
         Self {
             matrix: self.inverse,
@@ -48,6 +53,17 @@ impl Transform3 {
         }
     }
 
+    /// Whether the transform keeps the angles between directions, which it does if it only rotates,
+    /// reflects, translates and scales uniformly.
+    pub fn keeps_angles(&self) -> bool {
+        let m = Matrix3::from_mat4(self.matrix);
+        let mtm = m.transpose() * m;
+        let scale_squared = mtm.x_axis.x;
+
+        scale_squared > 0.0
+            && mtm.abs_diff_eq(Matrix3::IDENTITY * scale_squared, 1e-4 * scale_squared)
+    }
+
     pub fn transform_point(&self, p: Point3) -> Point3 {
         let transformed = self.matrix * p.extend(1.0);
         if transformed.w == 1.0 {
@@ -71,7 +87,7 @@ impl Transform3 {
         let o = self.transform_point(r.o);
         let d = self.transform_vector(r.d);
 
-        Ray::new(o, d)
+        Ray::new(o, d).with_time(r.time)
     }
 
     pub fn transform_point_inv(&self, p: Point3) -> Point3 {
@@ -97,7 +113,7 @@ impl Transform3 {
         let o = self.transform_point_inv(r.o);
         let d = self.transform_vector_inv(r.d);
 
-        Ray::new(o, d)
+        Ray::new(o, d).with_time(r.time)
     }
 
     pub fn transform_bounds(&self, bounds: Bounds3) -> Bounds3 {
@@ -108,8 +124,8 @@ impl Transform3 {
 
         for i in 0..3 {
             for j in 0..3 {
-                let a = self.matrix.col(i)[j] * bounds.min[j];
-                let b = self.matrix.col(i)[j] * bounds.max[j];
+                let a = self.matrix.col(j)[i] * bounds.min[j];
+                let b = self.matrix.col(j)[i] * bounds.max[j];
                 if a < b {
                     min[i] += a;
                     max[i] += b;
@@ -138,6 +154,7 @@ impl Transform3 {
             uv: si.uv,
             dp_du: self.transform_normal(si.dp_du),
             dp_dv: self.transform_normal(si.dp_dv),
+            time: si.time,
         }
     }
 }
@@ -172,3 +189,26 @@ impl Transform2 {
         (self.matrix * v.extend(0.0)).truncate()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use core::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn bounds_follow_rotations() {
+        let transform = Transform3::translate(Vector3::new(0.0, 0.0, 3.0))
+            * Transform3::rotate(Point3::new(0.0, 0.0, FRAC_PI_2));
+        let bounds = transform.transform_bounds(Bounds3::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 1.0, 1.0),
+        ));
+
+        let expected = Bounds3::new(Point3::new(-1.0, 0.0, 3.0), Point3::new(0.0, 2.0, 4.0));
+        assert!(
+            bounds.min.distance(expected.min) < 1e-5 && bounds.max.distance(expected.max) < 1e-5,
+            "{bounds:?} != {expected:?}"
+        );
+    }
+}
@@ -39,6 +39,19 @@ pub fn square_to_uniform_sphere(u: Point2) -> Vector3 {
     Vector3::new(r * c, r * s, z)
 }
 
+/// Uniform directions in the cone around `+z` with one minus the cosine of its half angle
+/// `one_minus_cos_theta_max`, which keeps narrow cones precise.
+pub fn square_to_uniform_cone(u: Point2, one_minus_cos_theta_max: f32) -> Vector3 {
+    let one_minus_cos_theta = u.x * one_minus_cos_theta_max;
+    let z = 1.0 - one_minus_cos_theta;
+    let r = (one_minus_cos_theta * (2.0 - one_minus_cos_theta))
+        .max(0.0)
+        .sqrt();
+    let (s, c) = (2.0 * core::f32::consts::PI * u.y).sin_cos();
+
+    Vector3::new(r * c, r * s, z)
+}
+
 /// Returns (b0, b1), b2 = 1.0 - b0 - b1.
 pub fn square_to_barycentric(u: Point2) -> (f32, f32) {
     let su0 = u.x.sqrt();
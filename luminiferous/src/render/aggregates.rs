@@ -6,6 +6,9 @@ pub use vector::*;
 mod bvh;
 pub use bvh::*;
 
+mod instance;
+pub use instance::*;
+
 use crate::prelude::*;
 use crate::primitive::{Intersection, SurfaceInteraction};
 
@@ -26,4 +29,6 @@ pub trait AggregateT {
 pub enum Aggregate {
     Vector(Vector),
     Bvh(Bvh),
+    /// A bvh over instances of objects that each have their own bvh.
+    Instanced(Bvh<Instance>),
 }
@@ -30,9 +30,28 @@ struct BvhNode {
     node_type: BvhNodeType,
 }
 
-pub struct Bvh {
+/// Something a [`Bvh`] can be built over.
+pub trait BvhPrimitive: Clone {
+    fn make_bounds(&self) -> Bounds3;
+
+    fn intersect(&self, ray: Ray) -> Option<Intersection<'_>>;
+}
+
+impl BvhPrimitive for Primitive {
+    #[inline]
+    fn make_bounds(&self) -> Bounds3 {
+        Primitive::make_bounds(self)
+    }
+
+    #[inline]
+    fn intersect(&self, ray: Ray) -> Option<Intersection<'_>> {
+        Primitive::intersect(self, ray)
+    }
+}
+
+pub struct Bvh<P = Primitive> {
     nodes: Vec<BvhNode>,
-    primitives: Vec<Primitive>,
+    primitives: Vec<P>,
 }
 
 #[derive(Debug)]
@@ -47,10 +66,10 @@ pub enum SplitMethod {
     Sah,
 }
 
-impl Bvh {
+impl<P: BvhPrimitive> Bvh<P> {
     /// Generate the optimal SAH Bvh from a set of primitives by brute force.
     /// Mostly just here for comparison and because its easy to implement.
-    pub fn new_by_brute_force(primitives: Vec<Primitive>) -> Self {
+    pub fn new_by_brute_force(primitives: Vec<P>) -> Self {
         let mut nodes = primitives
             .iter()
             .enumerate()
@@ -105,8 +124,8 @@ impl Bvh {
     fn recursive_split(
         nodes: &mut Vec<BvhNode>,
         primitive_info: &mut Vec<PrimitiveInfo>,
-        primitives: &[Primitive],
-        ordered_primitives: &mut Vec<Primitive>,
+        primitives: &[P],
+        ordered_primitives: &mut Vec<P>,
         start: usize,
         end: usize,
     ) -> usize {
@@ -259,7 +278,7 @@ impl Bvh {
         nodes.len() - 1
     }
 
-    pub fn new_by_recursive_split(primitives: Vec<Primitive>) -> Self {
+    pub fn new_by_recursive_split(primitives: Vec<P>) -> Self {
         let mut primitive_infos = primitives
             .iter()
             .enumerate()
@@ -292,20 +311,20 @@ impl Bvh {
         }
     }
 
-    pub fn new(primitives: Vec<Primitive>) -> Self {
-        if primitives.len() * 2 - 1 > u32::MAX as usize {
-            panic!(
-                "too many primitives, the maximum number of supported primitives is 2^31. Found {}",
-                primitives.len()
-            );
-        }
-
+    pub fn new(primitives: Vec<P>) -> Self {
         if primitives.is_empty() {
             return Self {
                 primitives,
                 nodes: vec![],
             };
         }
+
+        if primitives.len() * 2 - 1 > u32::MAX as usize {
+            panic!(
+                "too many primitives, the maximum number of supported primitives is 2^31. Found {}",
+                primitives.len()
+            );
+        }
         //NOTE: just here for testing since programmatically constructing scenes can leave them in a weird state
         // let mut rand = oorandom::Rand32::new(0);
         // primitives.sort_by_cached_key(|_| rand.rand_u32());
@@ -315,7 +334,7 @@ impl Bvh {
 
         STATS
             .primitive_memory
-            .add((ret.primitives.len() * size_of::<Primitive>()) as u64);
+            .add((ret.primitives.len() * size_of::<P>()) as u64);
         STATS
             .aggregate_memory
             .add((ret.nodes.len() * size_of::<BvhNode>() + size_of::<Self>()) as u64);
@@ -324,7 +343,7 @@ impl Bvh {
     }
 }
 
-impl AggregateT for Bvh {
+impl<P: BvhPrimitive> AggregateT for Bvh<P> {
     fn intersect_p(&self, ray: Ray) -> (Option<Intersection>, usize) {
         if self.nodes.is_empty() {
             return (None, 0);
@@ -0,0 +1,42 @@
+use std::mem::size_of;
+use std::sync::Arc;
+
+use crate::prelude::*;
+use crate::primitive::Intersection;
+
+use super::{AggregateT, Bvh, BvhPrimitive};
+
+/// A transformed copy of an object, all instances of the object share its primitives and its bvh.
+#[derive(Clone)]
+pub struct Instance {
+    object: Arc<Bvh>,
+    world_to_object: Transform3,
+}
+
+impl Instance {
+    pub fn new(object: Arc<Bvh>, world_to_object: Transform3) -> Self {
+        STATS.aggregate_memory.add(size_of::<Self>() as u64);
+
+        Self {
+            object,
+            world_to_object,
+        }
+    }
+}
+
+impl BvhPrimitive for Instance {
+    fn make_bounds(&self) -> Bounds3 {
+        self.world_to_object.transform_bounds(self.object.bounds())
+    }
+
+    fn intersect(&self, ray: Ray) -> Option<Intersection<'_>> {
+        let (intersection, _) = self
+            .object
+            .intersect_p(self.world_to_object.transform_ray_inv(ray));
+
+        intersection.map(|i| Intersection {
+            instance: Some(&self.world_to_object),
+            ..i
+        })
+    }
+}
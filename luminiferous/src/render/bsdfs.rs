@@ -25,6 +25,18 @@ pub use measured::*;
 mod null;
 pub use null::*;
 
+mod hair;
+pub use hair::*;
+
+mod diffuse_transmission;
+pub use diffuse_transmission::*;
+
+mod thin_dielectric;
+pub use thin_dielectric::*;
+
+mod sheen;
+pub use sheen::*;
+
 mod util;
 pub use util::*;
 
@@ -50,9 +62,16 @@ bitflags! {
         const DeltaReflection = 1 << 4;
         const DeltaTransmission = 1 << 5;
 
+        const GlossyTransmission = 1 << 6;
+
+        // the path continues with a random walk below the surface
+        const Subsurface = 1 << 7;
+
+        const DiffuseTransmission = 1 << 8;
+
         // Compound
-        const Diffuse = Self::DiffuseReflection.bits();
-        const Glossy = Self::GlossyReflection.bits();
+        const Diffuse = Self::DiffuseReflection.bits() | Self::DiffuseTransmission.bits();
+        const Glossy = Self::GlossyReflection.bits() | Self::GlossyTransmission.bits();
         const Smooth = Self::Diffuse.bits() | Self::Glossy.bits();
         const Delta = Self::DeltaReflection.bits() | Self::DeltaTransmission.bits();
     }
@@ -64,6 +83,9 @@ pub trait BsdfT {
 
     fn eval(&self, si: &SurfaceInteraction, wi: Vector3, wo: Vector3) -> Spectrum;
 
+    /// Solid angle density of `sample` returning `wo`, zero for delta lobes.
+    fn pdf(&self, si: &SurfaceInteraction, wi: Vector3, wo: Vector3) -> f32;
+
     fn flags(&self) -> BsdfFlags;
 }
 
@@ -77,4 +99,11 @@ pub enum Bsdf {
     Null(NullBsdf),
     Plastic(PlasticBsdf),
     Conductor(ConductorBsdf),
+    Hair(HairBsdf),
+    DiffuseTransmission(DiffuseTransmissionBsdf),
+    ThinDielectric(ThinDielectricBsdf),
+    Sheen(SheenBsdf),
 }
+
+#[cfg(test)]
+mod test;
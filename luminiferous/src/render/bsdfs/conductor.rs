@@ -28,6 +28,10 @@ impl BsdfT for ConductorBsdf {
         Spectrum::zero()
     }
 
+    fn pdf(&self, _si: &SurfaceInteraction, _wi: Vector3, _wo: Vector3) -> f32 {
+        0.0
+    }
+
     fn sample(&self, wi: Vector3, si: &SurfaceInteraction, _u1: f32, _u2: Point2) -> BsdfSample {
         let wo = reflect(wi);
 
@@ -32,6 +32,10 @@ impl BsdfT for Dielectric {
         Spectrum::zero()
     }
 
+    fn pdf(&self, _si: &SurfaceInteraction, _wi: Vector3, _wo: Vector3) -> f32 {
+        0.0
+    }
+
     fn sample(&self, wi: Vector3, _si: &SurfaceInteraction, u1: f32, u2: Point2) -> BsdfSample {
         let cos_theta_i = Frame3::cos_theta(wi);
         // let entering = cos_theta_i >= 0.0;
@@ -0,0 +1,89 @@
+use crate::prelude::*;
+use crate::{
+    primitive::SurfaceInteraction,
+    spectra::{Spectrum, SpectrumT},
+    stats::STATS,
+    textures::{SpectralTexture, TextureT},
+};
+
+use super::{BsdfFlags, BsdfSample, BsdfT};
+
+/// Lambertian reflection on the side of `wi` and lambertian transmission to the other side, for thin
+/// surfaces like leaves and paper.
+#[derive(Debug, Clone)]
+pub struct DiffuseTransmissionBsdf {
+    reflectance: SpectralTexture,
+    transmittance: SpectralTexture,
+}
+
+impl DiffuseTransmissionBsdf {
+    pub fn new(reflectance: SpectralTexture, transmittance: SpectralTexture) -> Self {
+        STATS.bsdfs_created.inc();
+
+        Self {
+            reflectance,
+            transmittance,
+        }
+    }
+
+    /// Returns the probability of sampling reflection over transmission.
+    fn reflection_probability(r: Spectrum, t: Spectrum) -> f32 {
+        let pr = r.to_rgb().into_iter().fold(0.0, f32::max);
+        let pt = t.to_rgb().into_iter().fold(0.0, f32::max);
+        if pr + pt <= 0.0 {
+            return 0.5;
+        }
+
+        pr / (pr + pt)
+    }
+}
+
+impl BsdfT for DiffuseTransmissionBsdf {
+    fn sample(&self, wi: Vector3, si: &SurfaceInteraction, u1: f32, u2: Point2) -> BsdfSample {
+        let r = self.reflectance.eval(si);
+        let t = self.transmittance.eval(si);
+        let pr = Self::reflection_probability(r, t);
+
+        let mut wo = warp::square_to_cosine_hemisphere(u2);
+        let (spectrum, sampled) = if u1 < pr {
+            (r / pr, BsdfFlags::DiffuseReflection)
+        } else {
+            wo.z = -wo.z;
+            (t / (1.0 - pr), BsdfFlags::DiffuseTransmission)
+        };
+
+        // keep reflection on the same side as wi
+        if Frame3::cos_theta(wi) < 0.0 {
+            wo.z = -wo.z;
+        }
+
+        BsdfSample {
+            wo,
+            sampled,
+            spectrum,
+        }
+    }
+
+    fn eval(&self, si: &SurfaceInteraction, wi: Vector3, wo: Vector3) -> Spectrum {
+        if Frame3::cos_theta(wi) * Frame3::cos_theta(wo) > 0.0 {
+            self.reflectance.eval(si) * core::f32::consts::FRAC_1_PI
+        } else {
+            self.transmittance.eval(si) * core::f32::consts::FRAC_1_PI
+        }
+    }
+
+    fn pdf(&self, si: &SurfaceInteraction, wi: Vector3, wo: Vector3) -> f32 {
+        let pr = Self::reflection_probability(self.reflectance.eval(si), self.transmittance.eval(si));
+        let cos_theta = Frame3::cos_theta(wo).abs() * core::f32::consts::FRAC_1_PI;
+
+        if Frame3::cos_theta(wi) * Frame3::cos_theta(wo) > 0.0 {
+            pr * cos_theta
+        } else {
+            (1.0 - pr) * cos_theta
+        }
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::DiffuseReflection | BsdfFlags::DiffuseTransmission
+    }
+}
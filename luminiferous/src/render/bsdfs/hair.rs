@@ -0,0 +1,412 @@
+// adapted from pbrt-v3's implementation of "A Practical and Controllable Hair and Fur Model for
+// Production Path Tracing" (Chiang et al. 2016) https://pbr-book.org/3ed-2018/Reflection_Models/Hair_BSDF
+
+use crate::prelude::*;
+use crate::{
+    primitive::SurfaceInteraction,
+    spectra::{Spectrum, SpectrumT},
+};
+
+use super::{fresnel_dielectric, BsdfFlags, BsdfSample, BsdfT};
+
+/// Number of explicitly modeled scattering lobes (R, TT, TRT), everything past that is lumped into one.
+const P_MAX: usize = 3;
+
+const SQRT_PI_OVER_8: f32 = 0.626_657_07;
+
+const EUMELANIN_SIGMA_A: [f32; 3] = [0.419, 0.697, 1.37];
+const PHEOMELANIN_SIGMA_A: [f32; 3] = [0.187, 0.4, 1.05];
+
+/// A fiber scattering model for hair and fur. The shading frame is expected to have `x` along the fiber
+/// and `z` along the surface normal, and the `v` coordinate of the interaction to go across the fiber.
+#[derive(Debug, Clone)]
+pub struct HairBsdf {
+    eta: f32,
+    sigma_a: Spectrum,
+    /// longitudinal variance per lobe
+    v: [f32; P_MAX + 1],
+    /// azimuthal logistic scale
+    s: f32,
+    sin_2k_alpha: [f32; 3],
+    cos_2k_alpha: [f32; 3],
+}
+
+impl HairBsdf {
+    /// `beta_m` and `beta_n` are the longitudinal and azimuthal roughness in [0, 1], `alpha` is the
+    /// angle of the cuticle scales in degrees.
+    pub fn new(sigma_a: Spectrum, eta: f32, beta_m: f32, beta_n: f32, alpha: f32) -> Self {
+        STATS.bsdfs_created.inc();
+
+        let beta_m = beta_m.clamp(0.0, 1.0);
+        let beta_n = beta_n.clamp(0.0, 1.0);
+
+        let mut v = [0.0; P_MAX + 1];
+        v[0] = (0.726 * beta_m + 0.812 * beta_m.powi(2) + 3.7 * beta_m.powi(20)).powi(2);
+        v[1] = 0.25 * v[0];
+        v[2] = 4.0 * v[0];
+        let v2 = v[2];
+        v[3..].fill(v2);
+
+        let s = SQRT_PI_OVER_8
+            * (0.265 * beta_n + 1.194 * beta_n.powi(2) + 5.372 * beta_n.powi(22));
+
+        let mut sin_2k_alpha = [alpha.to_radians().sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [(1.0 - sin_2k_alpha[0].powi(2)).max(0.0).sqrt(), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+
+        Self {
+            eta,
+            sigma_a,
+            v,
+            s,
+            sin_2k_alpha,
+            cos_2k_alpha,
+        }
+    }
+
+    /// Creates a hair bsdf whose absorption is given by the concentration of the eumelanin (brown/black)
+    /// and pheomelanin (red/yellow) pigments. Realistic values are in the range of roughly [0, 8].
+    pub fn from_melanin(
+        eumelanin: f32,
+        pheomelanin: f32,
+        eta: f32,
+        beta_m: f32,
+        beta_n: f32,
+        alpha: f32,
+    ) -> Self {
+        Self::new(
+            Self::sigma_a_from_melanin(eumelanin, pheomelanin),
+            eta,
+            beta_m,
+            beta_n,
+            alpha,
+        )
+    }
+
+    /// Creates a hair bsdf that approximately has the color `reflectance` after multiple scattering.
+    pub fn from_reflectance(
+        reflectance: Spectrum,
+        eta: f32,
+        beta_m: f32,
+        beta_n: f32,
+        alpha: f32,
+    ) -> Self {
+        Self::new(
+            Self::sigma_a_from_reflectance(reflectance, beta_n),
+            eta,
+            beta_m,
+            beta_n,
+            alpha,
+        )
+    }
+
+    pub fn sigma_a_from_melanin(eumelanin: f32, pheomelanin: f32) -> Spectrum {
+        let [r, g, b] = [0, 1, 2]
+            .map(|i| eumelanin * EUMELANIN_SIGMA_A[i] + pheomelanin * PHEOMELANIN_SIGMA_A[i]);
+
+        Spectrum::from_rgb(r, g, b)
+    }
+
+    pub fn sigma_a_from_reflectance(reflectance: Spectrum, beta_n: f32) -> Spectrum {
+        let denom = 5.969 - 0.215 * beta_n + 2.532 * beta_n.powi(2) - 10.73 * beta_n.powi(3)
+            + 5.574 * beta_n.powi(4)
+            + 0.245 * beta_n.powi(5);
+
+        let [r, g, b] = reflectance
+            .to_rgb()
+            .map(|c| (c.max(1e-4).ln() / denom).powi(2));
+
+        Spectrum::from_rgb(r, g, b)
+    }
+
+    /// Offset across the fiber in [-1, 1], from where the curve was hit along its width. The shading normal
+    /// of flat curves always faces the ray, so it can't be found from the incident direction.
+    #[inline]
+    fn h(si: &SurfaceInteraction) -> f32 {
+        (2.0 * si.uv.y - 1.0).clamp(-1.0, 1.0)
+    }
+
+    /// Returns (sin_theta, cos_theta) of `sin_theta`, `cos_theta` rotated by the scale tilt for lobe `p`.
+    #[inline]
+    fn tilt(&self, p: usize, sin_theta: f32, cos_theta: f32) -> (f32, f32) {
+        let (sin_theta_p, cos_theta_p) = match p {
+            0 => (
+                sin_theta * self.cos_2k_alpha[1] - cos_theta * self.sin_2k_alpha[1],
+                cos_theta * self.cos_2k_alpha[1] + sin_theta * self.sin_2k_alpha[1],
+            ),
+            1 => (
+                sin_theta * self.cos_2k_alpha[0] + cos_theta * self.sin_2k_alpha[0],
+                cos_theta * self.cos_2k_alpha[0] - sin_theta * self.sin_2k_alpha[0],
+            ),
+            2 => (
+                sin_theta * self.cos_2k_alpha[2] + cos_theta * self.sin_2k_alpha[2],
+                cos_theta * self.cos_2k_alpha[2] - sin_theta * self.sin_2k_alpha[2],
+            ),
+            _ => (sin_theta, cos_theta),
+        };
+
+        (sin_theta_p, cos_theta_p.abs())
+    }
+
+    /// Returns the attenuation of each lobe and gamma_t, the refracted azimuthal offset.
+    fn attenuation(&self, sin_theta_i: f32, cos_theta_i: f32, h: f32) -> ([Spectrum; P_MAX + 1], f32) {
+        let sin_theta_t = sin_theta_i / self.eta;
+        let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).max(0.0).sqrt();
+
+        let etap = (self.eta * self.eta - sin_theta_i * sin_theta_i).sqrt() / cos_theta_i;
+        let sin_gamma_t = (h / etap).clamp(-1.0, 1.0);
+        let cos_gamma_t = (1.0 - sin_gamma_t * sin_gamma_t).max(0.0).sqrt();
+
+        let t = (-self.sigma_a * (2.0 * cos_gamma_t / cos_theta_t)).exp();
+
+        let cos_gamma_i = (1.0 - h * h).max(0.0).sqrt();
+        let f = fresnel_dielectric(cos_theta_i * cos_gamma_i, self.eta);
+
+        let mut ap = [Spectrum::zero(); P_MAX + 1];
+        ap[0] = Spectrum::splat(f);
+        ap[1] = (1.0 - f).powi(2) * t;
+        for p in 2..P_MAX {
+            ap[p] = ap[p - 1] * t * f;
+        }
+        ap[P_MAX] = ap[P_MAX - 1] * t * f / (Spectrum::splat(1.0) - t * f);
+
+        (ap, sin_gamma_t.asin())
+    }
+
+    fn lobe_pdfs(&self, sin_theta_i: f32, cos_theta_i: f32, h: f32) -> [f32; P_MAX + 1] {
+        let (ap, _) = self.attenuation(sin_theta_i, cos_theta_i, h);
+        let sum = ap.iter().map(|a| a.y()).sum::<f32>();
+
+        if sum <= 0.0 {
+            return [(P_MAX as f32 + 1.0).recip(); P_MAX + 1];
+        }
+
+        ap.map(|a| a.y() / sum)
+    }
+}
+
+#[inline]
+fn i0(x: f32) -> f32 {
+    let mut val = 0.0;
+    let mut x2i = 1.0;
+    let mut ifact = 1.0;
+    let mut i4 = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            ifact *= i as f32;
+        }
+        val += x2i / (i4 * ifact * ifact);
+        x2i *= x * x;
+        i4 *= 4.0;
+    }
+
+    val
+}
+
+#[inline]
+fn log_i0(x: f32) -> f32 {
+    if x > 12.0 {
+        x + 0.5 * (-(core::f32::consts::TAU).ln() + x.recip().ln() + (8.0 * x).recip())
+    } else {
+        i0(x).ln()
+    }
+}
+
+/// Longitudinal scattering function.
+#[inline]
+fn mp(cos_theta_i: f32, cos_theta_o: f32, sin_theta_i: f32, sin_theta_o: f32, v: f32) -> f32 {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+
+    if v <= 0.1 {
+        (log_i0(a) - b - v.recip() + core::f32::consts::LN_2 + (2.0 * v).recip().ln()).exp()
+    } else {
+        ((-b).exp() * i0(a)) / ((v.recip()).sinh() * 2.0 * v)
+    }
+}
+
+#[inline]
+fn phi(p: usize, gamma_i: f32, gamma_t: f32) -> f32 {
+    2.0 * p as f32 * gamma_t - 2.0 * gamma_i + p as f32 * core::f32::consts::PI
+}
+
+#[inline]
+fn logistic(x: f32, s: f32) -> f32 {
+    let x = x.abs();
+    (-x / s).exp() / (s * (1.0 + (-x / s).exp()).powi(2))
+}
+
+#[inline]
+fn logistic_cdf(x: f32, s: f32) -> f32 {
+    (1.0 + (-x / s).exp()).recip()
+}
+
+#[inline]
+fn trimmed_logistic(x: f32, s: f32, a: f32, b: f32) -> f32 {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+#[inline]
+fn sample_trimmed_logistic(u: f32, s: f32, a: f32, b: f32) -> f32 {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * ((u * k + logistic_cdf(a, s)).recip() - 1.0).ln();
+
+    x.clamp(a, b)
+}
+
+/// Azimuthal scattering function.
+#[inline]
+fn np(dphi: f32, p: usize, s: f32, gamma_i: f32, gamma_t: f32) -> f32 {
+    let mut dphi = dphi - phi(p, gamma_i, gamma_t);
+    while dphi > core::f32::consts::PI {
+        dphi -= core::f32::consts::TAU;
+    }
+    while dphi < -core::f32::consts::PI {
+        dphi += core::f32::consts::TAU;
+    }
+
+    trimmed_logistic(dphi, s, -core::f32::consts::PI, core::f32::consts::PI)
+}
+
+impl BsdfT for HairBsdf {
+    fn eval(&self, si: &SurfaceInteraction, wi: Vector3, wo: Vector3) -> Spectrum {
+        let h = Self::h(si);
+        let gamma_i = h.asin();
+
+        let sin_theta_i = wi.x;
+        let cos_theta_i = (1.0 - sin_theta_i * sin_theta_i).max(0.0).sqrt();
+        let phi_i = wi.z.atan2(wi.y);
+
+        let sin_theta_o = wo.x;
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(0.0).sqrt();
+        let phi_o = wo.z.atan2(wo.y);
+
+        let (ap, gamma_t) = self.attenuation(sin_theta_i, cos_theta_i, h);
+        let dphi = phi_o - phi_i;
+
+        let mut f = Spectrum::zero();
+        for (p, ap) in ap.iter().enumerate().take(P_MAX) {
+            let (sin_theta_ip, cos_theta_ip) = self.tilt(p, sin_theta_i, cos_theta_i);
+            f += *ap
+                * mp(cos_theta_o, cos_theta_ip, sin_theta_o, sin_theta_ip, self.v[p])
+                * np(dphi, p, self.s, gamma_i, gamma_t);
+        }
+        f += ap[P_MAX]
+            * mp(cos_theta_o, cos_theta_i, sin_theta_o, sin_theta_i, self.v[P_MAX])
+            * core::f32::consts::FRAC_1_PI
+            * 0.5;
+
+        // the integrator applies the cosine term, which is not part of this model
+        let cos_theta = Frame3::cos_theta(wo).abs();
+        if cos_theta > 0.0 {
+            f /= cos_theta;
+        }
+
+        f
+    }
+
+    fn sample(&self, wi: Vector3, si: &SurfaceInteraction, u1: f32, u2: Point2) -> BsdfSample {
+        let h = Self::h(si);
+        let gamma_i = h.asin();
+
+        let sin_theta_i = wi.x;
+        let cos_theta_i = (1.0 - sin_theta_i * sin_theta_i).max(0.0).sqrt();
+        let phi_i = wi.z.atan2(wi.y);
+
+        // choose a lobe and then reuse the remainder of u1 for the azimuthal offset
+        let ap_pdf = self.lobe_pdfs(sin_theta_i, cos_theta_i, h);
+        let mut u_lobe = u1;
+        let mut p = 0;
+        while p < P_MAX {
+            if u_lobe < ap_pdf[p] {
+                break;
+            }
+            u_lobe -= ap_pdf[p];
+            p += 1;
+        }
+        let u_lobe = (u_lobe / ap_pdf[p]).clamp(0.0, 1.0 - f32::EPSILON);
+
+        let (sin_theta_ip, cos_theta_ip) = self.tilt(p, sin_theta_i, cos_theta_i);
+
+        let u_m = u2.x.max(1e-5);
+        let cos_theta = 1.0 + self.v[p] * (u_m + (1.0 - u_m) * (-2.0 / self.v[p]).exp()).ln();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let cos_phi = (core::f32::consts::TAU * u2.y).cos();
+        let sin_theta_o = -cos_theta * sin_theta_ip + sin_theta * cos_phi * cos_theta_ip;
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(0.0).sqrt();
+
+        let etap = (self.eta * self.eta - sin_theta_i * sin_theta_i).sqrt() / cos_theta_i;
+        let gamma_t = (h / etap).clamp(-1.0, 1.0).asin();
+        let dphi = if p < P_MAX {
+            phi(p, gamma_i, gamma_t)
+                + sample_trimmed_logistic(
+                    u_lobe,
+                    self.s,
+                    -core::f32::consts::PI,
+                    core::f32::consts::PI,
+                )
+        } else {
+            core::f32::consts::TAU * u_lobe
+        };
+        let phi_o = phi_i + dphi;
+
+        let wo = Vector3::new(
+            sin_theta_o,
+            cos_theta_o * phi_o.cos(),
+            cos_theta_o * phi_o.sin(),
+        );
+
+        let pdf = self.pdf(si, wi, wo);
+        let spectrum = if pdf > 0.0 {
+            self.eval(si, wi, wo) * Frame3::cos_theta(wo).abs() / pdf
+        } else {
+            Spectrum::zero()
+        };
+
+        BsdfSample {
+            wo,
+            sampled: self.flags(),
+            spectrum,
+        }
+    }
+
+    fn pdf(&self, si: &SurfaceInteraction, wi: Vector3, wo: Vector3) -> f32 {
+        let h = Self::h(si);
+        let gamma_i = h.asin();
+
+        let sin_theta_i = wi.x;
+        let cos_theta_i = (1.0 - sin_theta_i * sin_theta_i).max(0.0).sqrt();
+        let phi_i = wi.z.atan2(wi.y);
+
+        let sin_theta_o = wo.x;
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(0.0).sqrt();
+        let phi_o = wo.z.atan2(wo.y);
+
+        let etap = (self.eta * self.eta - sin_theta_i * sin_theta_i).sqrt() / cos_theta_i;
+        let gamma_t = (h / etap).clamp(-1.0, 1.0).asin();
+
+        let ap_pdf = self.lobe_pdfs(sin_theta_i, cos_theta_i, h);
+        let dphi = phi_o - phi_i;
+
+        let mut pdf = 0.0;
+        for (p, ap_pdf) in ap_pdf.iter().enumerate().take(P_MAX) {
+            let (sin_theta_ip, cos_theta_ip) = self.tilt(p, sin_theta_i, cos_theta_i);
+            pdf += mp(cos_theta_o, cos_theta_ip, sin_theta_o, sin_theta_ip, self.v[p])
+                * ap_pdf
+                * np(dphi, p, self.s, gamma_i, gamma_t);
+        }
+        pdf += mp(cos_theta_o, cos_theta_i, sin_theta_o, sin_theta_i, self.v[P_MAX])
+            * ap_pdf[P_MAX]
+            * core::f32::consts::FRAC_1_PI
+            * 0.5;
+
+        pdf
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::GlossyReflection | BsdfFlags::GlossyTransmission
+    }
+}
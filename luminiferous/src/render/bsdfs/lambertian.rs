@@ -42,6 +42,10 @@ impl BsdfT for Lambertian {
         self.reflectance.eval(si) * core::f32::consts::FRAC_1_PI
     }
 
+    fn pdf(&self, _si: &SurfaceInteraction, _wi: Vector3, wo: Vector3) -> f32 {
+        Frame3::cos_theta(wo).max(0.0) * core::f32::consts::FRAC_1_PI
+    }
+
     fn flags(&self) -> BsdfFlags {
         BsdfFlags::DiffuseReflection
     }
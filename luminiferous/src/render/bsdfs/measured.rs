@@ -10,7 +10,7 @@ use std::{
 };
 
 use crate::prelude::*;
-use crate::spectra::SpectrumT;
+use crate::spectra::{cie_xyz, SpectrumT};
 use crate::{primitive::SurfaceInteraction, spectra::Spectrum, stats::STATS};
 
 use super::{reflect_across, spherical_theta, BsdfFlags, BsdfSample, BsdfT};
@@ -507,6 +507,7 @@ where
 }
 
 pub type Warp2D0 = Marginal2d<0>;
+pub type Warp2D1 = Marginal2d<1>;
 pub type Warp2D2 = Marginal2d<2>;
 pub type Warp2D3 = Marginal2d<3>;
 
@@ -694,14 +695,47 @@ struct MeasuredBrdfData {
     pub jacobian: bool,
 }
 
-#[derive(Debug, Clone)]
-pub struct MeasuredBsdf {
-    //TODO: this should be in a centralized store for coherency reasons
-    //NOTE: this is boxed so the size of Bsdf is smaller, maybe it will become unboxed if other bsdfs are larger.
-    data: Box<MeasuredBrdfData>,
+/// Integrates the spectral samples of an RGL tensor (`[phi_i, theta_i, wavelength, y, x]`) against the CIE
+/// color matching functions, producing the layout of the rgb variant (`[phi_i, theta_i, channel, y, x]`).
+fn spectra_to_rgb(data: &[f32], shape: &[usize], wavelengths: &[f32]) -> Vec<f32> {
+    let n_wavelengths = wavelengths.len();
+    let slice_size = shape[3] * shape[4];
+
+    // trapezoidal weights, normalized so a constant spectrum of 1 has a luminance of 1
+    let mut weights = vec![0.0; n_wavelengths];
+    for i in 0..n_wavelengths {
+        let lo = wavelengths[i.saturating_sub(1)];
+        let hi = wavelengths[(i + 1).min(n_wavelengths - 1)];
+        weights[i] = 0.5 * (hi - lo);
+    }
+    let cie = wavelengths.iter().map(|&l| cie_xyz(l)).collect::<Vec<_>>();
+    let y_integral = (0..n_wavelengths).map(|i| cie[i][1] * weights[i]).sum::<f32>();
+
+    let mut rgb = vec![0.0; shape[0] * shape[1] * 3 * slice_size];
+    for slice in 0..shape[0] * shape[1] {
+        let spectra = &data[slice * n_wavelengths * slice_size..];
+        let out = &mut rgb[slice * 3 * slice_size..];
+
+        for j in 0..slice_size {
+            let mut xyz = [0.0; 3];
+            for i in 0..n_wavelengths {
+                let v = spectra[i * slice_size + j] * weights[i] / y_integral;
+                xyz[0] += v * cie[i][0];
+                xyz[1] += v * cie[i][1];
+                xyz[2] += v * cie[i][2];
+            }
+
+            let c = Spectrum::from_xyz(xyz[0], xyz[1], xyz[2]).to_rgb();
+            for k in 0..3 {
+                out[k * slice_size + j] = c[k];
+            }
+        }
+    }
+
+    rgb
 }
 
-impl MeasuredBsdf {
+impl MeasuredBrdfData {
     pub fn load_from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
         let tf = TensorFile::load_from_file(path)?;
         let theta_i = tf
@@ -712,7 +746,12 @@ impl MeasuredBsdf {
         let ndf = tf.fields.get("ndf").ok_or("bad tensor file, no ndf")?;
         let sigma = tf.fields.get("sigma").ok_or("bad tensor file, no sigma")?;
         let vndf = tf.fields.get("vndf").ok_or("bad tensor file, no vndf")?;
-        let rgb = tf.fields.get("rgb").ok_or("bad tensor file, no rgb")?;
+        let spectra = tf.fields.get("spectra");
+        let rgb = tf
+            .fields
+            .get("rgb")
+            .or(spectra)
+            .ok_or("bad tensor file, no rgb or spectra")?;
         let luminance = tf
             .fields
             .get("luminance")
@@ -763,7 +802,7 @@ impl MeasuredBsdf {
                 && rgb.shape.len() == 5
                 && rgb.shape[0] == phi_i.shape[0]
                 && rgb.shape[1] == theta_i.shape[0]
-                && rgb.shape[2] == 3
+                && (spectra.is_some() || rgb.shape[2] == 3)
                 && rgb.shape[3] == luminance.shape[2])
             {
                 Err("Invalid tensor file: bad rgb.")?;
@@ -844,34 +883,54 @@ impl MeasuredBsdf {
             true,
         )?;
 
+        let rgb_data = rgb
+            .data
+            .chunks(4)
+            .map(|x| f32::from_le_bytes(x.try_into().expect("oop")))
+            .collect::<Vec<_>>();
+        let rgb_data = if spectra.is_some() {
+            let wavelengths = tf
+                .fields
+                .get("wavelengths")
+                .ok_or("bad tensor file, no wavelengths")?;
+            if !(wavelengths.shape.len() == 1
+                && wavelengths.dtype == TensorFieldType::Float32
+                && wavelengths.shape[0] == rgb.shape[2])
+            {
+                Err("Invalid tensor file: bad wavelengths.")?;
+            }
+            let wavelengths = wavelengths
+                .data
+                .chunks(4)
+                .map(|x| f32::from_le_bytes(x.try_into().expect("oop")))
+                .collect::<Vec<_>>();
+
+            spectra_to_rgb(&rgb_data, &rgb.shape, &wavelengths)
+        } else {
+            rgb_data
+        };
+
         let channels = vec![0.0, 1.0, 2.0];
         let rgb = Warp2D3::new(
             UVector2::new(rgb.shape[4] as u32, rgb.shape[3] as u32),
-            &rgb.data
-                .chunks(4)
-                .map(|x| f32::from_le_bytes(x.try_into().expect("oop")))
-                .collect::<Vec<_>>(),
+            &rgb_data,
             [phi_i_data, theta_i_data, channels],
             false,
             false,
         )?;
 
-        Ok(MeasuredBsdf {
-            data: Box::new(MeasuredBrdfData {
-                ndf,
-                sigma,
-                vndf,
-                luminance,
-                rgb,
-                isotropic,
-                jacobian,
-            }),
+        Ok(MeasuredBrdfData {
+            ndf,
+            sigma,
+            vndf,
+            luminance,
+            rgb,
+            isotropic,
+            jacobian,
         })
     }
-}
 
-impl BsdfT for MeasuredBsdf {
-    fn sample(&self, wi: Vector3, _si: &SurfaceInteraction, u1: f32, u2: Point2) -> BsdfSample {
+    fn sample(&self, wi: Vector3, u2: Point2) -> BsdfSample {
         // return BsdfSample {
         //     wo: wi,
         //     sampled: BsdfFlags::Delta,
@@ -891,11 +950,11 @@ impl BsdfT for MeasuredBsdf {
         let sample = Vector2::new(u2.y, u2.x);
         // let lum_pdf = 1.0;
 
-        let (sample, lum_pdf) = self.data.luminance.sample(sample, &params);
+        let (sample, lum_pdf) = self.luminance.sample(sample, &params);
 
-        let (u_wm, ndf_pdf) = self.data.vndf.sample(sample, &params);
+        let (u_wm, ndf_pdf) = self.vndf.sample(sample, &params);
 
-        let phi_m = u2phi(u_wm.y) + if self.data.isotropic { phi_i } else { 0.0 };
+        let phi_m = u2phi(u_wm.y) + if self.isotropic { phi_i } else { 0.0 };
         let theta_m = u2theta(u_wm.x);
 
         /* Spherical -> Cartesian coordinates */
@@ -913,7 +972,7 @@ impl BsdfT for MeasuredBsdf {
         if wo.z <= 0.0 {
             return BsdfSample {
                 wo: Vector3::Z,
-                sampled: self.flags(),
+                sampled: MEASURED_FLAGS,
                 spectrum: Spectrum::zero(),
             };
         }
@@ -924,11 +983,11 @@ impl BsdfT for MeasuredBsdf {
         for i in 0..3 {
             let params_fr = [phi_i, theta_i, i as f32];
 
-            fr[i] = self.data.rgb.eval(sample, &params_fr).max(0.0);
+            fr[i] = self.rgb.eval(sample, &params_fr).max(0.0);
         }
 
         let u_wi = Vector2::new(theta2u(theta_i), phi2u(phi_i));
-        fr *= self.data.ndf.eval(u_wm, &params) / (4.0 * self.data.sigma.eval(u_wi, &params));
+        fr *= self.ndf.eval(u_wm, &params) / (4.0 * self.sigma.eval(u_wi, &params));
 
         let jacobian = (2.0 * core::f32::consts::PI.powi(2) * u_wm.x * sin_theta_m).max(1e-6)
             * 4.0
@@ -938,12 +997,12 @@ impl BsdfT for MeasuredBsdf {
 
         BsdfSample {
             wo,
-            sampled: self.flags(),
+            sampled: MEASURED_FLAGS,
             spectrum: Spectrum::from_rgb(fr.x, fr.y, fr.z) / pdf,
         }
     }
 
-    fn eval(&self, _si: &SurfaceInteraction, mut wi: Vector3, mut wo: Vector3) -> Spectrum {
+    fn eval(&self, mut wi: Vector3, mut wo: Vector3) -> Spectrum {
         if wo.z * wi.z < 0.0 {
             return Spectrum::zero();
         }
@@ -969,35 +1028,536 @@ impl BsdfT for MeasuredBsdf {
         let u_wi = Vector2::new(theta2u(theta_i), phi2u(phi_i));
         let mut u_wm = Vector2::new(
             theta2u(theta_m),
-            phi2u(if self.data.isotropic {
+            phi2u(if self.isotropic {
                 phi_m - phi_i
             } else {
                 phi_m
             }),
         );
-        u_wm.y = u_wm.y.fract();
+        u_wm.y = u_wm.y.rem_euclid(1.0);
 
         let params = [phi_i, theta_i];
-        let (sample, _vndf_pdf) = self.data.vndf.invert(u_wm, &params);
+        let (sample, _vndf_pdf) = self.vndf.invert(u_wm, &params);
 
         let mut fr = Vector3::ZERO;
         for i in 0..3 {
             let params_fr = [phi_i, theta_i, i as f32];
 
-            fr[i] = self.data.rgb.eval(sample, &params_fr);
+            fr[i] = self.rgb.eval(sample, &params_fr);
 
             /* clamp the value to zero (negative values occur when the original
             spectral data goes out of gamut) */
             fr[i] = fr[i].max(0.0);
         }
 
-        fr *= self.data.ndf.eval(u_wm, &params) / (4.0 * self.data.sigma.eval(u_wi, &params));
+        fr *= self.ndf.eval(u_wm, &params) / (4.0 * self.sigma.eval(u_wi, &params));
 
         Spectrum::from_rgb(fr.x, fr.y, fr.z)
     }
 
+    fn pdf(&self, mut wi: Vector3, mut wo: Vector3) -> f32 {
+        if wo.z * wi.z <= 0.0 {
+            return 0.0;
+        }
+
+        if wo.z < 0.0 {
+            wo = -wo;
+            wi = -wi;
+        }
+
+        let wm = wi + wo;
+        if wm.length_squared() == 0.0 {
+            return 0.0;
+        }
+        let wm = wm.normalize();
+
+        let theta_i = spherical_theta(wi);
+        let phi_i = wi.y.atan2(wi.x);
+        let theta_m = spherical_theta(wm);
+        let phi_m = wm.y.atan2(wm.x);
+
+        let mut u_wm = Vector2::new(
+            theta2u(theta_m),
+            phi2u(if self.isotropic {
+                phi_m - phi_i
+            } else {
+                phi_m
+            }),
+        );
+        u_wm.y = u_wm.y.rem_euclid(1.0);
+
+        let params = [phi_i, theta_i];
+        let (sample, vndf_pdf) = self.vndf.invert(u_wm, &params);
+        let lum_pdf = self.luminance.eval(sample, &params);
+
+        let sin_theta_m = (wm.x * wm.x + wm.y * wm.y).sqrt();
+        let jacobian =
+            (2.0 * core::f32::consts::PI.powi(2) * u_wm.x * sin_theta_m).max(1e-6) * 4.0 * wi.dot(wm);
+
+        vndf_pdf * lum_pdf / jacobian
+    }
+}
+
+const MERL_THETA_H: usize = 90;
+const MERL_THETA_D: usize = 90;
+const MERL_PHI_D: usize = 180;
+const MERL_SCALE: [f32; 3] = [1.0 / 1500.0, 1.15 / 1500.0, 1.66 / 1500.0];
+
+/// Resolution of the tabulated outgoing distribution used to importance sample MERL data.
+const MERL_SAMPLING_THETA_I: usize = 32;
+const MERL_SAMPLING_RES: u32 = 64;
+
+#[derive(Debug, Clone)]
+struct MerlBrdfData {
+    /// Channel planar brdf values indexed by (theta_h, theta_d, phi_d).
+    pub data: Vec<f32>,
+    /// Density of `wo` in the (theta2u, phi2u) square, relative to `phi_i` and conditioned on `theta_i`.
+    pub distribution: Warp2D1,
+}
+
+impl MerlBrdfData {
+    pub fn load_from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::new(file);
+
+        let mut dims = [0; 3];
+        for dim in dims.iter_mut() {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            *dim = i32::from_le_bytes(bytes) as usize;
+        }
+        if dims != [MERL_THETA_H, MERL_THETA_D, MERL_PHI_D] {
+            Err(format!("Invalid MERL file: unexpected dimensions {dims:?}"))?;
+        }
+
+        let n = MERL_THETA_H * MERL_THETA_D * MERL_PHI_D;
+        let mut bytes = vec![0; n * 3 * 8];
+        reader.read_exact(&mut bytes)?;
+        let data = bytes
+            .chunks(8)
+            .enumerate()
+            .map(|(i, x)| {
+                let v = f64::from_le_bytes(x.try_into().expect("oop")) as f32;
+                // negative values mark missing measurements
+                (v * MERL_SCALE[i / n]).max(0.0)
+            })
+            .collect::<Vec<_>>();
+
+        let mut merl = MerlBrdfData {
+            data,
+            distribution: Warp2D1::new(UVector2::splat(2), &[1.0; 4], [vec![0.0]], true, true)?,
+        };
+
+        // tabulate luminance * cos(theta_o) in the warped square so the jacobian matches the rgl sampling
+        let res = MERL_SAMPLING_RES as usize;
+        let theta_i_values = (0..MERL_SAMPLING_THETA_I)
+            .map(|i| i as f32 / (MERL_SAMPLING_THETA_I - 1) as f32 * (0.5 * core::f32::consts::PI))
+            .collect::<Vec<_>>();
+        let mut density = Vec::with_capacity(MERL_SAMPLING_THETA_I * res * res);
+        for &theta_i in &theta_i_values {
+            let (sin_theta_i, cos_theta_i) = theta_i.sin_cos();
+            let wi = Vector3::new(sin_theta_i, 0.0, cos_theta_i);
+
+            for y in 0..res {
+                let phi_o = u2phi(y as f32 / (res - 1) as f32);
+                for x in 0..res {
+                    let u = x as f32 / (res - 1) as f32;
+                    let theta_o = u2theta(u);
+
+                    let (sin_phi_o, cos_phi_o) = phi_o.sin_cos();
+                    let (sin_theta_o, cos_theta_o) = theta_o.sin_cos();
+                    let wo = Vector3::new(
+                        cos_phi_o * sin_theta_o,
+                        sin_phi_o * sin_theta_o,
+                        cos_theta_o,
+                    );
+
+                    // the floor keeps every direction with a non zero brdf reachable
+                    let f = merl.lookup(wi, wo).y().max(0.0) + 1e-2;
+                    let jacobian = 2.0 * core::f32::consts::PI.powi(2) * u * sin_theta_o;
+                    density.push(f * cos_theta_o * jacobian);
+                }
+            }
+        }
+
+        merl.distribution = Warp2D1::new(
+            UVector2::splat(MERL_SAMPLING_RES),
+            &density,
+            [theta_i_values],
+            true,
+            true,
+        )?;
+
+        Ok(merl)
+    }
+
+    /// Looks up the brdf with the half/difference angle parameterization from
+    /// "A Data-Driven Reflectance Model" (Matusik et al. 2003).
+    fn lookup(&self, wi: Vector3, wo: Vector3) -> Spectrum {
+        let wh = (wi + wo).normalize();
+        let theta_h = spherical_theta(wh);
+        let phi_h = wh.y.atan2(wh.x);
+
+        // rotate wi so the half vector is the north pole
+        let (sin_phi_h, cos_phi_h) = (-phi_h).sin_cos();
+        let (sin_theta_h, cos_theta_h) = (-theta_h).sin_cos();
+        let t = Vector3::new(
+            wi.x * cos_phi_h - wi.y * sin_phi_h,
+            wi.x * sin_phi_h + wi.y * cos_phi_h,
+            wi.z,
+        );
+        let wd = Vector3::new(
+            t.x * cos_theta_h + t.z * sin_theta_h,
+            t.y,
+            -t.x * sin_theta_h + t.z * cos_theta_h,
+        );
+        let theta_d = spherical_theta(wd);
+        let mut phi_d = wd.y.atan2(wd.x);
+        if phi_d < 0.0 {
+            phi_d += core::f32::consts::PI;
+        }
+
+        let theta_h_index = if theta_h <= 0.0 {
+            0
+        } else {
+            ((theta_h / (0.5 * core::f32::consts::PI)).sqrt() * MERL_THETA_H as f32) as usize
+        }
+        .min(MERL_THETA_H - 1);
+        let theta_d_index = ((theta_d / (0.5 * core::f32::consts::PI) * MERL_THETA_D as f32)
+            as usize)
+            .min(MERL_THETA_D - 1);
+        let phi_d_index =
+            ((phi_d / core::f32::consts::PI * MERL_PHI_D as f32) as usize).min(MERL_PHI_D - 1);
+
+        let index = phi_d_index + MERL_PHI_D * (theta_d_index + MERL_THETA_D * theta_h_index);
+        let n = MERL_THETA_H * MERL_THETA_D * MERL_PHI_D;
+
+        Spectrum::from_rgb(
+            self.data[index],
+            self.data[index + n],
+            self.data[index + 2 * n],
+        )
+    }
+
+    fn sample(&self, wi: Vector3, u2: Point2) -> BsdfSample {
+        let (wi, flip_wo) = if wi.z <= 0.0 {
+            (-wi, true)
+        } else {
+            (wi, false)
+        };
+
+        let theta_i = spherical_theta(wi);
+        let phi_i = wi.y.atan2(wi.x);
+
+        let (u_wo, pdf) = self.distribution.sample(Vector2::new(u2.x, u2.y), &[theta_i]);
+
+        let phi_o = u2phi(u_wo.y) + phi_i;
+        let theta_o = u2theta(u_wo.x);
+
+        let (sin_phi_o, cos_phi_o) = phi_o.sin_cos();
+        let (sin_theta_o, cos_theta_o) = theta_o.sin_cos();
+        let wo = Vector3::new(
+            cos_phi_o * sin_theta_o,
+            sin_phi_o * sin_theta_o,
+            cos_theta_o,
+        );
+
+        let jacobian = 2.0 * core::f32::consts::PI.powi(2) * u_wo.x * sin_theta_o;
+        let pdf = pdf / jacobian;
+        if pdf.is_nan() || pdf <= 0.0 || cos_theta_o <= 0.0 {
+            return BsdfSample {
+                wo: Vector3::Z,
+                sampled: MEASURED_FLAGS,
+                spectrum: Spectrum::zero(),
+            };
+        }
+
+        let f = self.lookup(wi, wo);
+
+        BsdfSample {
+            wo: wo * if flip_wo { -1.0 } else { 1.0 },
+            sampled: MEASURED_FLAGS,
+            spectrum: f * cos_theta_o / pdf,
+        }
+    }
+
+    fn eval(&self, mut wi: Vector3, mut wo: Vector3) -> Spectrum {
+        if wo.z * wi.z < 0.0 {
+            return Spectrum::zero();
+        }
+
+        if wo.z < 0.0 {
+            wo = -wo;
+            wi = -wi;
+        }
+
+        self.lookup(wi, wo)
+    }
+
+    fn pdf(&self, mut wi: Vector3, mut wo: Vector3) -> f32 {
+        if wo.z * wi.z <= 0.0 {
+            return 0.0;
+        }
+
+        if wo.z < 0.0 {
+            wo = -wo;
+            wi = -wi;
+        }
+
+        let theta_i = spherical_theta(wi);
+        let phi_i = wi.y.atan2(wi.x);
+        let theta_o = spherical_theta(wo);
+        let phi_o = wo.y.atan2(wo.x);
+
+        let mut u_wo = Vector2::new(theta2u(theta_o), phi2u(phi_o - phi_i));
+        u_wo.y = u_wo.y.rem_euclid(1.0);
+
+        let jacobian = 2.0 * core::f32::consts::PI.powi(2) * u_wo.x * theta_o.sin();
+        if jacobian <= 0.0 {
+            return 0.0;
+        }
+
+        self.distribution.eval(u_wo, &[theta_i]) / jacobian
+    }
+}
+
+// the whole thing is boxed in `MeasuredBsdf` anyways
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+enum MeasuredData {
+    Rgl(MeasuredBrdfData),
+    Merl(MerlBrdfData),
+}
+
+const MEASURED_FLAGS: BsdfFlags = BsdfFlags::DiffuseReflection;
+// const MEASURED_FLAGS: BsdfFlags = BsdfFlags::DeltaReflection;
+
+#[derive(Debug, Clone)]
+pub struct MeasuredBsdf {
+    //TODO: this should be in a centralized store for coherency reasons
+    //NOTE: this is boxed so the size of Bsdf is smaller, maybe it will become unboxed if other bsdfs are larger.
+    data: Box<MeasuredData>,
+}
+
+impl MeasuredBsdf {
+    /// Loads either an RGL `.bsdf` tensor file, rgb or spectral, or a MERL 100 isotropic `.binary` file.
+    pub fn load_from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let data = match path.extension().and_then(|e| e.to_str()) {
+            Some("binary") => MeasuredData::Merl(MerlBrdfData::load_from_file(path)?),
+            _ => MeasuredData::Rgl(MeasuredBrdfData::load_from_file(path)?),
+        };
+
+        Ok(MeasuredBsdf {
+            data: Box::new(data),
+        })
+    }
+}
+
+impl BsdfT for MeasuredBsdf {
+    fn sample(&self, wi: Vector3, _si: &SurfaceInteraction, _u1: f32, u2: Point2) -> BsdfSample {
+        match self.data.as_ref() {
+            MeasuredData::Rgl(data) => data.sample(wi, u2),
+            MeasuredData::Merl(data) => data.sample(wi, u2),
+        }
+    }
+
+    fn eval(&self, _si: &SurfaceInteraction, wi: Vector3, wo: Vector3) -> Spectrum {
+        match self.data.as_ref() {
+            MeasuredData::Rgl(data) => data.eval(wi, wo),
+            MeasuredData::Merl(data) => data.eval(wi, wo),
+        }
+    }
+
+    fn pdf(&self, _si: &SurfaceInteraction, wi: Vector3, wo: Vector3) -> f32 {
+        match self.data.as_ref() {
+            MeasuredData::Rgl(data) => data.pdf(wi, wo),
+            MeasuredData::Merl(data) => data.pdf(wi, wo),
+        }
+    }
+
     fn flags(&self) -> BsdfFlags {
-        BsdfFlags::DiffuseReflection
-        // BsdfFlags::DeltaReflection
+        MEASURED_FLAGS
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::core::albedo;
+
+    use super::*;
+
+    const PAPER_WHITE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../assets/brdfs/paper_white_rgb.bsdf"
+    );
+
+    fn temp_path(name: &str, extension: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "luminiferous_{name}_{}.{extension}",
+            std::process::id()
+        ))
+    }
+
+    fn write_merl_file(name: &str, dims: [i32; 3], value: impl Fn(usize) -> f64) -> PathBuf {
+        let n = MERL_THETA_H * MERL_THETA_D * MERL_PHI_D;
+        let mut data = dims
+            .iter()
+            .flat_map(|d| d.to_le_bytes())
+            .collect::<Vec<_>>();
+        data.extend((0..3 * n).flat_map(|i| value(i / n).to_le_bytes()));
+
+        let path = temp_path(name, "binary");
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    fn write_tensor_file(name: &str, fields: &[(&str, &TensorField)]) -> PathBuf {
+        let header_size = 12
+            + 2
+            + 4
+            + fields
+                .iter()
+                .map(|(name, field)| 2 + name.len() + 2 + 1 + 8 + 8 * field.shape.len())
+                .sum::<usize>();
+
+        let mut header = b"tensor_file\0".to_vec();
+        header.extend([1, 0]);
+        header.extend((fields.len() as u32).to_le_bytes());
+        let mut data: Vec<u8> = vec![];
+        for (name, field) in fields {
+            let dtype = (0..=11)
+                .find(|&i| TensorFieldType::from(i) == field.dtype)
+                .unwrap();
+
+            header.extend((name.len() as u16).to_le_bytes());
+            header.extend(name.as_bytes());
+            header.extend((field.shape.len() as u16).to_le_bytes());
+            header.push(dtype);
+            header.extend(((header_size + data.len()) as u64).to_le_bytes());
+            header.extend(field.shape.iter().flat_map(|&s| (s as u64).to_le_bytes()));
+            data.extend(&field.data);
+        }
+        assert_eq!(header.len(), header_size);
+        header.extend(data);
+
+        let path = temp_path(name, "bsdf");
+        std::fs::write(&path, header).unwrap();
+        path
+    }
+
+    fn f32_field(shape: Vec<usize>, data: &[f32]) -> TensorField {
+        TensorField {
+            dtype: TensorFieldType::Float32,
+            shape,
+            data: data.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        }
+    }
+
+    fn upper_directions() -> Vec<Vector3> {
+        [0.05f32, 0.4, 0.9, 1.3]
+            .into_iter()
+            .map(|theta| Vector3::new(theta.sin(), 0.0, theta.cos()))
+            .collect()
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let path = temp_path("bad_tensor", "bsdf");
+        std::fs::write(&path, b"not_a_tensor\x01\x00\x00\x00\x00\x00").unwrap();
+        let tensor = MeasuredBsdf::load_from_file(&path);
+        std::fs::remove_file(path).unwrap();
+        assert!(tensor.is_err());
+
+        let path = write_merl_file("bad_merl", [90, 90, 90], |_| 1.0);
+        let merl = MeasuredBsdf::load_from_file(&path);
+        std::fs::remove_file(path).unwrap();
+        assert!(merl.is_err());
+    }
+
+    #[test]
+    fn merl_white_furnace() {
+        // a constant brdf of 1 / pi in every channel is a white lambertian
+        let path = write_merl_file("white_merl", [90, 90, 180], |c| {
+            (core::f32::consts::FRAC_1_PI / MERL_SCALE[c]) as f64
+        });
+        let bsdf = MeasuredBsdf::load_from_file(&path);
+        std::fs::remove_file(path).unwrap();
+        let MeasuredData::Merl(merl) = *bsdf.unwrap().data else {
+            panic!("a .binary file should load as MERL data");
+        };
+
+        for wi in upper_directions() {
+            let albedo = albedo(|_, u2| merl.sample(wi, u2).spectrum, 100_000);
+            assert!(
+                albedo.iter().all(|a| (a - 1.0).abs() < 0.02),
+                "wi = {wi} reflects {albedo:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn spectral_rgl_matches_rgb() {
+        // rewrite the rgb data as flat spectra of the same luminance, which should reflect the same luminance
+        let tf = TensorFile::load_from_file(Path::new(PAPER_WHITE)).unwrap();
+        let rgb = &tf.fields["rgb"];
+        let rgb_data = rgb
+            .data
+            .chunks(4)
+            .map(|x| f32::from_le_bytes(x.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        let wavelengths = (0..48).map(|i| 360.0 + 10.0 * i as f32).collect::<Vec<_>>();
+        let slice_size = rgb.shape[3] * rgb.shape[4];
+        let mut spectra = Vec::with_capacity(rgb_data.len() / 3 * wavelengths.len());
+        for slice in rgb_data.chunks(3 * slice_size) {
+            let luminance = (0..slice_size)
+                .map(|j| {
+                    Spectrum::from_rgb(slice[j], slice[slice_size + j], slice[2 * slice_size + j])
+                        .y()
+                })
+                .collect::<Vec<_>>();
+            for _ in &wavelengths {
+                spectra.extend(&luminance);
+            }
+        }
+
+        let spectra = f32_field(
+            vec![
+                rgb.shape[0],
+                rgb.shape[1],
+                wavelengths.len(),
+                rgb.shape[3],
+                rgb.shape[4],
+            ],
+            &spectra,
+        );
+        let wavelengths = f32_field(vec![wavelengths.len()], &wavelengths);
+        let mut fields = tf
+            .fields
+            .iter()
+            .filter(|(name, _)| name.as_str() != "rgb")
+            .map(|(name, field)| (name.as_str(), field))
+            .collect::<Vec<_>>();
+        fields.push(("spectra", &spectra));
+        fields.push(("wavelengths", &wavelengths));
+
+        let path = write_tensor_file("spectral_rgl", &fields);
+        let spectral = MeasuredBrdfData::load_from_file(&path);
+        std::fs::remove_file(path).unwrap();
+        let spectral = spectral.unwrap();
+        let rgb = MeasuredBrdfData::load_from_file(Path::new(PAPER_WHITE)).unwrap();
+
+        for wi in upper_directions() {
+            let expected = albedo(|_, u2| rgb.sample(wi, u2).spectrum, 20_000);
+            let expected = Spectrum::from_rgb(expected[0], expected[1], expected[2]).y();
+            let actual = albedo(|_, u2| spectral.sample(wi, u2).spectrum, 20_000);
+            let actual = Spectrum::from_rgb(actual[0], actual[1], actual[2]).y();
+            assert!(
+                (0.0..=1.0).contains(&actual) && (actual - expected).abs() < 0.01,
+                "wi = {wi}: the spectral data reflects {actual}, the rgb data {expected}"
+            );
+        }
     }
 }
@@ -27,6 +27,10 @@ impl BsdfT for MirrorBsdf {
         Spectrum::zero()
     }
 
+    fn pdf(&self, _si: &SurfaceInteraction, _wi: Vector3, _wo: Vector3) -> f32 {
+        0.0
+    }
+
     fn sample(&self, wi: Vector3, si: &SurfaceInteraction, _u1: f32, _u2: Point2) -> BsdfSample {
         let wo = reflect(wi);
         let reflectance = self.reflectance.eval(si);
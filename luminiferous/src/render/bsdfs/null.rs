@@ -22,6 +22,10 @@ impl BsdfT for NullBsdf {
         Spectrum::zero()
     }
 
+    fn pdf(&self, _si: &SurfaceInteraction, _wi: Vector3, _wo: Vector3) -> f32 {
+        0.0
+    }
+
     fn flags(&self) -> BsdfFlags {
         BsdfFlags::Null
     }
@@ -10,25 +10,61 @@ use super::{fresnel, fresnel_diffuse_reflectance, reflect, BsdfFlags, BsdfSample
 #[derive(Debug, Clone)]
 pub struct PlasticBsdf {
     diffuse_reflectance: SpectralTexture,
+    /// Ior of the coating relative to the outside.
     eta: f32,
-    fdr_i: f32,
-    // fdr_t: f32,
+    /// Fraction of diffuse light inside the coating that is reflected back in at the interface.
+    fdr_internal: f32,
     alpha: f32,
 }
 
 impl PlasticBsdf {
+    /// A diffuse substrate with ior `eta_t` under a coating of roughness `alpha`, seen from a medium with
+    /// ior `eta_i`. A roughness of zero makes the coating a perfect mirror.
     pub fn new(diffuse_reflectance: SpectralTexture, eta_i: f32, eta_t: f32, alpha: f32) -> Self {
         STATS.bsdfs_created.inc();
-        let eta = eta_i / eta_t;
-        let (fdr_i, _fdr_t) = fresnel_diffuse_reflectance(eta);
+        let eta = eta_t / eta_i;
+        let (fdr_external, _) = fresnel_diffuse_reflectance(eta);
+        // the light that gets in from a uniform diffuse field is the light that gets out of it scaled by
+        // eta squared, which gives the internal reflectance from the external one
+        let fdr_internal = 1.0 - (1.0 - fdr_external) / (eta * eta);
         Self {
             diffuse_reflectance,
             eta,
-            fdr_i,
-            // fdr_t,
+            fdr_internal,
             alpha,
         }
     }
+
+    #[inline]
+    fn is_smooth(&self) -> bool {
+        self.alpha <= 0.0
+    }
+
+    /// Density of `normalize(reflect(wi) + alpha * s)` with `s` uniform on the unit sphere, found by
+    /// projecting the sphere of radius alpha around the mirror direction onto the directions through it.
+    fn glossy_pdf(&self, wi: Vector3, wo: Vector3) -> f32 {
+        if self.alpha <= 0.0 {
+            return 0.0;
+        }
+
+        let c = reflect(wi);
+        let b = wo.dot(c);
+        let discriminant = b * b - (c.length_squared() - self.alpha * self.alpha);
+        if discriminant < 0.0 {
+            return 0.0;
+        }
+
+        let root = discriminant.sqrt();
+        [b - root, b + root]
+            .into_iter()
+            .filter(|&t| t > 0.0)
+            .map(|t| {
+                let n = (wo * t - c) / self.alpha;
+                let cos = wo.dot(n).abs().max(1e-6);
+                t * t / (4.0 * core::f32::consts::PI * self.alpha * self.alpha * cos)
+            })
+            .sum()
+    }
 }
 
 impl BsdfT for PlasticBsdf {
@@ -36,55 +72,81 @@ impl BsdfT for PlasticBsdf {
         let cos_theta_i = Frame3::cos_theta(wi);
         let cos_theta_o = Frame3::cos_theta(wo);
 
-        if cos_theta_i <= 0.0 && cos_theta_o <= 0.0 {
+        if cos_theta_i <= 0.0 || cos_theta_o <= 0.0 {
             return Spectrum::zero();
         }
 
         let r_i = fresnel(cos_theta_i, self.eta).0;
         let r_o = fresnel(cos_theta_o, self.eta).0;
 
-        self.diffuse_reflectance.eval(si)
-            * core::f32::consts::FRAC_1_PI
-            * self.fdr_i
-            * self.eta.powi(2).recip()
-            * (1.0 - r_i)
-            * (1.0 - r_o)
+        // light that gets through the coating bounces between the substrate and the inside of the coating
+        // until it gets out
+        let reflectance = self.diffuse_reflectance.eval(si);
+        let diffuse = reflectance * (1.0 - r_i) * (1.0 - r_o)
+            / ((Spectrum::splat(1.0) - reflectance * self.fdr_internal)
+                * (core::f32::consts::PI * self.eta * self.eta));
+
+        // the glossy lobe is defined by its sampling, so f * cos is the density of picking and sampling it
+        let glossy = r_i * self.glossy_pdf(wi, wo) / cos_theta_o;
+
+        diffuse + Spectrum::splat(glossy)
     }
 
-    fn sample(&self, wi: Vector3, si: &SurfaceInteraction, u1: f32, u2: Point2) -> BsdfSample {
-        let cos_theta_i = Frame3::cos_theta(wi);
+    fn pdf(&self, _si: &SurfaceInteraction, wi: Vector3, wo: Vector3) -> f32 {
+        // glossy samples below the surface are discarded, so they don't count towards the density
+        if Frame3::cos_theta(wi) <= 0.0 || Frame3::cos_theta(wo) <= 0.0 {
+            return 0.0;
+        }
+
+        let r_i = fresnel(Frame3::cos_theta(wi), self.eta).0;
+
+        r_i * self.glossy_pdf(wi, wo)
+            + (1.0 - r_i) * Frame3::cos_theta(wo) * core::f32::consts::FRAC_1_PI
+    }
 
-        let (r_i, _, _, _) = fresnel(cos_theta_i, self.eta);
+    fn sample(&self, wi: Vector3, si: &SurfaceInteraction, u1: f32, u2: Point2) -> BsdfSample {
+        let r_i = fresnel(Frame3::cos_theta(wi), self.eta).0;
 
-        if u1 < r_i {
-            // specular
-            BsdfSample {
-                //FIXME: this is a mildly hacky way to do roughness, should prob fix it
-                wo: (reflect(wi) + warp::square_to_uniform_sphere(u2) * self.alpha).normalize(),
-                sampled: self.flags(),
+        if u1 < r_i && self.is_smooth() {
+            // picked with the probability of the fresnel term, which cancels it out
+            return BsdfSample {
+                wo: reflect(wi),
+                sampled: BsdfFlags::DeltaReflection,
                 spectrum: Spectrum::splat(1.0),
-            }
+            };
+        }
+
+        let (wo, sampled) = if u1 < r_i {
+            //FIXME: this is a mildly hacky way to do roughness, should prob fix it
+            let wo = (reflect(wi) + warp::square_to_uniform_sphere(u2) * self.alpha).normalize();
+            (wo, BsdfFlags::GlossyReflection)
+        } else {
+            (
+                warp::square_to_cosine_hemisphere(u2),
+                BsdfFlags::DiffuseReflection,
+            )
+        };
+
+        // weight by the whole bsdf so the sample agrees with eval and pdf whichever lobe produced it
+        let pdf = self.pdf(si, wi, wo);
+        let spectrum = if pdf > 0.0 {
+            self.eval(si, wi, wo) * Frame3::cos_theta(wo).abs() / pdf
         } else {
-            // diffuse
-            let wo = warp::square_to_cosine_hemisphere(u2);
-            let diffuse_reflectance = self.diffuse_reflectance.eval(si);
-            let r_o = fresnel(Frame3::cos_theta(wo), self.eta).0;
-
-            let mut spectrum = diffuse_reflectance;
-            spectrum /= 1.0 - self.fdr_i;
-            spectrum *= 1.0 - r_o;
-            // spectrum /= self.eta.recip().powi(2) * (1.0 - self.fdr_i) * (1.0 - r_o);
-            // * (1.0 - r_i) / (1.0 - r_i);
-
-            BsdfSample {
-                wo,
-                sampled: self.flags(),
-                spectrum,
-            }
+            Spectrum::zero()
+        };
+
+        BsdfSample {
+            wo,
+            sampled,
+            spectrum,
         }
     }
 
     fn flags(&self) -> BsdfFlags {
-        BsdfFlags::GlossyReflection | BsdfFlags::DiffuseReflection
+        if self.is_smooth() {
+            BsdfFlags::DeltaReflection | BsdfFlags::DiffuseReflection
+        } else {
+            BsdfFlags::GlossyReflection | BsdfFlags::DiffuseReflection
+        }
     }
 }
@@ -0,0 +1,250 @@
+// Sheen from "Production Friendly Microfacet Sheen BRDF" (Estevez and Kulla 2017)
+// http://www.aconty.com/pdf/s2017_pbs_imageworks_sheen.pdf
+
+use std::sync::OnceLock;
+
+use crate::prelude::*;
+use crate::{
+    primitive::SurfaceInteraction,
+    spectra::{Spectrum, SpectrumT},
+    stats::STATS,
+    textures::{SpectralTexture, Texture, TextureT},
+};
+
+use super::{Bsdf, BsdfFlags, BsdfSample, BsdfT};
+
+const ALBEDO_RES: usize = 16;
+
+/// The "Charlie" sheen lobe for cloth and velvet, with an optional base bsdf underneath. The base is
+/// attenuated by the directional albedo of the sheen so the layer doesn't add energy.
+#[derive(Debug, Clone)]
+pub struct SheenBsdf {
+    color: SpectralTexture,
+    roughness: Texture<f32>,
+    base: Option<Box<Bsdf>>,
+}
+
+impl SheenBsdf {
+    pub fn new(color: SpectralTexture, roughness: Texture<f32>) -> Self {
+        STATS.bsdfs_created.inc();
+
+        Self {
+            color,
+            roughness,
+            base: None,
+        }
+    }
+
+    /// Layers the sheen on top of `base`.
+    pub fn with_base(mut self, base: Bsdf) -> Self {
+        self.base = Some(Box::new(base));
+        self
+    }
+
+    #[inline]
+    fn roughness(&self, si: &SurfaceInteraction) -> f32 {
+        self.roughness.eval(si).clamp(1e-3, 1.0)
+    }
+
+    /// Probability of sampling the sheen lobe, which is also the fraction of energy taken away from the base.
+    fn sheen_weight(&self, color: Spectrum, r: f32, wi: Vector3) -> f32 {
+        if self.base.is_none() {
+            return 1.0;
+        }
+
+        let max_color = color.to_rgb().into_iter().fold(0.0, f32::max);
+        (max_color * albedo(Frame3::cos_theta(wi).abs(), r)).clamp(0.0, 1.0)
+    }
+
+    fn eval_lobe(&self, color: Spectrum, r: f32, wi: Vector3, wo: Vector3) -> Spectrum {
+        // mirror both directions if wi is below the surface, sheen is the same on both sides
+        let (wi, wo) = if Frame3::cos_theta(wi) < 0.0 {
+            (-wi, -wo)
+        } else {
+            (wi, wo)
+        };
+
+        color * eval_sheen(wi, wo, r)
+    }
+
+    fn pdf_lobe(&self, wi: Vector3, wo: Vector3) -> f32 {
+        if Frame3::cos_theta(wi) * Frame3::cos_theta(wo) <= 0.0 {
+            return 0.0;
+        }
+
+        Frame3::cos_theta(wo).abs() * core::f32::consts::FRAC_1_PI
+    }
+}
+
+#[inline]
+fn d_charlie(cos_theta_h: f32, r: f32) -> f32 {
+    let inv_r = r.recip();
+    let sin_theta_h = (1.0 - cos_theta_h * cos_theta_h).max(0.0).sqrt();
+    (2.0 + inv_r) * sin_theta_h.powf(inv_r) * 0.5 * core::f32::consts::FRAC_1_PI
+}
+
+/// The fitted curve that Λ is built from.
+#[inline]
+fn l(x: f32, r: f32) -> f32 {
+    let t = (1.0 - r) * (1.0 - r);
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+
+    let a = lerp(25.3245, 21.5473);
+    let b = lerp(3.32435, 3.82987);
+    let c = lerp(0.16801, 0.19823);
+    let d = lerp(-1.27393, -1.97760);
+    let e = lerp(-4.85967, -4.32054);
+
+    a / (1.0 + b * x.powf(c)) + d * x + e
+}
+
+#[inline]
+fn lambda(cos_theta: f32, r: f32) -> f32 {
+    if cos_theta < 0.5 {
+        l(cos_theta, r).exp()
+    } else {
+        (2.0 * l(0.5, r) - l(1.0 - cos_theta, r)).exp()
+    }
+}
+
+/// Sheen lobe without color and the cosine, both directions are in the upper hemisphere.
+fn eval_sheen(wi: Vector3, wo: Vector3, r: f32) -> f32 {
+    let cos_theta_i = Frame3::cos_theta(wi);
+    let cos_theta_o = Frame3::cos_theta(wo);
+    if cos_theta_i <= 0.0 || cos_theta_o <= 0.0 {
+        return 0.0;
+    }
+
+    let wh = wi + wo;
+    if wh.length_squared() == 0.0 {
+        return 0.0;
+    }
+    let wh = wh.normalize();
+
+    // softened terminator from the paper
+    let lambda_o = lambda(cos_theta_o, r).powf(1.0 + 2.0 * (1.0 - cos_theta_o).powi(8));
+    let g = (1.0 + lambda(cos_theta_i, r) + lambda_o).recip();
+
+    d_charlie(Frame3::cos_theta(wh), r) * g / (4.0 * cos_theta_i * cos_theta_o)
+}
+
+/// Directional albedo of the uncolored sheen lobe, integrated once over a grid of angles and roughnesses.
+fn albedo(cos_theta: f32, r: f32) -> f32 {
+    static TABLE: OnceLock<[[f32; ALBEDO_RES]; ALBEDO_RES]> = OnceLock::new();
+
+    let table = TABLE.get_or_init(|| {
+        const N: usize = 32;
+        let mut table = [[0.0; ALBEDO_RES]; ALBEDO_RES];
+        for (ri, row) in table.iter_mut().enumerate() {
+            let r = (ri as f32 / (ALBEDO_RES - 1) as f32).max(1e-3);
+            for (ci, e) in row.iter_mut().enumerate() {
+                let cos_theta = (ci as f32 / (ALBEDO_RES - 1) as f32).max(1e-3);
+                let wi = Vector3::new((1.0 - cos_theta * cos_theta).sqrt(), 0.0, cos_theta);
+
+                let mut sum = 0.0;
+                for y in 0..N {
+                    for x in 0..N {
+                        let u = Point2::new(
+                            (x as f32 + 0.5) / N as f32,
+                            (y as f32 + 0.5) / N as f32,
+                        );
+                        // cosine weighted, so the estimate of f * cos / pdf is f * pi
+                        let wo = warp::square_to_cosine_hemisphere(u);
+                        sum += eval_sheen(wi, wo, r) * core::f32::consts::PI;
+                    }
+                }
+
+                *e = (sum / (N * N) as f32).min(1.0);
+            }
+        }
+
+        table
+    });
+
+    let x = cos_theta.clamp(0.0, 1.0) * (ALBEDO_RES - 1) as f32;
+    let y = r.clamp(0.0, 1.0) * (ALBEDO_RES - 1) as f32;
+    let (x0, y0) = ((x as usize).min(ALBEDO_RES - 2), (y as usize).min(ALBEDO_RES - 2));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+    let a = table[y0][x0] * (1.0 - tx) + table[y0][x0 + 1] * tx;
+    let b = table[y0 + 1][x0] * (1.0 - tx) + table[y0 + 1][x0 + 1] * tx;
+    a * (1.0 - ty) + b * ty
+}
+
+impl BsdfT for SheenBsdf {
+    fn sample(&self, wi: Vector3, si: &SurfaceInteraction, u1: f32, u2: Point2) -> BsdfSample {
+        let color = self.color.eval(si);
+        let r = self.roughness(si);
+        let weight = self.sheen_weight(color, r, wi);
+
+        let (base, u1) = match &self.base {
+            Some(base) if u1 >= weight => (base, (u1 - weight) / (1.0 - weight)),
+            _ => {
+                // sheen is close enough to uniform that cosine sampling does fine
+                let mut wo = warp::square_to_cosine_hemisphere(u2);
+                if Frame3::cos_theta(wi) < 0.0 {
+                    wo.z = -wo.z;
+                }
+
+                let pdf = self.pdf(si, wi, wo);
+                let spectrum = if pdf > 0.0 {
+                    self.eval(si, wi, wo) * Frame3::cos_theta(wo).abs() / pdf
+                } else {
+                    Spectrum::zero()
+                };
+
+                return BsdfSample {
+                    wo,
+                    sampled: BsdfFlags::GlossyReflection,
+                    spectrum,
+                };
+            }
+        };
+
+        let mut sample = base.sample(wi, si, u1.min(1.0 - f32::EPSILON), u2);
+        if sample.sampled.intersects(BsdfFlags::Delta | BsdfFlags::Null) {
+            // the base attenuation cancels with the selection probability
+            return sample;
+        }
+
+        let base_pdf = base.pdf(si, wi, sample.wo);
+        if base_pdf <= 0.0 {
+            return sample;
+        }
+
+        let pdf = (1.0 - weight) * base_pdf + weight * self.pdf_lobe(wi, sample.wo);
+        let sheen = self.eval_lobe(color, r, wi, sample.wo) * Frame3::cos_theta(sample.wo).abs();
+        sample.spectrum = (sample.spectrum * ((1.0 - weight) * base_pdf) + sheen) / pdf;
+        sample
+    }
+
+    fn eval(&self, si: &SurfaceInteraction, wi: Vector3, wo: Vector3) -> Spectrum {
+        let color = self.color.eval(si);
+        let r = self.roughness(si);
+        let sheen = self.eval_lobe(color, r, wi, wo);
+
+        match &self.base {
+            Some(base) => {
+                sheen + base.eval(si, wi, wo) * (1.0 - self.sheen_weight(color, r, wi))
+            }
+            None => sheen,
+        }
+    }
+
+    fn pdf(&self, si: &SurfaceInteraction, wi: Vector3, wo: Vector3) -> f32 {
+        match &self.base {
+            Some(base) => {
+                let weight = self.sheen_weight(self.color.eval(si), self.roughness(si), wi);
+                weight * self.pdf_lobe(wi, wo) + (1.0 - weight) * base.pdf(si, wi, wo)
+            }
+            None => self.pdf_lobe(wi, wo),
+        }
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        match &self.base {
+            Some(base) => BsdfFlags::GlossyReflection | base.flags(),
+            None => BsdfFlags::GlossyReflection,
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use std::path::Path;
+
+use crate::core::{albedo, ChiSquareTest};
+use crate::{
+    media::MediumInterface,
+    primitive::{Primitive, SurfaceInteraction},
+    shapes::{Shape, Sphere},
+    spectra::{Spectrum, SpectrumT},
+    textures::{ConstantTexture, SpectralTexture, Texture},
+};
+
+use super::*;
+
+const MEASURED_BRDF: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../assets/brdfs/paper_white_rgb.bsdf"
+);
+
+fn constant(r: f32, g: f32, b: f32) -> SpectralTexture {
+    SpectralTexture::Constant(ConstantTexture::new(Spectrum::from_rgb(r, g, b)))
+}
+
+fn white() -> SpectralTexture {
+    constant(1.0, 1.0, 1.0)
+}
+
+fn with_si<R>(f: impl FnOnce(&SurfaceInteraction) -> R) -> R {
+    let primitive = Primitive::new(
+        Shape::Sphere(Sphere::new(1.0)),
+        0,
+        None,
+        None,
+        MediumInterface::none(),
+    );
+    let si = SurfaceInteraction {
+        primitive: &primitive,
+        t: 1.0,
+        p: Point3::ZERO,
+        n: Normal3::Z,
+        wi: Vector3::Z,
+        uv: Point2::splat(0.5),
+        dp_du: Vector3::X,
+        dp_dv: Vector3::Y,
+        time: 0.0,
+    };
+
+    f(&si)
+}
+
+/// Incident directions in the upper hemisphere, from grazing to normal.
+fn upper_directions() -> Vec<Vector3> {
+    vec![
+        Vector3::new(0.0, 0.0, 1.0),
+        Vector3::new(0.3, 0.2, 0.9).normalize(),
+        Vector3::new(-0.6, 0.1, 0.5).normalize(),
+        Vector3::new(0.8, -0.3, 0.1).normalize(),
+    ]
+}
+
+fn all_directions() -> Vec<Vector3> {
+    let mut directions = upper_directions();
+    directions.push(Vector3::new(0.2, 0.4, -0.8).normalize());
+    directions.push(Vector3::new(-0.7, -0.2, -0.3).normalize());
+    directions
+}
+
+/// Bsdfs with a non delta lobe, with the incident directions they support.
+fn smooth_bsdfs() -> Vec<(&'static str, Bsdf, Vec<Vector3>)> {
+    vec![
+        (
+            "lambertian",
+            Bsdf::Lambertian(Lambertian::new(constant(0.5, 0.6, 0.7))),
+            upper_directions(),
+        ),
+        (
+            "plastic",
+            Bsdf::Plastic(PlasticBsdf::new(constant(0.5, 0.3, 0.2), 1.0, 1.5, 0.3)),
+            upper_directions(),
+        ),
+        (
+            "hair",
+            Bsdf::Hair(HairBsdf::from_melanin(1.3, 0.0, 1.55, 0.3, 0.3, 2.0)),
+            all_directions(),
+        ),
+        (
+            "diffuse transmission",
+            Bsdf::DiffuseTransmission(DiffuseTransmissionBsdf::new(
+                constant(0.3, 0.4, 0.5),
+                constant(0.6, 0.5, 0.4),
+            )),
+            all_directions(),
+        ),
+        (
+            "sheen",
+            Bsdf::Sheen(SheenBsdf::new(
+                white(),
+                Texture::Constant(ConstantTexture::new(0.5)),
+            )),
+            all_directions(),
+        ),
+        (
+            "sheen over lambertian",
+            Bsdf::Sheen(
+                SheenBsdf::new(
+                    constant(0.8, 0.2, 0.2),
+                    Texture::Constant(ConstantTexture::new(0.3)),
+                )
+                .with_base(Bsdf::Lambertian(Lambertian::new(constant(0.2, 0.5, 0.8)))),
+            ),
+            upper_directions(),
+        ),
+        (
+            "measured",
+            Bsdf::Measured(MeasuredBsdf::load_from_file(Path::new(MEASURED_BRDF)).unwrap()),
+            upper_directions(),
+        ),
+    ]
+}
+
+/// Bsdfs with only delta lobes, they can't be compared against a density but should still conserve energy.
+fn delta_bsdfs() -> Vec<(&'static str, Bsdf, Vec<Vector3>)> {
+    vec![
+        (
+            "mirror",
+            Bsdf::Mirror(MirrorBsdf::new(white())),
+            upper_directions(),
+        ),
+        (
+            "dielectric",
+            Bsdf::Dielectric(Dielectric::new(1.0, 1.5, Spectrum::splat(1.0))),
+            all_directions(),
+        ),
+        (
+            "thin dielectric",
+            Bsdf::ThinDielectric(ThinDielectricBsdf::new(1.5)),
+            all_directions(),
+        ),
+        (
+            "conductor",
+            Bsdf::Conductor(ConductorBsdf::new(
+                constant(3.9, 2.4, 1.9),
+                constant(0.2, 0.4, 1.4),
+            )),
+            upper_directions(),
+        ),
+        ("null", Bsdf::Null(NullBsdf::new()), all_directions()),
+    ]
+}
+
+#[test]
+fn chi2_sample_matches_pdf() {
+    let bsdfs = smooth_bsdfs();
+    let test_count = bsdfs.iter().map(|(_, _, d)| d.len()).sum();
+    let chi2 = ChiSquareTest::default();
+
+    with_si(|si| {
+        for (name, bsdf, directions) in &bsdfs {
+            for &wi in directions {
+                let result = chi2.run(
+                    |u1, u2| {
+                        let sample = bsdf.sample(wi, si, u1, u2);
+                        (!sample.spectrum.is_black()).then_some(sample.wo)
+                    },
+                    |wo| bsdf.pdf(si, wi, wo),
+                    test_count,
+                );
+
+                if let Err(e) = result {
+                    panic!("{name} with wi = {wi}: {e}");
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn sample_weight_matches_eval() {
+    // the measured bsdfs interpolate differently when sampling, so they are only checked statistically
+    let bsdfs = smooth_bsdfs()
+        .into_iter()
+        .filter(|(name, _, _)| *name != "measured");
+
+    with_si(|si| {
+        for (name, bsdf, directions) in bsdfs {
+            for wi in directions {
+                let mut rng = oorandom::Rand32::new(7);
+                for _ in 0..1000 {
+                    let u1 = rng.rand_float();
+                    let u2 = Point2::new(rng.rand_float(), rng.rand_float());
+                    let sample = bsdf.sample(wi, si, u1, u2);
+
+                    let pdf = bsdf.pdf(si, wi, sample.wo);
+                    if pdf < 1e-3 {
+                        continue;
+                    }
+
+                    let expected =
+                        (bsdf.eval(si, wi, sample.wo) * Frame3::cos_theta(sample.wo).abs() / pdf)
+                            .to_rgb();
+                    let actual = sample.spectrum.to_rgb();
+                    for c in 0..3 {
+                        assert!(
+                            (expected[c] - actual[c]).abs() <= 1e-2 * expected[c].max(1.0),
+                            "{name} with wi = {wi}, wo = {}: sampled {actual:?}, evaluated {expected:?}",
+                            sample.wo
+                        );
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn energy_conservation() {
+    with_si(|si| {
+        for (name, bsdf, directions) in smooth_bsdfs().into_iter().chain(delta_bsdfs()) {
+            for wi in directions {
+                let albedo = albedo(|u1, u2| bsdf.sample(wi, si, u1, u2).spectrum, 100_000);
+                assert!(
+                    albedo.iter().all(|&a| (0.0..=1.02).contains(&a)),
+                    "{name} with wi = {wi} reflects {albedo:?}"
+                );
+            }
+        }
+    });
+}
+
+#[test]
+fn plastic_albedo() {
+    with_si(|si| {
+        for alpha in [0.0, 0.3] {
+            let bsdf = Bsdf::Plastic(PlasticBsdf::new(white(), 1.0, 1.5, alpha));
+            for wi in upper_directions() {
+                let albedo = albedo(|u1, u2| bsdf.sample(wi, si, u1, u2).spectrum, 100_000);
+                // a white substrate under a clear coating only loses the rough reflections that end up below
+                // the surface at grazing angles
+                let min = if alpha == 0.0 || wi.z > 0.5 {
+                    0.97
+                } else {
+                    0.8
+                };
+                assert!(
+                    albedo.iter().all(|&a| (min..=1.02).contains(&a)),
+                    "plastic with alpha = {alpha} and wi = {wi} reflects {albedo:?}"
+                );
+            }
+        }
+    });
+}
+
+#[test]
+fn white_furnace() {
+    let lossless = [
+        (
+            "lambertian",
+            Bsdf::Lambertian(Lambertian::new(white())),
+            upper_directions(),
+        ),
+        (
+            "diffuse transmission",
+            Bsdf::DiffuseTransmission(DiffuseTransmissionBsdf::new(
+                constant(0.5, 0.5, 0.5),
+                constant(0.5, 0.5, 0.5),
+            )),
+            all_directions(),
+        ),
+        (
+            "hair",
+            Bsdf::Hair(HairBsdf::new(Spectrum::zero(), 1.55, 0.3, 0.3, 2.0)),
+            all_directions(),
+        ),
+        (
+            "mirror",
+            Bsdf::Mirror(MirrorBsdf::new(white())),
+            upper_directions(),
+        ),
+        (
+            "dielectric",
+            Bsdf::Dielectric(Dielectric::new(1.0, 1.5, Spectrum::splat(1.0))),
+            all_directions(),
+        ),
+        (
+            "thin dielectric",
+            Bsdf::ThinDielectric(ThinDielectricBsdf::new(1.5)),
+            all_directions(),
+        ),
+        ("null", Bsdf::Null(NullBsdf::new()), all_directions()),
+    ];
+
+    with_si(|si| {
+        for (name, bsdf, directions) in lossless {
+            for wi in directions {
+                let albedo = albedo(|u1, u2| bsdf.sample(wi, si, u1, u2).spectrum, 100_000);
+                assert!(
+                    albedo.iter().all(|&a| (a - 1.0).abs() < 0.02),
+                    "{name} with wi = {wi} reflects {albedo:?} in a white furnace"
+                );
+            }
+        }
+    });
+}
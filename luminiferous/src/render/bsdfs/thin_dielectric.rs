@@ -0,0 +1,59 @@
+use crate::prelude::*;
+use crate::{
+    primitive::SurfaceInteraction,
+    spectra::{Spectrum, SpectrumT},
+};
+
+use super::{fresnel_dielectric, util::reflect, BsdfFlags, BsdfSample, BsdfT};
+
+/// An infinitely thin slab of dielectric, light is reflected or passes straight through without being
+/// offset. Interreflections inside of the slab are accounted for in closed form.
+#[derive(Debug, Clone)]
+pub struct ThinDielectricBsdf {
+    eta: f32,
+}
+
+impl ThinDielectricBsdf {
+    pub fn new(eta: f32) -> Self {
+        STATS.bsdfs_created.inc();
+
+        Self { eta }
+    }
+}
+
+impl BsdfT for ThinDielectricBsdf {
+    fn eval(&self, _si: &SurfaceInteraction, _wi: Vector3, _wo: Vector3) -> Spectrum {
+        Spectrum::zero()
+    }
+
+    fn pdf(&self, _si: &SurfaceInteraction, _wi: Vector3, _wo: Vector3) -> f32 {
+        0.0
+    }
+
+    fn sample(&self, wi: Vector3, _si: &SurfaceInteraction, u1: f32, _u2: Point2) -> BsdfSample {
+        let mut r = fresnel_dielectric(Frame3::cos_theta(wi).abs(), self.eta);
+        if r < 1.0 {
+            // sum of the geometric series of bounces between the two interfaces
+            let t = 1.0 - r;
+            r += t * t * r / (1.0 - r * r);
+        }
+
+        if u1 < r {
+            BsdfSample {
+                wo: reflect(wi),
+                sampled: BsdfFlags::DeltaReflection,
+                spectrum: Spectrum::splat(1.0),
+            }
+        } else {
+            BsdfSample {
+                wo: -wi,
+                sampled: BsdfFlags::DeltaTransmission,
+                spectrum: Spectrum::splat(1.0),
+            }
+        }
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::DeltaReflection | BsdfFlags::DeltaTransmission
+    }
+}
@@ -58,6 +58,28 @@ pub(crate) fn fresnel(cos_theta_i: f32, eta: f32) -> (f32, f32, f32, f32) {
     (r_i, cos_theta_t, eta_i, eta_t)
 }
 
+/// Fresnel reflectance of an unpolarized dielectric interface, `eta` is the relative ior of the
+/// transmitted side over the incident side.
+pub(crate) fn fresnel_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let (eta, cos_theta_i) = if cos_theta_i < 0.0 {
+        (eta.recip(), -cos_theta_i)
+    } else {
+        (eta, cos_theta_i)
+    };
+
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i).max(0.0) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        // total internal reflection
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
 #[inline]
 fn fdr_d_eon_irving(inv_eta: f32) -> f32 {
     let inv_eta_2 = inv_eta * inv_eta;
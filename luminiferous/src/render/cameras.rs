@@ -11,6 +11,8 @@ pub struct CameraSample {
     pub p_film: Point2,
     // in the range of [0, 1)^2
     pub p_lens: Point2,
+    // in the range of [0, 1), mapped to the time the shutter is open
+    pub time: f32,
 }
 
 #[enum_dispatch]
@@ -19,6 +21,8 @@ pub trait CameraT {
 
     fn get_film(&self) -> &Film;
 
+    fn get_film_mut(&mut self) -> &mut Film;
+
     fn medium(&self) -> Option<Medium>;
 }
 
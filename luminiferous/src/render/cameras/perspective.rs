@@ -10,6 +10,10 @@ pub struct PerspectiveCamera {
     pub focal_dist: f32,
     film: Film,
     to_world: Transform3,
+    /// Moves the camera while the shutter is open, replacing `to_world`.
+    motion: Option<AnimatedTransform>,
+    /// The times the shutter opens and closes at.
+    shutter: (f32, f32),
     medium: Option<Medium>,
 }
 
@@ -54,9 +58,21 @@ impl PerspectiveCamera {
             ),
             lens_radius,
             focal_dist,
+            motion: None,
+            shutter: (0.0, 0.0),
             medium,
         }
     }
+
+    pub fn with_shutter(mut self, open: f32, close: f32) -> Self {
+        self.shutter = (open, close);
+        self
+    }
+
+    pub fn with_motion(mut self, motion: Option<AnimatedTransform>) -> Self {
+        self.motion = motion.filter(|m| m.is_animated());
+        self
+    }
 }
 
 impl CameraT for PerspectiveCamera {
@@ -65,7 +81,8 @@ impl CameraT for PerspectiveCamera {
             .raster_to_camera
             .transform_point(sample.p_film.extend(0.0));
 
-        let mut ray = Ray::new(Vector3::splat(0.0), p_camera.normalize());
+        let time = self.shutter.0 + sample.time * (self.shutter.1 - self.shutter.0);
+        let mut ray = Ray::new(Vector3::splat(0.0), p_camera.normalize()).with_time(time);
 
         if self.lens_radius > 0.0 {
             let p_lens = self.lens_radius * warp::square_to_uniform_disk_concentric(sample.p_lens);
@@ -77,13 +94,19 @@ impl CameraT for PerspectiveCamera {
             ray.d = (ray.o - focus).normalize();
         }
 
-        self.to_world.transform_ray(ray)
+        self.motion
+            .map_or(self.to_world, |motion| motion.at(time))
+            .transform_ray(ray)
     }
 
     fn get_film(&self) -> &Film {
         &self.film
     }
 
+    fn get_film_mut(&mut self) -> &mut Film {
+        &mut self.film
+    }
+
     fn medium(&self) -> Option<Medium> {
         self.medium.clone()
     }
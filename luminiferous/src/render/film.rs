@@ -49,6 +49,8 @@ impl Clone for Pixel {
 #[derive(Debug)]
 pub struct Film {
     pixels: Array2d<Pixel>,
+    /// The contributions of the lights in each light group, next to its name.
+    light_groups: Vec<(String, Array2d<Pixel>)>,
     filter: RFilter,
 
     tev_reporter: Mutex<TevReporter>,
@@ -64,6 +66,7 @@ impl Film {
 
         Self {
             pixels: Array2d::with_default(extent, Pixel::default()),
+            light_groups: Vec::new(),
             filter: filter.into(),
             tev_reporter: Mutex::new(tev_reporter),
         }
@@ -73,6 +76,25 @@ impl Film {
         self.pixels.get_extent()
     }
 
+    /// Sets up a layer for every light group, which the contributions of the lights in the group are
+    /// accumulated into on top of the image.
+    pub fn set_light_groups(&mut self, names: Vec<String>) {
+        let extent = self.get_extent();
+        STATS.film_memory.add(
+            names.len() as u64 * extent.x as u64 * extent.y as u64 * size_of::<Pixel>() as u64,
+        );
+
+        self.light_groups = names
+            .into_iter()
+            .map(|name| (name, Array2d::with_default(extent, Pixel::default())))
+            .collect();
+    }
+
+    #[inline]
+    pub fn light_group_count(&self) -> usize {
+        self.light_groups.len()
+    }
+
     fn get_sample_bounds(&self, p: Point2) -> UBounds2 {
         let min = ((p - self.filter.get_radius() + Vector2::splat(0.5))
             .floor()
@@ -114,10 +136,19 @@ impl Film {
     }
 
     pub fn create_tile(&self, bounds: UBounds2) -> FilmTile {
-        FilmTile::new(bounds, self.filter.clone())
+        FilmTile::new(bounds, self.filter.clone(), self.light_groups.len())
     }
 
     pub fn apply_tile(&self, tile: FilmTile) {
+        self.merge_tile_layer(&tile, &tile.pixels, &self.pixels);
+        for ((_, pixels), tile_pixels) in self.light_groups.iter().zip(&tile.light_groups) {
+            self.merge_tile_layer(&tile, tile_pixels, pixels);
+        }
+
+        self.report_tile(tile.bounds);
+    }
+
+    fn merge_tile_layer(&self, tile: &FilmTile, from: &Array2d<Pixel>, to: &Array2d<Pixel>) {
         let bounds = tile.border_bounds;
 
         for i in bounds.min.y..bounds.max.y {
@@ -125,7 +156,7 @@ impl Film {
                 let tx = j - bounds.min.x;
                 let ty = i - bounds.min.y;
 
-                let p = &tile.pixels[ty as usize][tx as usize];
+                let p = &from[ty as usize][tx as usize];
                 let weight = p.filter_weight_sum.load(Ordering::Acquire);
                 let x = p.contribution_sum_xyz[0].load(Ordering::Acquire);
                 let y = p.contribution_sum_xyz[1].load(Ordering::Acquire);
@@ -138,19 +169,14 @@ impl Film {
                     .saturating_sub(tile.border_size.x)
                     .min(self.get_extent().x - 1);
 
-                self.pixels[i as usize][j as usize]
+                to[i as usize][j as usize]
                     .filter_weight_sum
                     .fetch_add(weight, Ordering::Release);
-                self.pixels[i as usize][j as usize].contribution_sum_xyz[0]
-                    .fetch_add(x, Ordering::Release);
-                self.pixels[i as usize][j as usize].contribution_sum_xyz[1]
-                    .fetch_add(y, Ordering::Release);
-                self.pixels[i as usize][j as usize].contribution_sum_xyz[2]
-                    .fetch_add(z, Ordering::Release);
+                to[i as usize][j as usize].contribution_sum_xyz[0].fetch_add(x, Ordering::Release);
+                to[i as usize][j as usize].contribution_sum_xyz[1].fetch_add(y, Ordering::Release);
+                to[i as usize][j as usize].contribution_sum_xyz[2].fetch_add(z, Ordering::Release);
             }
         }
-
-        self.report_tile(tile.bounds);
     }
 
     pub fn report_tile(&self, bounds: UBounds2) {
@@ -174,7 +200,8 @@ impl Film {
         }
     }
 
-    /// Writes the render artifacts to the filesystem into the specified directory.
+    /// Writes the render artifacts to the filesystem into the specified directory. Light groups are written
+    /// as `<group>.R`, `<group>.G` and `<group>.B` channels next to the image.
     pub fn develop(&self, directory: &Path) {
         use exr::prelude::*;
 
@@ -186,21 +213,62 @@ impl Film {
             }
         }
 
-        write_rgb_file(
-            path,
-            self.pixels.get_extent().x as usize,
-            self.pixels.get_extent().y as usize,
-            |x, y| {
-                let p = &self.pixels[y][x];
-                p.xyz()
-            },
-        )
-        .unwrap();
+        if self.light_groups.is_empty() {
+            write_rgb_file(
+                path,
+                self.pixels.get_extent().x as usize,
+                self.pixels.get_extent().y as usize,
+                |x, y| {
+                    let p = &self.pixels[y][x];
+                    p.xyz()
+                },
+            )
+            .unwrap();
+            return;
+        }
+
+        let extent = self.get_extent();
+        let channels = |prefix: &str, pixels: &Array2d<Pixel>| {
+            let mut rgb = [vec![], vec![], vec![]];
+            for y in 0..extent.y as usize {
+                for x in 0..extent.x as usize {
+                    let (r, g, b) = pixels[y][x].xyz();
+                    rgb[0].push(r);
+                    rgb[1].push(g);
+                    rgb[2].push(b);
+                }
+            }
+
+            ["R", "G", "B"]
+                .into_iter()
+                .zip(rgb)
+                .map(|(channel, samples)| {
+                    AnyChannel::new(
+                        format!("{prefix}{channel}").as_str(),
+                        FlatSamples::F32(samples),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut all = channels("", &self.pixels);
+        for (name, pixels) in &self.light_groups {
+            all.extend(channels(&format!("{name}."), pixels));
+        }
+
+        let layer = Layer::new(
+            (extent.x as usize, extent.y as usize),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(all.into()),
+        );
+        Image::from_layer(layer).write().to_file(path).unwrap();
     }
 }
 
 pub struct FilmTile {
     pixels: Array2d<Pixel>,
+    light_groups: Vec<Array2d<Pixel>>,
     pub bounds: UBounds2,
     pub border_bounds: UBounds2,
     pub border_size: UVector2,
@@ -208,11 +276,15 @@ pub struct FilmTile {
 }
 
 impl FilmTile {
-    pub fn new(bounds: UBounds2, filter: RFilter) -> Self {
+    pub fn new(bounds: UBounds2, filter: RFilter, light_group_count: usize) -> Self {
         let border_size = (filter.get_radius() - 0.5).ceil().as_uvec2();
         let border_bounds = UBounds2::new(bounds.min, bounds.max + border_size * 2);
         FilmTile {
             pixels: Array2d::with_default(border_bounds.extent(), Pixel::default()),
+            light_groups: vec![
+                Array2d::with_default(border_bounds.extent(), Pixel::default());
+                light_group_count
+            ],
             bounds,
             border_bounds,
             border_size,
@@ -238,7 +310,8 @@ impl FilmTile {
         UBounds2::new(min, max)
     }
 
-    pub fn apply_sample(&self, p: Point2, sample: Spectrum) {
+    /// Adds a sample to the tile, along with the parts of it that came from each light group.
+    pub fn apply_sample(&self, p: Point2, sample: Spectrum, light_groups: &[Spectrum]) {
         let p = p + self.border_size.as_vec2();
         let bounds = self.get_sample_bounds(p);
 
@@ -250,17 +323,21 @@ impl FilmTile {
                 let weight = self.filter.eval(p1);
 
                 if weight >= 0.0 {
-                    let [x, y, z] = sample.to_rgb();
-
-                    self.pixels[i as usize][j as usize]
-                        .filter_weight_sum
-                        .fetch_add(weight, Ordering::Release);
-                    self.pixels[i as usize][j as usize].contribution_sum_xyz[0]
-                        .fetch_add(x * weight, Ordering::Release);
-                    self.pixels[i as usize][j as usize].contribution_sum_xyz[1]
-                        .fetch_add(y * weight, Ordering::Release);
-                    self.pixels[i as usize][j as usize].contribution_sum_xyz[2]
-                        .fetch_add(z * weight, Ordering::Release);
+                    let layers = std::iter::once((&self.pixels, sample))
+                        .chain(self.light_groups.iter().zip(light_groups.iter().copied()));
+                    for (pixels, sample) in layers {
+                        let [x, y, z] = sample.to_rgb();
+
+                        pixels[i as usize][j as usize]
+                            .filter_weight_sum
+                            .fetch_add(weight, Ordering::Release);
+                        pixels[i as usize][j as usize].contribution_sum_xyz[0]
+                            .fetch_add(x * weight, Ordering::Release);
+                        pixels[i as usize][j as usize].contribution_sum_xyz[1]
+                            .fetch_add(y * weight, Ordering::Release);
+                        pixels[i as usize][j as usize].contribution_sum_xyz[2]
+                            .fetch_add(z * weight, Ordering::Release);
+                    }
                 }
             }
         }
@@ -358,3 +435,61 @@ impl ParallelIterator for TileProvider {
             .drive_unindexed(consumer)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::rfilters::TentFilter;
+
+    use super::*;
+
+    #[test]
+    fn light_groups_sum_to_the_image() {
+        let extent = UExtent2::new(8, 8);
+        let mut film = Film::new(extent, TentFilter::new(Vector2::splat(1.5)));
+        film.set_light_groups(vec!["key".to_string(), "fill".to_string()]);
+
+        let mut rng = oorandom::Rand32::new(3);
+        for bounds in [
+            UBounds2::new(UVector2::new(0, 0), UVector2::new(4, 8)),
+            UBounds2::new(UVector2::new(4, 0), UVector2::new(8, 8)),
+        ] {
+            let tile = film.create_tile(bounds);
+            let tile_extent = tile.get_extent();
+            for ty in 0..tile_extent.y {
+                for tx in 0..tile_extent.x {
+                    for _ in 0..4 {
+                        let p =
+                            Point2::new(tx as f32 + rng.rand_float(), ty as f32 + rng.rand_float());
+                        let groups = [
+                            Spectrum::from_rgb(
+                                rng.rand_float(),
+                                rng.rand_float(),
+                                rng.rand_float(),
+                            ),
+                            Spectrum::from_rgb(rng.rand_float(), 0.0, rng.rand_float()),
+                        ];
+                        tile.apply_sample(p, groups[0] + groups[1], &groups);
+                    }
+                }
+            }
+            film.apply_tile(tile);
+        }
+
+        for y in 0..extent.y as usize {
+            for x in 0..extent.x as usize {
+                let image = film.pixels[y][x].xyz();
+                let (key, fill) = (
+                    film.light_groups[0].1[y][x].xyz(),
+                    film.light_groups[1].1[y][x].xyz(),
+                );
+                let sum = (key.0 + fill.0, key.1 + fill.1, key.2 + fill.2);
+                assert!(
+                    (image.0 - sum.0).abs() < 1e-4
+                        && (image.1 - sum.1).abs() < 1e-4
+                        && (image.2 - sum.2).abs() < 1e-4,
+                    "pixel ({x}, {y}) is {image:?} but its light groups sum to {sum:?}"
+                );
+            }
+        }
+    }
+}
@@ -2,21 +2,20 @@ use std::path::Path;
 
 use rayon::prelude::*;
 
-use crate::lights::Light;
 use crate::media::MediumInteraction;
 use crate::prelude::*;
-use crate::primitive::SurfaceInteraction;
+use crate::primitive::{Interaction, LightLink, SurfaceInteraction};
 use crate::{
     bsdfs::BsdfFlags,
     cameras::{CameraSample, CameraT},
     core::ProgressBar,
     film::TileProvider,
     lights::LightT,
-    materials::MaterialT,
+    materials::{Material, MaterialT, SubsurfaceMaterial},
     media::MediumT,
     phase_functions::PhaseFunctionT,
     samplers::{Sampler, SamplerT},
-    scene::Scene,
+    scene::{Occlusion, Scene},
     spectra::{Spectrum, SpectrumT},
 };
 
@@ -37,41 +36,58 @@ impl PathIntegrator {
         }
     }
 
+    /// Direct lighting from the light at `light_index`, picked with probability `pmf`, weighted against
+    /// reaching it by sampling `lobe`, which returns the value and the density of a direction at `si`.
     fn sample_light_from_surface(
         &self,
         scene: &Scene,
-        light: &Light,
-        ray: Ray,
+        light_index: usize,
+        pmf: f32,
         si: &SurfaceInteraction,
+        lobe: impl Fn(Vector3) -> (Spectrum, f32),
         sampler: &mut Sampler,
     ) -> Spectrum {
-        let emitted = light.sample_li(&si.as_interaction(), sampler.next_2d());
-
-        if !scene.unoccluded(emitted.visibility) {
+        if !si.primitive.light_link.contains(light_index) {
             return Spectrum::zero();
         }
-        let f = scene.materials[si.primitive.material_index].eval(si, emitted.wo, ray.d);
+        let emitted = scene.lights[light_index].sample_li(&si.as_interaction(), sampler.next_2d());
 
-        f * emitted.li * emitted.wo.dot(si.n).abs()
+        let occlusion = scene.occlusion(emitted.visibility, light_index);
+        if occlusion == Occlusion::Blocked {
+            return Spectrum::zero();
+        }
+        let (f, pdf) = lobe(emitted.wo);
+        let weight = if emitted.pdf > 0.0 && occlusion == Occlusion::Clear {
+            power_heuristic(pmf * emitted.pdf, pdf)
+        } else {
+            1.0
+        };
+
+        f * emitted.li * emitted.wo.dot(si.n).abs() * weight / pmf
     }
 
     fn sample_light_from_medium(
         &self,
         scene: &Scene,
-        light: &Light,
+        light_index: usize,
+        pmf: f32,
         ray: Ray,
         mi: &MediumInteraction,
         sampler: &mut Sampler,
     ) -> Spectrum {
         if let Some(pf) = &mi.phase_function && mi.medium.is_some() {
-            let emitted = light.sample_li(&mi.as_interaction(), sampler.next_2d());
+            let emitted = scene.lights[light_index].sample_li(&mi.as_interaction(), sampler.next_2d());
+            // whether the shadow ray passed through primitives that don't cast shadows for the light
+            let mut unlinked = false;
             let mut transmittance = {
                 let mut medium = mi.medium.unwrap();
                 let mut ray = emitted.visibility.ray;
                 let mut transmittance = Spectrum::splat(1.0);
                 
                 while let (Some(intersection), _) = scene.intersect(ray) {
-                    if scene.materials[intersection.primitive.material_index].bsdf_flags() != BsdfFlags::Null
+                    if !intersection.primitive.shadow_link.contains(light_index) {
+                        unlinked = true;
+                    } else if scene.materials[intersection.primitive.material_index].bsdf_flags() != BsdfFlags::Null
                     {
                         return Spectrum::zero();
                     }
@@ -99,7 +115,12 @@ impl PathIntegrator {
             
             if !transmittance.is_black() {
                 let f = pf.eval(mi, emitted.wo, ray.d);
-                transmittance *= f * emitted.wo.dot(mi.wi).abs();
+                let weight = if emitted.pdf > 0.0 && !unlinked {
+                    power_heuristic(pmf * emitted.pdf, f)
+                } else {
+                    1.0
+                };
+                transmittance *= f * emitted.wo.dot(mi.wi).abs() * weight / pmf;
             }
 
             return transmittance * emitted.li;
@@ -107,6 +128,48 @@ impl PathIntegrator {
 
         Spectrum::zero()
     }
+
+    /// Weight of emission found by sampling the direction `wi` from `last`, the previous vertex and the
+    /// density of the direction, against finding it by sampling the light at `light_index` from there.
+    fn emission_weight(
+        &self,
+        scene: &Scene,
+        light_index: usize,
+        last: Option<(Interaction, f32)>,
+        wi: Vector3,
+    ) -> f32 {
+        let Some((interaction, pdf)) = last else {
+            return 1.0;
+        };
+
+        let light_pdf = scene.light_pmf(light_index) * scene.lights[light_index].pdf_li(&interaction, wi);
+        power_heuristic(pdf, light_pdf)
+    }
+}
+
+/// Adds the light arriving from the light at `light_index` to the path, and to the light group of the light.
+#[inline]
+fn add_light(
+    scene: &Scene,
+    light_index: usize,
+    l: Spectrum,
+    contributed: &mut Spectrum,
+    light_groups: &mut [Spectrum],
+) {
+    *contributed += l;
+    if let Some(group) = scene.light_groups[light_index] {
+        light_groups[group] += l;
+    }
+}
+
+#[inline]
+fn power_heuristic(f_pdf: f32, g_pdf: f32) -> f32 {
+    let (f, g) = (f_pdf * f_pdf, g_pdf * g_pdf);
+    if f + g > 0.0 {
+        f / (f + g)
+    } else {
+        0.0
+    }
 }
 
 impl IntegratorT for PathIntegrator {
@@ -122,6 +185,7 @@ impl IntegratorT for PathIntegrator {
             .into_par_iter()
             .for_each(|bounds| {
                 let tile = film.create_tile(bounds);
+                let mut light_groups = vec![Spectrum::zero(); film.light_group_count()];
 
                 let tile_extent = tile.get_extent();
                 for ty in 0..tile_extent.y {
@@ -139,12 +203,20 @@ impl IntegratorT for PathIntegrator {
                             let mut ray = scene.camera.sample_ray(CameraSample {
                                 p_film: p,
                                 p_lens: pixel_sampler.next_2d(),
+                                time: pixel_sampler.next_1d(),
                             });
                             STATS.camera_rays_traced.inc();
 
                             let mut surface_reflectance = Spectrum::from_rgb(1.0, 1.0, 1.0);
                             let mut contributed = Spectrum::zero();
+                            light_groups.fill(Spectrum::zero());
                             let mut medium = scene.camera.medium();
+                            // the last scattering vertex and the density of the direction sampled there, only
+                            // when the lights were also sampled from it so the two can be weighted
+                            let mut last_scatter: Option<(Interaction, f32)> = None;
+                            // the lights linked to the last surface the path scattered off, camera rays and
+                            // media see all of them
+                            let mut last_light_link: Option<&LightLink> = None;
 
                             let mut depth = 1;
                             let mut _num_tests = 0;
@@ -164,23 +236,26 @@ impl IntegratorT for PathIntegrator {
                                 };
 
                                 if let Some((mi, l)) = &mi {
-                                    if mi.phase_function.is_some() {
+                                    if let Some(pf) = &mi.phase_function {
                                         surface_reflectance *= *l;
-                                        for light in scene.lights.iter() {
-                                            contributed += surface_reflectance
+                                        let light = scene.sample_light(pixel_sampler.next_1d());
+                                        if let Some((light_index, pmf)) = light {
+                                            let l = surface_reflectance
                                                 * self.sample_light_from_medium(
                                                     &scene,
-                                                    light,
+                                                    light_index,
+                                                    pmf,
                                                     ray,
                                                     mi,
                                                     &mut pixel_sampler,
                                                 );
+                                            add_light(&scene, light_index, l, &mut contributed, &mut light_groups);
                                         }
-                                        let sample = mi
-                                            .phase_function
-                                            .as_ref()
-                                            .unwrap()
-                                            .sample(mi, pixel_sampler.next_2d());
+                                        let sample = pf.sample(mi, pixel_sampler.next_2d());
+                                        last_scatter = light
+                                            .is_some()
+                                            .then(|| (mi.as_interaction(), pf.eval(mi, sample.wo, ray.d)));
+                                        last_light_link = None;
                                         ray = mi.as_interaction().spawn_ray(sample.wo);
                                     }
                                 } else if let Some(si) = interaction {
@@ -189,9 +264,19 @@ impl IntegratorT for PathIntegrator {
                                     // break 'outer;
 
                                     if let Some(area_light_index) = si.primitive.area_light_index {
-                                        let l = scene.lights[area_light_index].l_e(-ray.d);
-
-                                        contributed += surface_reflectance * l;
+                                        if last_light_link.is_some_and(|link| !link.contains(area_light_index)) {
+                                            break 'outer;
+                                        }
+                                        let l = scene.lights[area_light_index].l(&si, -ray.d);
+                                        let weight = self.emission_weight(&scene, area_light_index, last_scatter, ray.d);
+
+                                        add_light(
+                                            &scene,
+                                            area_light_index,
+                                            surface_reflectance * l * weight,
+                                            &mut contributed,
+                                            &mut light_groups,
+                                        );
                                         break 'outer;
                                     }
 
@@ -203,17 +288,59 @@ impl IntegratorT for PathIntegrator {
                                     if l.has_nan() {
                                         break;
                                     }
-                                    if sample.sampled.contains(BsdfFlags::Smooth){
-                                        for light in scene.lights.iter() {
-                                            contributed += surface_reflectance
+                                    if let Material::Subsurface(subsurface) = material
+                                        && sample.sampled.contains(BsdfFlags::Subsurface)
+                                    {
+                                        let Some((exit, wo, throughput)) = subsurface.random_walk(
+                                            &scene,
+                                            &si,
+                                            sample.wo,
+                                            &mut pixel_sampler,
+                                        ) else {
+                                            break;
+                                        };
+
+                                        surface_reflectance *= throughput;
+
+                                        // the walk leaves through a diffuse lobe, so the lights are sampled from there
+                                        let exit_lobe = |w| SubsurfaceMaterial::exit_lobe(exit.n, wo, w);
+                                        let light = scene.sample_light(pixel_sampler.next_1d());
+                                        if let Some((light_index, pmf)) = light {
+                                            let l = surface_reflectance
                                                 * self.sample_light_from_surface(
                                                     &scene,
-                                                    light,
-                                                    ray,
-                                                    &si,
+                                                    light_index,
+                                                    pmf,
+                                                    &exit,
+                                                    exit_lobe,
                                                     &mut pixel_sampler,
                                                 );
+                                            add_light(&scene, light_index, l, &mut contributed, &mut light_groups);
                                         }
+
+                                        last_scatter = light.is_some().then(|| (exit.as_interaction(), exit_lobe(wo).1));
+                                        last_light_link = Some(&exit.primitive.light_link);
+                                        ray = exit.as_interaction().spawn_ray(wo);
+                                        medium = exit.target_medium(wo);
+                                        depth += 1;
+                                        continue;
+                                    }
+                                    let light = if material.bsdf_flags().intersects(BsdfFlags::Smooth) {
+                                        scene.sample_light(pixel_sampler.next_1d())
+                                    } else {
+                                        None
+                                    };
+                                    if let Some((light_index, pmf)) = light {
+                                        let l = surface_reflectance
+                                            * self.sample_light_from_surface(
+                                                &scene,
+                                                light_index,
+                                                pmf,
+                                                &si,
+                                                |wo| (material.eval(&si, -ray.d, wo), material.pdf(&si, -ray.d, wo)),
+                                                &mut pixel_sampler,
+                                            );
+                                        add_light(&scene, light_index, l, &mut contributed, &mut light_groups);
                                     }
                                     surface_reflectance *= l;
 
@@ -222,18 +349,31 @@ impl IntegratorT for PathIntegrator {
                                     }
 
                                     if sample.sampled != BsdfFlags::Null {
+                                        // delta lobes can't reach a light that was sampled, so their hits count fully
+                                        last_scatter = (light.is_some() && !sample.sampled.intersects(BsdfFlags::Delta))
+                                            .then(|| (si.as_interaction(), material.pdf(&si, -ray.d, sample.wo)));
+                                        last_light_link = Some(&si.primitive.light_link);
                                         ray = si.as_interaction().spawn_ray(sample.wo);
                                     } else {
                                         depth -= 1;
-                                        ray = Ray::new(si.p + ray.d * 1e-5, ray.d);
+                                        ray = Ray::new(si.p + ray.d * 1e-5, ray.d).with_time(ray.time);
                                     }
                                     medium = si.target_medium(sample.wo);
 
                                     // ray = interaction.spawn_ray(sample.wo);
                                 } else {
-                                    for light in scene.lights.iter() {
-                                        if light.is_environment() {
-                                            contributed += surface_reflectance * light.l_e(ray.d);
+                                    for (index, light) in scene.lights.iter().enumerate() {
+                                        if light.is_environment()
+                                            && last_light_link.is_none_or(|link| link.contains(index))
+                                        {
+                                            let weight = self.emission_weight(&scene, index, last_scatter, ray.d);
+                                            add_light(
+                                                &scene,
+                                                index,
+                                                surface_reflectance * light.l_e(ray.d) * weight,
+                                                &mut contributed,
+                                                &mut light_groups,
+                                            );
                                         }
                                     }
 
@@ -248,7 +388,7 @@ impl IntegratorT for PathIntegrator {
                                 STATS.zero_radiance_paths.inc();
                             }
 
-                            tile.apply_sample(tp, contributed);
+                            tile.apply_sample(tp, contributed, &light_groups);
                         }
 
                         progress.advance(1);
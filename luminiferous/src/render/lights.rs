@@ -15,8 +15,23 @@ pub use area::*;
 mod spot;
 pub use spot::*;
 
+mod sky;
+pub use sky::*;
+
+mod ies;
+pub use ies::*;
+
+mod goniometric;
+pub use goniometric::*;
+
+mod projection;
+pub use projection::*;
+
+mod portal;
+pub use portal::*;
+
 use crate::prelude::*;
-use crate::primitive::Interaction;
+use crate::primitive::{Interaction, SurfaceInteraction};
 use crate::spectra::Spectrum;
 
 pub struct Visibility {
@@ -25,8 +40,11 @@ pub struct Visibility {
 }
 
 pub struct LightSample {
+    /// Incident radiance divided by the density of `wo`.
     pub li: Spectrum,
     pub wo: Vector3,
+    /// Solid angle density of `wo`, zero for lights that can only be reached by sampling them.
+    pub pdf: f32,
     pub visibility: Visibility,
 }
 
@@ -36,14 +54,53 @@ pub trait LightT {
 
     fn l_e(&self, wi: Vector3) -> Spectrum;
 
+    /// Radiance leaving the surface of an area light at `si` in the direction `w`.
+    fn l(&self, si: &SurfaceInteraction, w: Vector3) -> Spectrum;
+
     fn sample_li(&self, interaction: &Interaction, u: Point2) -> LightSample;
+
+    /// Solid angle density of `sample_li` choosing `wi` from `interaction`.
+    fn pdf_li(&self, interaction: &Interaction, wi: Vector3) -> f32;
+
+    /// Total power emitted by the light, `scene_radius` is the radius of a sphere bounding the scene for
+    /// lights that are infinitely far away.
+    fn power(&self, scene_radius: f32) -> Spectrum;
 }
 
 #[enum_dispatch(LightT)]
 pub enum Light {
-    Environment(Environment),
+    Environment(Box<Environment>),
     Point(PointLight),
     Distant(DistantLight),
-    Area(AreaLight),
+    Area(Box<AreaLight>),
     Spot(Spotlight),
+    Goniometric(GoniometricLight),
+    Projection(ProjectionLight),
+}
+
+/// Lets large lights be boxed in `Light` so they don't blow up the size of every other variant.
+impl<T: LightT> LightT for Box<T> {
+    fn is_environment(&self) -> bool {
+        (**self).is_environment()
+    }
+
+    fn l_e(&self, wi: Vector3) -> Spectrum {
+        (**self).l_e(wi)
+    }
+
+    fn l(&self, si: &SurfaceInteraction, w: Vector3) -> Spectrum {
+        (**self).l(si, w)
+    }
+
+    fn sample_li(&self, interaction: &Interaction, u: Point2) -> LightSample {
+        (**self).sample_li(interaction, u)
+    }
+
+    fn pdf_li(&self, interaction: &Interaction, wi: Vector3) -> f32 {
+        (**self).pdf_li(interaction, wi)
+    }
+
+    fn power(&self, scene_radius: f32) -> Spectrum {
+        (**self).power(scene_radius)
+    }
 }
@@ -1,39 +1,83 @@
+use std::sync::Arc;
+
 use crate::prelude::*;
-use crate::primitive::Interaction;
+use crate::primitive::{Interaction, SurfaceInteraction};
 use crate::spectra::SpectrumT;
+use crate::textures::{SpectralTexture, TextureT};
 use crate::{primitive::Primitive, spectra::Spectrum};
 
-use super::{LightSample, LightT, Visibility};
+use super::{IesProfile, LightSample, LightT, Visibility};
 
+/// A light attached to the surface of a primitive, with the emitted radiance given by a texture over the uv
+/// coordinates of the surface. One sided lights only emit on the outside of the shape, and the parts cut
+/// out by the alpha of the primitive don't emit at all.
 pub struct AreaLight {
-    radiance: Spectrum,
+    radiance: Arc<SpectralTexture>,
+    two_sided: bool,
+    /// Measured distribution with its nadir along the normal, scales the radiance.
+    profile: Option<Arc<IesProfile>>,
     area: f32,
     pub primitive: Primitive,
 }
 
 impl AreaLight {
-    pub fn new(primitive: Primitive, radiance: Spectrum) -> Self {
+    pub fn new(primitive: Primitive, radiance: SpectralTexture) -> Self {
         STATS.lights_created.inc();
 
         Self {
             area: primitive.area(),
             primitive,
-            radiance,
+            radiance: Arc::new(radiance),
+            two_sided: false,
+            profile: None,
         }
     }
 
-    pub fn multi_new(primitives: Vec<Primitive>, radiance: Spectrum) -> Vec<Self> {
+    /// Creates a light for every primitive, sharing the radiance texture between all of them.
+    pub fn multi_new(
+        primitives: Vec<Primitive>,
+        radiance: SpectralTexture,
+        two_sided: bool,
+    ) -> Vec<Self> {
         STATS.lights_created.add(primitives.len() as u64);
 
+        let radiance = Arc::new(radiance);
         primitives
             .into_iter()
             .map(|p| Self {
                 area: p.area(),
                 primitive: p,
-                radiance,
+                radiance: radiance.clone(),
+                two_sided,
+                profile: None,
             })
             .collect()
     }
+
+    pub fn with_two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+
+    pub fn with_profile(mut self, profile: Arc<IesProfile>) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    #[inline]
+    fn radiance(&self, uv: Point2, n: Normal3, w: Vector3) -> Spectrum {
+        if !self.two_sided && n.dot(w) <= 0.0 {
+            return Spectrum::zero();
+        }
+
+        let radiance = self.radiance.eval_uv(uv);
+        match &self.profile {
+            Some(profile) => {
+                radiance * profile.normalized(Frame3::new(face_forward(n, w)).to_local(w))
+            }
+            None => radiance,
+        }
+    }
 }
 
 impl LightT for AreaLight {
@@ -42,20 +86,286 @@ impl LightT for AreaLight {
     }
 
     fn l_e(&self, _wi: Vector3) -> Spectrum {
-        self.radiance
+        Spectrum::zero()
+    }
+
+    fn l(&self, si: &SurfaceInteraction, w: Vector3) -> Spectrum {
+        self.radiance(si.uv, self.primitive.normal(si.p), w)
     }
 
     fn sample_li(&self, interaction: &Interaction, u: Point2) -> LightSample {
-        let shape_sample = self.primitive.sample(u);
-        let wo = (shape_sample.p - interaction.p).normalize();
+        // sample the directions towards the light directly if the shape can, it's much less noisy for
+        // lights that are close by
+        let (shape_sample, pdf) = match self.primitive.sample_solid_angle(interaction.p, u) {
+            Some(sample) => sample,
+            None => {
+                let shape_sample = self.primitive.sample(u);
+
+                // convert from the density over the area of the light to the density over solid angle
+                let d = shape_sample.p - interaction.p;
+                let cos_theta = shape_sample.n.dot(-d.normalize()).abs();
+                let pdf = if cos_theta > 0.0 {
+                    d.length_squared() / (cos_theta * self.area)
+                } else {
+                    0.0
+                };
+                (shape_sample, pdf)
+            }
+        };
+
+        let d = shape_sample.p - interaction.p;
+        let wo = d.normalize();
+        let ray = interaction.spawn_ray(wo);
+        let (li, pdf) = if d.length_squared() > 0.0 && pdf > 0.0 && pdf.is_finite() {
+            // the whole surface is sampled, the cut out parts are tested the same way as the rays that hit
+            // the light in `pdf_li` so both agree on which directions reach it
+            let distance = d.length();
+            let kept = self.primitive.alpha.is_none()
+                || self.primitive.intersect(ray).is_some_and(|hit| {
+                    (hit.shape_intersection.t - distance).abs() <= 1e-3 * distance.max(1.0)
+                });
+            let li = if kept {
+                self.radiance(shape_sample.uv, shape_sample.n, -wo) / pdf
+            } else {
+                Spectrum::zero()
+            };
+            (li, pdf)
+        } else {
+            (Spectrum::zero(), 0.0)
+        };
 
         LightSample {
-            li: self.l_e(wo),
+            li,
             wo,
+            pdf,
             visibility: Visibility {
-                ray: interaction.spawn_ray(wo),
+                ray,
                 end: shape_sample.p,
             },
         }
     }
+
+    fn pdf_li(&self, interaction: &Interaction, wi: Vector3) -> f32 {
+        let ray = interaction.spawn_ray(wi);
+        let Some(intersection) = self.primitive.intersect(ray) else {
+            return 0.0;
+        };
+
+        if let Some(pdf) = self.primitive.pdf_solid_angle(interaction.p, wi) {
+            return pdf;
+        }
+
+        let p = intersection.get_surface_interaction(ray).p;
+        let cos_theta = self.primitive.normal(p).dot(-wi).abs();
+        if cos_theta == 0.0 {
+            return 0.0;
+        }
+
+        p.distance_squared(interaction.p) / (cos_theta * self.area)
+    }
+
+    fn power(&self, _scene_radius: f32) -> Spectrum {
+        // average the texture over the surface, stratified so small lights still get a reasonable estimate
+        const N: usize = 4;
+        let mut sum = Spectrum::zero();
+        for y in 0..N {
+            for x in 0..N {
+                let u = Point2::new((x as f32 + 0.5) / N as f32, (y as f32 + 0.5) / N as f32);
+                let uv = self.primitive.sample(u).uv;
+                sum += self.primitive.alpha(uv) * self.radiance.eval_uv(uv);
+            }
+        }
+
+        // the profile scales the cosine weighted radiance by its average over the hemisphere
+        let profile = self.profile.as_ref().map_or(1.0, |profile| {
+            const M: usize = 32;
+            let mut sum = 0.0;
+            for y in 0..M {
+                for x in 0..M {
+                    let u = Point2::new((x as f32 + 0.5) / M as f32, (y as f32 + 0.5) / M as f32);
+                    sum += profile.normalized(warp::square_to_cosine_hemisphere(u));
+                }
+            }
+            sum / (M * M) as f32
+        });
+
+        let sides = if self.two_sided { 2.0 } else { 1.0 };
+        sum / (N * N) as f32 * profile * core::f32::consts::PI * self.area * sides
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::ChiSquareTest;
+    use crate::media::MediumInterface;
+    use crate::shapes::{Quad, Shape, Sphere, Triangle};
+    use crate::textures::{ConstantTexture, Texture};
+
+    fn light(shape: Shape, transform: Option<Transform3>) -> AreaLight {
+        AreaLight::new(
+            Primitive::new(shape, 0, None, transform, MediumInterface::none()),
+            SpectralTexture::Constant(ConstantTexture::new(Spectrum::splat(1.0))),
+        )
+        .with_two_sided(true)
+    }
+
+    fn lights() -> Vec<(&'static str, AreaLight)> {
+        vec![
+            (
+                "near sphere",
+                light(
+                    Shape::Sphere(Sphere::new(1.0)),
+                    Some(Transform3::translate(Vector3::new(0.3, -0.2, 1.6))),
+                ),
+            ),
+            (
+                "far sphere",
+                light(
+                    Shape::Sphere(Sphere::new(0.5)),
+                    Some(Transform3::translate(Vector3::new(-2.0, 1.0, 1.5))),
+                ),
+            ),
+            (
+                "triangle",
+                light(
+                    Shape::Triangle(Triangle::new(
+                        [
+                            Point3::new(-1.0, -1.0, 0.5),
+                            Point3::new(2.0, -0.5, 1.0),
+                            Point3::new(0.0, 1.5, 0.3),
+                        ],
+                        [Normal3::Z; 3],
+                        [Point2::ZERO, Point2::X, Point2::Y],
+                    )),
+                    None,
+                ),
+            ),
+        ]
+    }
+
+    /// Too small to sample by solid angle, and too small for the chi-square test to resolve.
+    fn tiny_triangle() -> AreaLight {
+        light(
+            Shape::Triangle(Triangle::new(
+                [
+                    Point3::new(0.0, 0.0, 3.0),
+                    Point3::new(0.02, 0.0, 3.0),
+                    Point3::new(0.0, 0.02, 3.0),
+                ],
+                [Normal3::Z; 3],
+                [Point2::ZERO, Point2::X, Point2::Y],
+            )),
+            None,
+        )
+    }
+
+    fn interaction() -> Interaction {
+        Interaction {
+            p: Point3::ZERO,
+            n: Normal3::Z,
+            wi: Vector3::Z,
+            time: 0.0,
+        }
+    }
+
+    #[test]
+    fn chi2_sample_matches_pdf() {
+        let interaction = interaction();
+        let lights = lights();
+
+        for (name, light) in &lights {
+            let result = ChiSquareTest::default().run(
+                |_, u| {
+                    let sample = light.sample_li(&interaction, u);
+                    (sample.pdf > 0.0).then_some(sample.wo)
+                },
+                |wi| light.pdf_li(&interaction, wi),
+                lights.len(),
+            );
+
+            if let Err(e) = result {
+                panic!("{name}: {e}");
+            }
+        }
+    }
+
+    #[test]
+    fn samples_hit_the_light() {
+        let interaction = interaction();
+
+        for (name, light) in lights()
+            .into_iter()
+            .chain([("tiny triangle", tiny_triangle())])
+        {
+            let mut rng = oorandom::Rand32::new(7);
+            for _ in 0..1000 {
+                let u = Point2::new(rng.rand_float(), rng.rand_float());
+                let sample = light.sample_li(&interaction, u);
+                assert!(
+                    sample.pdf > 0.0,
+                    "{name}: sampled {} with no density",
+                    sample.wo
+                );
+
+                let distance = interaction.p.distance(sample.visibility.end);
+                let hit = interaction.p + sample.wo * distance;
+                assert!(
+                    hit.distance(sample.visibility.end) < 1e-3 * distance,
+                    "{name}: sampled {} but the point is at {}",
+                    sample.wo,
+                    sample.visibility.end
+                );
+
+                let pdf = light.pdf_li(&interaction, sample.wo);
+                assert!(
+                    (pdf - sample.pdf).abs() <= 1e-2 * pdf,
+                    "{name}: sampled {} with a density of {}, evaluated {pdf}",
+                    sample.wo,
+                    sample.pdf
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cut_out_samples_match_the_pdf() {
+        let interaction = interaction();
+        let light = AreaLight::new(
+            Primitive::new(
+                Shape::Quad(Quad::unit()),
+                0,
+                None,
+                Some(Transform3::translate(Vector3::new(0.2, -0.1, 2.0))),
+                MediumInterface::none(),
+            )
+            .with_alpha(Some(Arc::new(Texture::Constant(ConstantTexture::new(0.5))))),
+            SpectralTexture::Constant(ConstantTexture::new(Spectrum::splat(1.0))),
+        )
+        .with_two_sided(true);
+
+        // the samples that are cut out are exactly the directions that miss the light
+        let mut rng = oorandom::Rand32::new(7);
+        let n = 10_000;
+        let mut kept = 0;
+        for _ in 0..n {
+            let u = Point2::new(rng.rand_float(), rng.rand_float());
+            let sample = light.sample_li(&interaction, u);
+            let pdf = light.pdf_li(&interaction, sample.wo);
+            assert_eq!(
+                sample.li.is_black(),
+                pdf == 0.0,
+                "sampled {} with {:?}, evaluated a density of {pdf}",
+                sample.wo,
+                sample.li
+            );
+            kept += usize::from(!sample.li.is_black());
+        }
+
+        let fraction = kept as f32 / n as f32;
+        assert!(
+            (fraction - 0.5).abs() < 0.03,
+            "{fraction} of the samples kept"
+        );
+    }
 }
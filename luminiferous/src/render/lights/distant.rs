@@ -1,12 +1,15 @@
 use crate::prelude::*;
-use crate::primitive::Interaction;
-use crate::spectra::Spectrum;
+use crate::primitive::{Interaction, SurfaceInteraction};
+use crate::spectra::{Spectrum, SpectrumT};
 
 use super::{LightSample, LightT, Visibility};
 
 pub struct DistantLight {
     w_light: Point3,
     radiance: Spectrum,
+    /// One minus the cosine of the half angle of the cone the light comes from, zero if it only comes from
+    /// `w_light`.
+    one_minus_cos_theta_max: f32,
 }
 
 impl DistantLight {
@@ -16,31 +19,91 @@ impl DistantLight {
         Self {
             w_light: w_light.normalize(),
             radiance,
+            one_minus_cos_theta_max: 0.0,
         }
     }
+
+    /// Spreads the light over the cone of directions within `angle` of `w_light`, like the disk of the sun.
+    /// Surfaces facing the light receive the same irradiance as without the cone.
+    pub fn with_angle(mut self, angle: f32) -> Self {
+        let sin_half_angle = (angle.clamp(0.0, core::f32::consts::FRAC_PI_2) / 2.0).sin();
+        self.one_minus_cos_theta_max = 2.0 * sin_half_angle * sin_half_angle;
+        self
+    }
+
+    #[inline]
+    fn is_delta(&self) -> bool {
+        self.one_minus_cos_theta_max == 0.0
+    }
+
+    #[inline]
+    fn in_cone(&self, wi: Vector3) -> bool {
+        1.0 - wi.normalize().dot(self.w_light) <= self.one_minus_cos_theta_max
+    }
+
+    /// Density of the directions in the cone.
+    #[inline]
+    fn cone_pdf(&self) -> f32 {
+        1.0 / (core::f32::consts::TAU * self.one_minus_cos_theta_max)
+    }
 }
 
 impl LightT for DistantLight {
     fn is_environment(&self) -> bool {
-        false
+        // rays that miss the scene can only hit the light if it has a cone
+        !self.is_delta()
+    }
+
+    fn l_e(&self, wi: Vector3) -> Spectrum {
+        if self.is_delta() {
+            return self.radiance;
+        }
+        if !self.in_cone(wi) {
+            return Spectrum::zero();
+        }
+
+        // the cosine weighted solid angle of the cone is pi sin^2 theta_max
+        let sin2_theta_max = self.one_minus_cos_theta_max * (2.0 - self.one_minus_cos_theta_max);
+        self.radiance / (core::f32::consts::PI * sin2_theta_max)
     }
 
-    fn l_e(&self, _wi: Vector3) -> Spectrum {
-        self.radiance
+    fn l(&self, _si: &SurfaceInteraction, _w: Vector3) -> Spectrum {
+        Spectrum::zero()
     }
 
-    fn sample_li(&self, interaction: &Interaction, _u: Point2) -> LightSample {
-        let wo = self.w_light;
+    fn sample_li(&self, interaction: &Interaction, u: Point2) -> LightSample {
+        let (wo, li, pdf) = if self.is_delta() {
+            (self.w_light, self.l_e(self.w_light), 0.0)
+        } else {
+            let wo = Frame3::new(self.w_light).to_world(warp::square_to_uniform_cone(
+                u,
+                self.one_minus_cos_theta_max,
+            ));
+            (wo, self.l_e(wo) / self.cone_pdf(), self.cone_pdf())
+        };
 
         let visibility_ray = interaction.spawn_ray(wo);
 
         LightSample {
             wo,
-            li: self.l_e(wo),
+            li,
+            pdf,
             visibility: Visibility {
                 ray: visibility_ray,
                 end: visibility_ray.at(1e7),
             },
         }
     }
+
+    fn pdf_li(&self, _interaction: &Interaction, wi: Vector3) -> f32 {
+        if !self.is_delta() && self.in_cone(wi) {
+            self.cone_pdf()
+        } else {
+            0.0
+        }
+    }
+
+    fn power(&self, scene_radius: f32) -> Spectrum {
+        self.radiance * core::f32::consts::PI * scene_radius * scene_radius
+    }
 }
@@ -1,58 +1,449 @@
+use std::path::Path;
+
 use crate::prelude::*;
 use crate::{
-    primitive::Interaction,
-    spectra::Spectrum,
-    textures::{SpectralTexture, TextureT},
+    primitive::{Interaction, SurfaceInteraction},
+    spectra::{Spectrum, SpectrumT},
+    textures::{ConstantTexture, ImageTexture, SpectralTexture, TextureT},
 };
 
-use super::{LightSample, LightT, Visibility};
+use super::{LightSample, LightT, Portal, Visibility};
 
+/// An infinitely far away light around the whole scene, with the radiance given by a texture in the
+/// lat-long layout. Directions are importance sampled by the luminance of the texture, or only through
+/// the portals of the light if any of them can be seen.
 pub struct Environment {
     radiance: SpectralTexture,
+    scale: f32,
+    /// Light to world transform, the poles of the map are on the y axis of the light. Only rotations and
+    /// reflections keep the sampling densities correct.
+    transform: Transform3,
+    distribution: Distribution2d,
+    portals: Vec<Portal>,
 }
 
 impl Environment {
     pub fn new(radiance: SpectralTexture) -> Self {
         STATS.lights_created.inc();
 
-        Self { radiance }
+        // textures without a resolution still get enough cells for the distortion at the poles
+        let extent = radiance.extent();
+        let (width, height) = (extent.x.max(64) as usize, extent.y.max(32) as usize);
+
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            // every cell of a row covers the same solid angle, less towards the poles
+            let v = (y as f32 + 0.5) / height as f32;
+            let cos_elevation = ((0.5 - v) * core::f32::consts::PI).cos();
+            for x in 0..width {
+                let u = (x as f32 + 0.5) / width as f32;
+                func.push(radiance.eval_uv(Point2::new(u, v)).y() * cos_elevation);
+            }
+        }
+
+        Self {
+            radiance,
+            scale: 1.0,
+            transform: Transform3::identity(),
+            distribution: Distribution2d::new(&func, width, height),
+            portals: Vec::new(),
+        }
+    }
+
+    /// Loads the map at `path`, or lights the scene with a solid `fallback` colour if it can't be read.
+    pub fn from_path_or(path: &Path, fallback: Spectrum) -> Self {
+        match ImageTexture::try_from_path(path) {
+            Ok(texture) => Self::new(SpectralTexture::Image(texture)),
+            Err(e) => {
+                warnln!(
+                    "could not load environment map {path:?}: {e}, using a solid colour instead"
+                );
+                Self::new(SpectralTexture::Constant(ConstantTexture::new(fallback)))
+            }
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Transform3) -> Self {
+        let m = Matrix3::from_mat4(transform.matrix());
+        self.transform = if (m.transpose() * m).abs_diff_eq(Matrix3::IDENTITY, 1e-3) {
+            transform
+        } else {
+            warnln!("environment lights can only be rotated, ignoring the rest of the transform.");
+            let (_, rotation, _) = transform.matrix().to_scale_rotation_translation();
+            Transform3::new(Matrix4::from_quat(rotation))
+        };
+
+        // the portals sample by the radiance behind them, which has moved
+        let corners = self
+            .portals
+            .drain(..)
+            .map(|p| p.corners())
+            .collect::<Vec<_>>();
+        for corners in corners {
+            self = self.with_portal(corners);
+        }
+
+        self
+    }
+
+    /// Adds a rectangular portal, like a window, that the light enters the scene through, see
+    /// [`Portal::new`]. The light is only sampled through the portals, which is far less noisy for interiors
+    /// lit from outside.
+    pub fn with_portal(mut self, corners: [Point3; 4]) -> Self {
+        let portal = Portal::new(corners, |w| self.l_e(w).y());
+        self.portals.push(portal);
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    fn direction_to_uv(&self, wi: Vector3) -> Point2 {
+        direction_to_lat_long(self.transform.transform_vector_inv(wi).normalize())
+    }
+
+    fn uv_to_direction(&self, uv: Point2) -> (Vector3, f32) {
+        let (wi, cos_elevation) = lat_long_to_direction(uv);
+        (
+            self.transform.transform_vector(wi).normalize(),
+            cos_elevation,
+        )
+    }
+}
+
+impl Environment {
+    /// The sum of the weights of the portals seen from `p`, zero if the light isn't sampled through them.
+    fn portal_weight(&self, p: Point3) -> f32 {
+        self.portals.iter().map(|portal| portal.weight(p)).sum()
+    }
+
+    /// Density of sampling `wi` from `p` through any of the portals.
+    fn portal_pdf(&self, p: Point3, wi: Vector3, total: f32) -> f32 {
+        self.portals
+            .iter()
+            .map(|portal| portal.weighted_pdf(p, wi))
+            .sum::<f32>()
+            / total
+    }
+
+    /// Picks a portal by its weight and samples a direction through it, `None` if no portal can be seen.
+    fn sample_portals(&self, interaction: &Interaction, u: Point2) -> Option<LightSample> {
+        let total = self.portal_weight(interaction.p);
+        if total <= 0.0 {
+            return None;
+        }
+
+        // reuse the sample that picked the portal within its range
+        let mut target = u.x * total;
+        let mut picked = None;
+        for portal in &self.portals {
+            let weight = portal.weight(interaction.p);
+            if weight <= 0.0 {
+                continue;
+            }
+
+            // the last portal that can be seen takes whatever is left over from rounding
+            picked = Some((portal, (target / weight).min(1.0 - f32::EPSILON)));
+            if target < weight {
+                break;
+            }
+            target -= weight;
+        }
+        let (portal, u_x) = picked?;
+
+        // the direction may also go through other portals, all of them add to the density
+        let wo = portal.sample(interaction.p, Point2::new(u_x, u.y))?;
+        let pdf = self.portal_pdf(interaction.p, wo, total);
+        let li = if pdf > 0.0 {
+            self.l_e(wo) / pdf
+        } else {
+            Spectrum::zero()
+        };
+
+        let visibility_ray = interaction.spawn_ray(wo);
+        Some(LightSample {
+            wo,
+            li,
+            pdf,
+            visibility: Visibility {
+                ray: visibility_ray,
+                end: visibility_ray.at(1.0e7),
+            },
+        })
     }
 }
 
+/// Position of a direction in a lat-long map, with the poles on the y axis.
+pub(crate) fn direction_to_lat_long(w: Vector3) -> Point2 {
+    Point2::new(
+        -w.z.atan2(w.x) / core::f32::consts::TAU,
+        -w.y.asin() / core::f32::consts::PI,
+    ) + Vector2::splat(0.5)
+}
+
+/// Inverse of `direction_to_lat_long`, also returns the cosine of the elevation which relates the
+/// densities over the map and over solid angle.
+pub(crate) fn lat_long_to_direction(uv: Point2) -> (Vector3, f32) {
+    let phi = (0.5 - uv.x) * core::f32::consts::TAU;
+    let elevation = (0.5 - uv.y) * core::f32::consts::PI;
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let (sin_elevation, cos_elevation) = elevation.sin_cos();
+
+    (
+        Vector3::new(
+            cos_elevation * cos_phi,
+            sin_elevation,
+            cos_elevation * sin_phi,
+        ),
+        cos_elevation,
+    )
+}
+
 impl LightT for Environment {
     fn is_environment(&self) -> bool {
         true
     }
 
     fn l_e(&self, wi: Vector3) -> Spectrum {
-        let wi = ((Matrix4::from_axis_angle(Vector3::Y, -core::f32::consts::FRAC_PI_3)
-            * wi.extend(0.0))
-        .truncate())
-        .normalize();
-        self.radiance.eval_uv(
-            Point2::new(
-                -wi.z.atan2(wi.x) / core::f32::consts::TAU,
-                -wi.y.asin() / core::f32::consts::PI,
-            ) + Vector2::splat(0.5),
-        )
+        self.radiance.eval_uv(self.direction_to_uv(wi)) * self.scale
+    }
+
+    fn l(&self, _si: &SurfaceInteraction, _w: Vector3) -> Spectrum {
+        Spectrum::zero()
     }
 
     fn sample_li(&self, interaction: &Interaction, u: Point2) -> LightSample {
-        let frame = Frame3::new(interaction.n);
-        let mut wo = -frame.to_local(warp::square_to_uniform_hemisphere(u).normalize());
-        if wo.dot(interaction.n) < 0.0 {
-            wo = -wo;
+        if let Some(sample) = self.sample_portals(interaction, u) {
+            return sample;
         }
 
+        let (uv, map_pdf) = self.distribution.sample_continuous(u);
+        let (wo, cos_elevation) = self.uv_to_direction(uv);
+
+        // the map covers 2pi by pi radians, squashed by the cosine of the elevation
+        let pdf = if cos_elevation > 0.0 {
+            map_pdf / (2.0 * core::f32::consts::PI * core::f32::consts::PI * cos_elevation)
+        } else {
+            0.0
+        };
+        let li = if pdf > 0.0 {
+            self.radiance.eval_uv(uv) * self.scale / pdf
+        } else {
+            Spectrum::zero()
+        };
+
         let visibility_ray = interaction.spawn_ray(wo);
 
         LightSample {
             wo,
-            li: self.l_e(wo),
+            li,
+            pdf,
             visibility: Visibility {
                 ray: visibility_ray,
                 end: visibility_ray.at(1.0e7),
             },
         }
     }
+
+    fn pdf_li(&self, interaction: &Interaction, wi: Vector3) -> f32 {
+        let total = self.portal_weight(interaction.p);
+        if total > 0.0 {
+            return self.portal_pdf(interaction.p, wi, total);
+        }
+
+        let uv = self.direction_to_uv(wi);
+        let cos_elevation = ((0.5 - uv.y) * core::f32::consts::PI).cos();
+        if cos_elevation <= 0.0 {
+            return 0.0;
+        }
+
+        self.distribution.pdf(uv)
+            / (2.0 * core::f32::consts::PI * core::f32::consts::PI * cos_elevation)
+    }
+
+    fn power(&self, scene_radius: f32) -> Spectrum {
+        // average radiance over a grid of directions, the map doesn't need to be seen in detail for this
+        const N: usize = 32;
+        let mut sum = Spectrum::zero();
+        for y in 0..N {
+            for x in 0..N {
+                let u = Point2::new((x as f32 + 0.5) / N as f32, (y as f32 + 0.5) / N as f32);
+                sum += self.l_e(warp::square_to_uniform_sphere(u));
+            }
+        }
+
+        sum / (N * N) as f32
+            * 4.0
+            * core::f32::consts::PI
+            * core::f32::consts::PI
+            * scene_radius
+            * scene_radius
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::ChiSquareTest;
+    use crate::textures::{CheckerboardTexture, TextureMapping};
+
+    fn checkerboard() -> Environment {
+        Environment::new(SpectralTexture::Checkerboard(CheckerboardTexture::new(
+            Spectrum::splat(10.0),
+            Spectrum::splat(0.1),
+            TextureMapping::new(Matrix3::from_scale(Vector2::new(4.0, 2.0))),
+        )))
+    }
+
+    /// The same map rotated, mirrored and scaled.
+    fn transformed() -> Environment {
+        checkerboard()
+            .with_transform(
+                Transform3::rotate(Point3::new(0.3, 1.2, -0.4))
+                    * Transform3::scale(Vector3::new(-1.0, 1.0, 1.0)),
+            )
+            .with_scale(2.5)
+    }
+
+    /// Windows in the ceiling and a wall of a room around the origin.
+    const PORTALS: [[Point3; 4]; 2] = [
+        [
+            Point3::new(-0.5, -0.3, 1.0),
+            Point3::new(-0.5, 0.7, 1.0),
+            Point3::new(0.8, 0.7, 1.0),
+            Point3::new(0.8, -0.3, 1.0),
+        ],
+        [
+            Point3::new(1.0, -0.5, -0.5),
+            Point3::new(1.0, -0.5, 0.5),
+            Point3::new(1.0, 0.5, 0.5),
+            Point3::new(1.0, 0.5, -0.5),
+        ],
+    ];
+
+    fn portals() -> Environment {
+        PORTALS
+            .into_iter()
+            .fold(transformed(), |environment, corners| {
+                environment.with_portal(corners)
+            })
+    }
+
+    fn interaction() -> Interaction {
+        Interaction {
+            p: Point3::ZERO,
+            n: Normal3::Z,
+            wi: Vector3::Z,
+            time: 0.0,
+        }
+    }
+
+    #[test]
+    fn chi2_sample_matches_pdf() {
+        let interaction = interaction();
+
+        for (name, environment) in [
+            ("checkerboard", checkerboard()),
+            ("transformed", transformed()),
+            ("portals", portals()),
+        ] {
+            let result = ChiSquareTest::default().run(
+                |_, u| {
+                    let sample = environment.sample_li(&interaction, u);
+                    (sample.pdf > 0.0).then_some(sample.wo)
+                },
+                |wi| environment.pdf_li(&interaction, wi),
+                3,
+            );
+
+            if let Err(e) = result {
+                panic!("{name}: {e}");
+            }
+        }
+    }
+
+    #[test]
+    fn sample_weight_matches_radiance() {
+        let interaction = interaction();
+
+        for environment in [checkerboard(), transformed(), portals()] {
+            let mut rng = oorandom::Rand32::new(3);
+            for _ in 0..1000 {
+                let u = Point2::new(rng.rand_float(), rng.rand_float());
+                let sample = environment.sample_li(&interaction, u);
+                if sample.pdf <= 0.0 {
+                    continue;
+                }
+
+                let pdf = environment.pdf_li(&interaction, sample.wo);
+                assert!(
+                    (pdf - sample.pdf).abs() <= 1e-3 * pdf,
+                    "sampled {} with a density of {}, evaluated {pdf}",
+                    sample.wo,
+                    sample.pdf
+                );
+
+                let expected = environment.l_e(sample.wo).y();
+                let actual = (sample.li * sample.pdf).y();
+                assert!(
+                    (expected - actual).abs() <= 1e-3 * expected,
+                    "sampled {} with radiance {actual}, expected {expected}",
+                    sample.wo
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn transform_moves_lookups() {
+        let transform = Transform3::rotate(Point3::new(core::f32::consts::FRAC_PI_2, 0.4, 0.0));
+        let environment = checkerboard().with_transform(transform);
+
+        let mut rng = oorandom::Rand32::new(5);
+        for _ in 0..100 {
+            let wi =
+                warp::square_to_uniform_sphere(Point2::new(rng.rand_float(), rng.rand_float()));
+            let expected = checkerboard().l_e(wi).y();
+            let actual = environment.l_e(transform.transform_vector(wi)).y();
+            assert_eq!(expected, actual, "{wi}");
+        }
+    }
+
+    #[test]
+    fn portals_sample_through_windows() {
+        let environment = portals();
+        let interaction = interaction();
+
+        let through_window = |w: Vector3| {
+            PORTALS.iter().any(|corners| {
+                let n = (corners[3] - corners[0]).cross(corners[1] - corners[0]);
+                let t = (corners[0] - interaction.p).dot(n) / w.dot(n);
+                let p = interaction.p + w * t;
+                let (min, max) = (corners[0].min(corners[2]), corners[0].max(corners[2]));
+                t > 0.0 && p.cmpge(min - 1e-3).all() && p.cmple(max + 1e-3).all()
+            })
+        };
+
+        let mut rng = oorandom::Rand32::new(11);
+        for _ in 0..1000 {
+            let u = Point2::new(rng.rand_float(), rng.rand_float());
+            let sample = environment.sample_li(&interaction, u);
+            assert!(
+                through_window(sample.wo),
+                "sampled {} past the windows",
+                sample.wo
+            );
+        }
+
+        assert_eq!(environment.pdf_li(&interaction, -Vector3::Z), 0.0);
+
+        // outside the room none of the windows can be seen, so the whole map is sampled
+        let outside = Interaction {
+            p: Point3::new(3.0, 0.0, 2.0),
+            ..interaction
+        };
+        assert!(environment.pdf_li(&outside, -Vector3::Z) > 0.0);
+    }
 }
@@ -0,0 +1,80 @@
+use crate::prelude::*;
+use crate::primitive::{Interaction, SurfaceInteraction};
+use crate::spectra::{Spectrum, SpectrumT};
+
+use super::{IesProfile, LightSample, LightT, Visibility};
+
+/// A point light with the measured intensity distribution of a luminaire. The nadir of the profile
+/// points down the y axis of the light and its zero horizontal angle along x.
+pub struct GoniometricLight {
+    p: Point3,
+    /// Intensity in the brightest direction of the profile.
+    intensity: Spectrum,
+    profile: IesProfile,
+    light_to_world: Transform3,
+}
+
+impl GoniometricLight {
+    pub fn new(light_to_world: Transform3, intensity: Spectrum, profile: IesProfile) -> Self {
+        STATS.lights_created.inc();
+
+        Self {
+            p: light_to_world.transform_point(Point3::ZERO),
+            intensity,
+            profile,
+            light_to_world,
+        }
+    }
+
+    /// Intensity leaving the light in the direction `w`.
+    fn intensity(&self, w: Vector3) -> Spectrum {
+        let w = self.light_to_world.transform_vector_inv(w).normalize();
+        self.intensity * self.profile.normalized(Vector3::new(w.x, w.z, -w.y))
+    }
+}
+
+impl LightT for GoniometricLight {
+    fn is_environment(&self) -> bool {
+        false
+    }
+
+    fn l_e(&self, wi: Vector3) -> Spectrum {
+        self.intensity(wi)
+    }
+
+    fn l(&self, _si: &SurfaceInteraction, _w: Vector3) -> Spectrum {
+        Spectrum::zero()
+    }
+
+    fn sample_li(&self, interaction: &Interaction, _u: Point2) -> LightSample {
+        let wo = (self.p - interaction.p).normalize();
+
+        LightSample {
+            wo,
+            li: self.intensity(-wo) / self.p.distance_squared(interaction.p),
+            pdf: 0.0,
+            visibility: Visibility {
+                ray: interaction.spawn_ray(wo),
+                end: self.p,
+            },
+        }
+    }
+
+    fn pdf_li(&self, _interaction: &Interaction, _wi: Vector3) -> f32 {
+        0.0
+    }
+
+    fn power(&self, _scene_radius: f32) -> Spectrum {
+        // integrate the profile over the sphere
+        const N: usize = 64;
+        let mut sum = 0.0;
+        for y in 0..N {
+            for x in 0..N {
+                let u = Point2::new((x as f32 + 0.5) / N as f32, (y as f32 + 0.5) / N as f32);
+                sum += self.profile.normalized(warp::square_to_uniform_sphere(u));
+            }
+        }
+
+        self.intensity * (sum / (N * N) as f32 * 4.0 * core::f32::consts::PI)
+    }
+}
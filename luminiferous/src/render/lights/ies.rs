@@ -0,0 +1,252 @@
+use std::{error::Error, fs, path::Path};
+
+use crate::prelude::*;
+
+/// Candela distribution of a luminaire from an IES LM-63 photometric file. Directions are in the photometric
+/// frame, the vertical angle is measured from the nadir along z and the horizontal angle from x towards y.
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+    /// In degrees, increasing.
+    vertical_angles: Vec<f32>,
+    /// In degrees, increasing.
+    horizontal_angles: Vec<f32>,
+    /// Candela for every vertical angle of every horizontal angle.
+    candela: Vec<Vec<f32>>,
+    max_candela: f32,
+}
+
+impl IesProfile {
+    pub fn load_from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        // keywords may be in any 8 bit encoding, only the numbers matter
+        let source = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+        Self::parse(&source)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, Box<dyn Error>> {
+        let mut lines = source.lines();
+        let tilt = lines
+            .by_ref()
+            .map(str::trim)
+            .find(|line| line.starts_with("TILT="))
+            .ok_or("Invalid IES file: no TILT line")?;
+
+        let mut numbers = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f32>());
+        let mut next = || -> Result<f32, Box<dyn Error>> {
+            Ok(numbers.next().ok_or("Invalid IES file: too short")??)
+        };
+
+        // tilt only matters for lamps that are mounted at an angle, it is skipped
+        if tilt == "TILT=INCLUDE" {
+            let _geometry = next()?;
+            let count = next()? as usize;
+            for _ in 0..2 * count {
+                next()?;
+            }
+        } else if tilt != "TILT=NONE" {
+            warnln!("IES tilt file {tilt} is ignored");
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()? as u32;
+        let _units = next()?;
+        let _width = next()?;
+        let _length = next()?;
+        let _height = next()?;
+        let ballast_factor = next()?;
+        let ballast_lamp_factor = next()?;
+        let _input_watts = next()?;
+
+        if photometric_type != 1 {
+            Err(format!(
+                "Unsupported IES file: photometric type {photometric_type}, only type C is supported"
+            ))?;
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            Err("Invalid IES file: no angles")?;
+        }
+
+        let vertical_angles = (0..vertical_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let horizontal_angles = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let scale = multiplier * ballast_factor * ballast_lamp_factor;
+        let candela = (0..horizontal_count)
+            .map(|_| {
+                (0..vertical_count)
+                    .map(|_| Ok(next()? * scale))
+                    .collect::<Result<Vec<_>, Box<dyn Error>>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let max_candela = candela.iter().flatten().fold(0.0f32, |a, &b| a.max(b));
+
+        Ok(Self {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+            max_candela,
+        })
+    }
+
+    /// The brightest value of the distribution.
+    #[inline]
+    pub fn max_candela(&self) -> f32 {
+        self.max_candela
+    }
+
+    /// Luminous intensity towards `w`.
+    pub fn candela(&self, w: Vector3) -> f32 {
+        let theta = w.z.clamp(-1.0, 1.0).acos().to_degrees();
+        let Some((v, tv)) = interval(&self.vertical_angles, theta) else {
+            return 0.0;
+        };
+
+        let phi = self.fold_horizontal(w.y.atan2(w.x).to_degrees().rem_euclid(360.0));
+        let (h, th) = interval(&self.horizontal_angles, phi).unwrap_or((0, 0.0));
+
+        let value = |h: usize, v: usize| {
+            let row = &self.candela[h.min(self.candela.len() - 1)];
+            row[v.min(row.len() - 1)]
+        };
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+        lerp(
+            lerp(value(h, v), value(h, v + 1), tv),
+            lerp(value(h + 1, v), value(h + 1, v + 1), tv),
+            th,
+        )
+    }
+
+    /// Intensity towards `w` relative to the brightest direction.
+    #[inline]
+    pub fn normalized(&self, w: Vector3) -> f32 {
+        if self.max_candela > 0.0 {
+            self.candela(w) / self.max_candela
+        } else {
+            0.0
+        }
+    }
+
+    /// Maps the horizontal angle into the range that is stored, files leave out the parts of symmetric
+    /// distributions that mirror the rest.
+    fn fold_horizontal(&self, phi: f32) -> f32 {
+        let first = self.horizontal_angles[0];
+        let last = self.horizontal_angles[self.horizontal_angles.len() - 1];
+
+        if last == first {
+            // rotationally symmetric
+            first
+        } else if first == 0.0 && last == 90.0 {
+            let phi = if phi > 180.0 { 360.0 - phi } else { phi };
+            if phi > 90.0 {
+                180.0 - phi
+            } else {
+                phi
+            }
+        } else if first == 0.0 && last == 180.0 {
+            if phi > 180.0 {
+                360.0 - phi
+            } else {
+                phi
+            }
+        } else if first == 90.0 && last == 270.0 {
+            if phi < 90.0 {
+                180.0 - phi
+            } else if phi > 270.0 {
+                540.0 - phi
+            } else {
+                phi
+            }
+        } else {
+            phi
+        }
+    }
+}
+
+/// Finds the segment of the increasing `angles` that contains `x` and how far along it `x` is, `None`
+/// outside of the range.
+fn interval(angles: &[f32], x: f32) -> Option<(usize, f32)> {
+    let (first, last) = (angles[0], angles[angles.len() - 1]);
+    if x < first || x > last {
+        return None;
+    }
+    if angles.len() == 1 {
+        return Some((0, 0.0));
+    }
+
+    let i = angles
+        .partition_point(|&a| a <= x)
+        .clamp(1, angles.len() - 1)
+        - 1;
+    let width = angles[i + 1] - angles[i];
+    let t = if width > 0.0 {
+        (x - angles[i]) / width
+    } else {
+        0.0
+    };
+
+    Some((i, t.clamp(0.0, 1.0)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PROFILE: &str = "IESNA:LM-63-2002
+[TEST] a made up downlight
+[MANUFAC] nobody
+TILT=NONE
+1 1000 2.0 4 3 1 2 0.1 0.1 0.0
+1.0 1.0 20
+0 30 60 90
+0 45 90
+100 80 40 0
+100, 60, 20, 0
+100 40 10 0
+";
+
+    #[test]
+    fn parse() {
+        let profile = IesProfile::parse(PROFILE).unwrap();
+
+        assert_eq!(profile.vertical_angles, [0.0, 30.0, 60.0, 90.0]);
+        assert_eq!(profile.horizontal_angles, [0.0, 45.0, 90.0]);
+        assert_eq!(profile.max_candela(), 200.0);
+        assert_eq!(profile.candela[1], [200.0, 120.0, 40.0, 0.0]);
+    }
+
+    #[test]
+    fn eval() {
+        let profile = IesProfile::parse(PROFILE).unwrap();
+        let direction = |theta: f32, phi: f32| {
+            let (theta, phi) = (theta.to_radians(), phi.to_radians());
+            Vector3::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            )
+        };
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-2;
+
+        assert!(close(profile.candela(direction(0.0, 0.0)), 200.0));
+        assert!(close(profile.candela(direction(30.0, 0.0)), 160.0));
+        assert!(close(profile.candela(direction(45.0, 0.0)), 120.0));
+        assert!(close(profile.candela(direction(30.0, 90.0)), 80.0));
+        // quadrant symmetric, so mirrored into the first quadrant
+        assert!(close(profile.candela(direction(30.0, 180.0)), 160.0));
+        assert!(close(profile.candela(direction(30.0, 270.0)), 80.0));
+        assert!(close(profile.candela(direction(30.0, 315.0)), 120.0));
+        // nothing above the horizon
+        assert_eq!(profile.candela(direction(120.0, 0.0)), 0.0);
+    }
+}
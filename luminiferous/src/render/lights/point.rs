@@ -1,6 +1,6 @@
 use crate::prelude::*;
-use crate::primitive::Interaction;
-use crate::spectra::Spectrum;
+use crate::primitive::{Interaction, SurfaceInteraction};
+use crate::spectra::{Spectrum, SpectrumT};
 
 use super::{LightSample, LightT, Visibility};
 
@@ -26,16 +26,29 @@ impl LightT for PointLight {
         self.radiance
     }
 
+    fn l(&self, _si: &SurfaceInteraction, _w: Vector3) -> Spectrum {
+        Spectrum::zero()
+    }
+
     fn sample_li(&self, interaction: &Interaction, _u: Point2) -> LightSample {
         let wo = (self.p - interaction.p).normalize();
 
         LightSample {
             wo,
             li: self.l_e(wo),
+            pdf: 0.0,
             visibility: Visibility {
                 ray: interaction.spawn_ray(wo),
                 end: self.p,
             },
         }
     }
+
+    fn pdf_li(&self, _interaction: &Interaction, _wi: Vector3) -> f32 {
+        0.0
+    }
+
+    fn power(&self, _scene_radius: f32) -> Spectrum {
+        self.radiance * 4.0 * core::f32::consts::PI
+    }
 }
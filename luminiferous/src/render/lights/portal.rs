@@ -0,0 +1,130 @@
+use crate::prelude::*;
+
+/// Resolution of the distribution over the directions through a portal.
+const RESOLUTION: usize = 128;
+
+/// A rectangle the light of an environment comes in through, like a window of an interior. Directions
+/// through it are parameterized by their angles to the normal along the two sides of the rectangle, in which
+/// the rectangle seen from any point is again a rectangle that can be sampled directly (Bitterli et al.,
+/// "Portal-Masked Environment Map Sampling").
+pub struct Portal {
+    corners: [Point3; 4],
+    /// `s` along the side from the first to the last corner, `t` from the first to the second and `n`
+    /// pointing out of the interior.
+    frame: Frame3,
+    distribution: WindowedDistribution2d,
+}
+
+impl Portal {
+    /// Creates a portal with the `corners` of a rectangle, ordered so that `(p3 - p0) x (p1 - p0)` points
+    /// out of the interior. Directions are importance sampled by `luminance`.
+    pub fn new(corners: [Point3; 4], luminance: impl Fn(Vector3) -> f32) -> Self {
+        let s = (corners[3] - corners[0]).normalize();
+        let t = (corners[1] - corners[0]).normalize();
+        if s.dot(t).abs() > 1e-3 || (corners[2] - corners[1]).normalize().dot(s) < 1.0 - 1e-3 {
+            warnln!(
+                "portal {corners:?} is not a rectangle, light through it will be sampled poorly"
+            );
+        }
+
+        let mut portal = Self {
+            corners,
+            frame: Frame3 {
+                n: s.cross(t).normalize(),
+                s,
+                t,
+            },
+            distribution: WindowedDistribution2d::new(&[1.0], 1, 1),
+        };
+
+        // like the environment, the brightest corner of every cell so there is no zero density where there
+        // is light
+        let corner = |x: usize, y: usize| {
+            let uv = Point2::new(x as f32, y as f32) / RESOLUTION as f32;
+            luminance(portal.uv_to_direction(uv).0)
+        };
+        let mut func = Vec::with_capacity(RESOLUTION * RESOLUTION);
+        for y in 0..RESOLUTION {
+            for x in 0..RESOLUTION {
+                func.push(
+                    corner(x, y)
+                        .max(corner(x + 1, y))
+                        .max(corner(x, y + 1))
+                        .max(corner(x + 1, y + 1)),
+                );
+            }
+        }
+        portal.distribution = WindowedDistribution2d::new(&func, RESOLUTION, RESOLUTION);
+
+        portal
+    }
+
+    #[inline]
+    pub fn corners(&self) -> [Point3; 4] {
+        self.corners
+    }
+
+    /// The rectangle of the parameterization covered by the portal as seen from `p`, `None` from outside.
+    fn window(&self, p: Point3) -> Option<(Point2, Point2)> {
+        let (a, _) = self.direction_to_uv(self.corners[0] - p)?;
+        let (b, _) = self.direction_to_uv(self.corners[2] - p)?;
+
+        Some((a.min(b), a.max(b)))
+    }
+
+    /// The parameterization of the direction `w` along with the change in solid angle per area of the
+    /// parameterization, `None` for directions into the interior.
+    fn direction_to_uv(&self, w: Vector3) -> Option<(Point2, f32)> {
+        let w = self.frame.to_local(w.normalize());
+        if w.z <= 0.0 {
+            return None;
+        }
+
+        let uv = Point2::new(w.x.atan2(w.z), w.y.atan2(w.z)) / core::f32::consts::PI
+            + Vector2::splat(0.5);
+        Some((uv, Self::jacobian(w)))
+    }
+
+    fn uv_to_direction(&self, uv: Point2) -> (Vector3, f32) {
+        let angles = (uv - Vector2::splat(0.5)) * core::f32::consts::PI;
+        let w = Vector3::new(angles.x.tan(), angles.y.tan(), 1.0).normalize();
+
+        (self.frame.to_world(w), Self::jacobian(w))
+    }
+
+    /// Solid angle per area of the parameterization, for the local direction `w`.
+    #[inline]
+    fn jacobian(w: Vector3) -> f32 {
+        core::f32::consts::PI * core::f32::consts::PI * (1.0 - w.x * w.x) * (1.0 - w.y * w.y) / w.z
+    }
+
+    /// The integral of the sampling function over the directions through the portal from `p`, relative to
+    /// the other portals of the same light.
+    pub(super) fn weight(&self, p: Point3) -> f32 {
+        self.window(p)
+            .map_or(0.0, |(min, max)| self.distribution.integral(min, max))
+    }
+
+    /// Samples a direction through the portal from `p`.
+    pub(super) fn sample(&self, p: Point3, u: Point2) -> Option<Vector3> {
+        let (min, max) = self.window(p)?;
+        let (uv, _) = self.distribution.sample_continuous(u, min, max)?;
+
+        Some(self.uv_to_direction(uv).0)
+    }
+
+    /// Density over solid angle of sampling `w` from `p` with `sample`, scaled by the weight of the portal.
+    pub(super) fn weighted_pdf(&self, p: Point3, w: Vector3) -> f32 {
+        let Some((min, max)) = self.window(p) else {
+            return 0.0;
+        };
+        let Some((uv, jacobian)) = self.direction_to_uv(w) else {
+            return 0.0;
+        };
+        if jacobian <= 0.0 {
+            return 0.0;
+        }
+
+        self.distribution.pdf(uv, min, max) * self.distribution.integral(min, max) / jacobian
+    }
+}
@@ -0,0 +1,182 @@
+use crate::prelude::*;
+use crate::primitive::{Interaction, SurfaceInteraction};
+use crate::spectra::{Spectrum, SpectrumT};
+use crate::textures::{SpectralTexture, TextureT};
+
+use super::{LightSample, LightT, Visibility};
+
+/// A point light that projects an image through a perspective frustum looking down the z axis of the
+/// light, like a slide projector. The field of view spans the shorter side of the image.
+pub struct ProjectionLight {
+    p: Point3,
+    light_to_world: Transform3,
+    image: SpectralTexture,
+    scale: Spectrum,
+    /// Bounds of the image on the plane at a distance of one.
+    screen_min: Point2,
+    screen_max: Point2,
+}
+
+impl ProjectionLight {
+    pub fn new(
+        light_to_world: Transform3,
+        image: SpectralTexture,
+        scale: Spectrum,
+        fov: f32,
+    ) -> Self {
+        STATS.lights_created.inc();
+
+        let extent = image.extent().max(UExtent2::ONE);
+        let aspect = extent.x as f32 / extent.y as f32;
+        let half = if aspect > 1.0 {
+            Vector2::new(aspect, 1.0)
+        } else {
+            Vector2::new(1.0, aspect.recip())
+        } * (fov.to_radians() / 2.0).tan();
+
+        Self {
+            p: light_to_world.transform_point(Point3::ZERO),
+            light_to_world,
+            image,
+            scale,
+            screen_min: -half,
+            screen_max: half,
+        }
+    }
+
+    /// Scales the light so the image alone emits `power` in total, measured by luminance. Like pbrt-v4 the
+    /// scale the light was created with still applies on top.
+    pub fn with_power(mut self, power: f32) -> Self {
+        let k_e = self.image_power().y();
+        if k_e > 0.0 {
+            self.scale *= power / k_e;
+        }
+        self
+    }
+
+    /// Power of the image without the scale, integrated over the image plane where a patch at (x, y, 1)
+    /// covers dx dy / (1 + x^2 + y^2)^(3/2).
+    fn image_power(&self) -> Spectrum {
+        const N: usize = 64;
+        let size = self.screen_max - self.screen_min;
+        let mut sum = Spectrum::zero();
+        for y in 0..N {
+            for x in 0..N {
+                let uv = Point2::new((x as f32 + 0.5) / N as f32, (y as f32 + 0.5) / N as f32);
+                let p = self.screen_min + uv * size;
+                sum += self.image.eval_uv(uv) * (1.0 + p.length_squared()).powf(-1.5);
+            }
+        }
+
+        sum * (size.x * size.y / (N * N) as f32)
+    }
+
+    /// Intensity leaving the light in the direction `w`.
+    fn intensity(&self, w: Vector3) -> Spectrum {
+        let w = self.light_to_world.transform_vector_inv(w);
+        if w.z <= 0.0 {
+            return Spectrum::zero();
+        }
+
+        let p = Point2::new(w.x / w.z, w.y / w.z);
+        if p.cmplt(self.screen_min).any() || p.cmpgt(self.screen_max).any() {
+            return Spectrum::zero();
+        }
+
+        // images are stored top down
+        let uv = (p - self.screen_min) / (self.screen_max - self.screen_min);
+        self.image.eval_uv(Point2::new(uv.x, 1.0 - uv.y)) * self.scale
+    }
+}
+
+impl LightT for ProjectionLight {
+    fn is_environment(&self) -> bool {
+        false
+    }
+
+    fn l_e(&self, wi: Vector3) -> Spectrum {
+        self.intensity(wi)
+    }
+
+    fn l(&self, _si: &SurfaceInteraction, _w: Vector3) -> Spectrum {
+        Spectrum::zero()
+    }
+
+    fn sample_li(&self, interaction: &Interaction, _u: Point2) -> LightSample {
+        let wo = (self.p - interaction.p).normalize();
+
+        LightSample {
+            wo,
+            li: self.intensity(-wo) / self.p.distance_squared(interaction.p),
+            pdf: 0.0,
+            visibility: Visibility {
+                ray: interaction.spawn_ray(wo),
+                end: self.p,
+            },
+        }
+    }
+
+    fn pdf_li(&self, _interaction: &Interaction, _wi: Vector3) -> f32 {
+        0.0
+    }
+
+    fn power(&self, _scene_radius: f32) -> Spectrum {
+        self.image_power() * self.scale
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::textures::ConstantTexture;
+
+    #[test]
+    fn power_matches_solid_angle() {
+        let fov = 10.0f32;
+        let light = ProjectionLight::new(
+            Transform3::identity(),
+            SpectralTexture::Constant(ConstantTexture::new(Spectrum::splat(1.0))),
+            Spectrum::splat(1.0),
+            fov,
+        );
+        let half = (fov.to_radians() / 2.0).tan();
+        // solid angle of a square pyramid
+        let expected = 4.0 * (half * half / (1.0 + half * half)).asin();
+        let power = light.power(0.0).y();
+        assert!(
+            (power - expected).abs() < 1e-3 * expected,
+            "{power} != {expected}"
+        );
+
+        let light = light.with_power(100.0);
+        assert!((light.power(0.0).y() - 100.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn power_keeps_the_scale() {
+        let light = ProjectionLight::new(
+            Transform3::identity(),
+            SpectralTexture::Constant(ConstantTexture::new(Spectrum::splat(5.0))),
+            Spectrum::splat(2.0),
+            30.0,
+        )
+        .with_power(100.0);
+
+        let power = light.power(0.0).y();
+        assert!((power - 200.0).abs() < 1e-2, "{power} != 200");
+    }
+
+    #[test]
+    fn projects_inside_the_frustum() {
+        let light = ProjectionLight::new(
+            Transform3::translate(Vector3::new(0.0, 2.0, 0.0)),
+            SpectralTexture::Constant(ConstantTexture::new(Spectrum::splat(3.0))),
+            Spectrum::splat(1.0),
+            30.0,
+        );
+
+        assert_eq!(light.l_e(Vector3::Z).y(), Spectrum::splat(3.0).y());
+        assert_eq!(light.l_e(Vector3::new(0.0, 0.5, 1.0).normalize()).y(), 0.0);
+        assert_eq!(light.l_e(-Vector3::Z).y(), 0.0);
+    }
+}
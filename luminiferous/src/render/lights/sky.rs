@@ -0,0 +1,403 @@
+use core::f32::consts::{PI, TAU};
+
+use crate::prelude::*;
+use crate::{
+    core::Array2d,
+    spectra::{Spectrum, SpectrumT},
+    textures::{ImageTexture, SpectralTexture},
+};
+
+use super::{lat_long_to_direction, DistantLight, Environment};
+
+/// Angular radius of the sun seen from the earth.
+const SUN_ANGULAR_RADIUS: f32 = 0.00465;
+/// Luminance of the sun above the atmosphere in kcd/m^2, the unit the sky model works in.
+const SUN_LUMINANCE: f32 = 1.6e6;
+/// How far below the horizon the sun can go before the sky is dark, the end of civil twilight.
+const TWILIGHT: f32 = 6.0 * PI / 180.0;
+
+/// Where and when the sun is seen from, to place it in the sky.
+#[derive(Debug, Clone, Copy)]
+pub struct SunLocation {
+    /// Degrees north of the equator.
+    pub latitude: f32,
+    /// Degrees east of Greenwich.
+    pub longitude: f32,
+    /// Hours the local standard time is ahead of UTC.
+    pub utc_offset: f32,
+    pub month: u32,
+    pub day: u32,
+    /// Local standard time in hours.
+    pub hour: f32,
+}
+
+impl SunLocation {
+    /// Direction towards the sun with y up, north along -z and east along +x, using the approximations
+    /// from the appendix of Preetham et al.
+    pub fn sun_direction(&self) -> Vector3 {
+        let day = day_of_year(self.month, self.day) as f32;
+        let latitude = self.latitude.to_radians();
+        let standard_meridian = (self.utc_offset * 15.0).to_radians();
+
+        let solar_time = self.hour + 0.170 * (2.0 * TAU * (day - 80.0) / 373.0).sin()
+            - 0.129 * (TAU * (day - 8.0) / 355.0).sin()
+            + 12.0 * (self.longitude.to_radians() - standard_meridian) / PI;
+        let declination = 0.4093 * (TAU * (day - 81.0) / 368.0).sin();
+        let hour_angle = PI * solar_time / 12.0;
+
+        let elevation = (latitude.sin() * declination.sin()
+            - latitude.cos() * declination.cos() * hour_angle.cos())
+        .asin();
+        // from the south towards the west
+        let azimuth = (-declination.cos() * hour_angle.sin()).atan2(
+            latitude.cos() * declination.sin()
+                - latitude.sin() * declination.cos() * hour_angle.cos(),
+        );
+
+        Vector3::new(
+            -azimuth.sin() * elevation.cos(),
+            elevation.sin(),
+            azimuth.cos() * elevation.cos(),
+        )
+    }
+}
+
+fn day_of_year(month: u32, day: u32) -> u32 {
+    const DAYS_BEFORE_MONTH: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+    DAYS_BEFORE_MONTH[(month.clamp(1, 12) - 1) as usize] + day
+}
+
+/// Daylight from "A Practical Analytic Model for Daylight" (Preetham et al. 1999) with a diffuse ground
+/// below the horizon. The sky is baked into a lat-long map so it's sampled like any other environment, the
+/// sun is far too small for the map and is a distant light with the angle of its disk instead. Radiance
+/// is in kcd/m^2 like the model, a clear zenith is around ten.
+#[derive(Debug, Clone)]
+pub struct Sky {
+    sun_direction: Vector3,
+    turbidity: f32,
+    ground_albedo: Spectrum,
+    resolution: UExtent2,
+    /// Light to world transform, the sun direction is in the space of the light with y up.
+    transform: Transform3,
+    scale: f32,
+}
+
+impl Sky {
+    pub fn new(sun_direction: Vector3) -> Self {
+        Self {
+            sun_direction: sun_direction.normalize(),
+            turbidity: 3.0,
+            ground_albedo: Spectrum::splat(0.3),
+            resolution: UExtent2::new(2048, 1024),
+            transform: Transform3::identity(),
+            scale: 1.0,
+        }
+    }
+
+    pub fn from_location(location: &SunLocation) -> Self {
+        Self::new(location.sun_direction())
+    }
+
+    /// Haziness of the atmosphere, from about 2 for a clear sky to 10 for a hazy one.
+    pub fn with_turbidity(mut self, turbidity: f32) -> Self {
+        self.turbidity = turbidity.clamp(1.7, 10.0);
+        self
+    }
+
+    pub fn with_ground_albedo(mut self, ground_albedo: Spectrum) -> Self {
+        self.ground_albedo = ground_albedo;
+        self
+    }
+
+    /// Resolution of the baked map.
+    pub fn with_resolution(mut self, resolution: UExtent2) -> Self {
+        self.resolution = resolution.max(UExtent2::splat(2));
+        self
+    }
+
+    /// Rotates the sky and the sun, see [`Environment::with_transform`].
+    pub fn with_transform(mut self, transform: Transform3) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Scales the radiance of the sky and the sun, to bring it from kcd/m^2 to the units of the scene.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    #[inline]
+    fn sun_elevation(&self) -> f32 {
+        self.sun_direction.y.clamp(-1.0, 1.0).asin()
+    }
+
+    /// Radiance of the sun disk, attenuated by the atmosphere along its path.
+    pub fn sun_radiance(&self) -> Spectrum {
+        let elevation = self.sun_elevation();
+        if elevation <= 0.0 {
+            return Spectrum::zero();
+        }
+
+        // relative optical air mass from Kasten and Young
+        let zenith = core::f32::consts::FRAC_PI_2 - elevation;
+        let air_mass =
+            (zenith.cos() + 0.50572 * (96.07995 - zenith.to_degrees()).powf(-1.6364)).recip();
+
+        // rayleigh and aerosol extinction at wavelengths for red, green and blue in micrometers
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = [0.68f32, 0.55, 0.44].map(|lambda| {
+            let rayleigh = 0.008735 * lambda.powf(-4.08);
+            let aerosol = beta * lambda.powf(-1.3);
+            (-(rayleigh + aerosol) * air_mass).exp()
+        });
+
+        Spectrum::from_rgb(transmittance[0], transmittance[1], transmittance[2]) * SUN_LUMINANCE
+    }
+
+    /// Bakes the sky and the ground into an environment light, without the sun.
+    pub fn build(&self) -> Environment {
+        let (width, height) = (self.resolution.x as usize, self.resolution.y as usize);
+        let model = Preetham::new(self.turbidity, self.sun_elevation());
+        let fade = ((self.sun_elevation() + TWILIGHT) / TWILIGHT).clamp(0.0, 1.0);
+
+        // image lookups scale uv by the extent minus one, so that is the spacing of the pixel centres
+        let spacing = Vector2::new((width - 1) as f32, (height - 1) as f32);
+        let pixel = |x: usize, y: usize| {
+            let uv = (Point2::new(x as f32, y as f32) + Vector2::splat(0.5)) / spacing;
+            let (w, cos_elevation) = lat_long_to_direction(uv.min(Point2::ONE));
+            (w, cos_elevation * TAU / spacing.x * PI / spacing.y)
+        };
+
+        let mut pixels = vec![Spectrum::zero(); width * height];
+        let mut irradiance = Spectrum::zero();
+        for y in 0..height {
+            for x in 0..width {
+                let (w, solid_angle) = pixel(x, y);
+                if w.y <= 0.0 {
+                    continue;
+                }
+
+                let l = model.radiance(w, self.sun_direction) * fade;
+                pixels[y * width + x] = l;
+                // the last column repeats the first
+                if x < width - 1 {
+                    irradiance += l * w.y * solid_angle;
+                }
+            }
+        }
+
+        // the ground is lit by the sun as well
+        irradiance += self.sun_radiance() * sun_solid_angle() * self.sun_direction.y.max(0.0);
+
+        let ground = self.ground_albedo * irradiance * core::f32::consts::FRAC_1_PI;
+        for y in 0..height {
+            for x in 0..width {
+                if pixel(x, y).0.y <= 0.0 {
+                    pixels[y * width + x] = ground;
+                }
+            }
+        }
+
+        Environment::new(SpectralTexture::Image(ImageTexture::new(Array2d::from_1d(
+            self.resolution,
+            pixels,
+        ))))
+        .with_transform(self.transform)
+        .with_scale(self.scale)
+    }
+
+    /// The sun as a distant light covering the disk of the sun, `None` once it has set.
+    pub fn sun(&self) -> Option<DistantLight> {
+        let radiance = self.sun_radiance();
+        if radiance.is_black() {
+            return None;
+        }
+
+        // a distant light is given by the irradiance it delivers
+        Some(
+            DistantLight::new(
+                self.transform.transform_vector(self.sun_direction),
+                radiance * sun_solid_angle() * self.scale,
+            )
+            .with_angle(SUN_ANGULAR_RADIUS),
+        )
+    }
+}
+
+/// The cosine weighted solid angle of the disk of the sun, which turns its radiance into irradiance.
+fn sun_solid_angle() -> f32 {
+    PI * SUN_ANGULAR_RADIUS.sin() * SUN_ANGULAR_RADIUS.sin()
+}
+
+/// The Perez sky distributions for luminance and chromaticity, fitted against the turbidity.
+struct Preetham {
+    /// Coefficients A to E for Y, x and y.
+    perez: [[f32; 5]; 3],
+    /// Y, x and y at the zenith.
+    zenith: [f32; 3],
+    sun_zenith: f32,
+}
+
+impl Preetham {
+    fn new(turbidity: f32, sun_elevation: f32) -> Self {
+        let t = turbidity;
+        // the fits only hold for the sun above the horizon
+        let theta = core::f32::consts::FRAC_PI_2 - sun_elevation.max(0.0);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let (theta2, theta3) = (theta * theta, theta * theta * theta);
+        let x = t * t * (0.00166 * theta3 - 0.00375 * theta2 + 0.00209 * theta)
+            + t * (-0.02903 * theta3 + 0.06377 * theta2 - 0.03202 * theta + 0.00394)
+            + (0.11693 * theta3 - 0.21196 * theta2 + 0.06052 * theta + 0.25886);
+        let y = t * t * (0.00275 * theta3 - 0.00610 * theta2 + 0.00317 * theta)
+            + t * (-0.04214 * theta3 + 0.08970 * theta2 - 0.04153 * theta + 0.00516)
+            + (0.15346 * theta3 - 0.26756 * theta2 + 0.06670 * theta + 0.26688);
+
+        Self {
+            perez,
+            zenith: [luminance, x, y],
+            sun_zenith: theta,
+        }
+    }
+
+    #[inline]
+    fn perez(c: &[f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+        let cos_gamma = gamma.cos();
+        (1.0 + c[0] * (c[1] / cos_theta).exp())
+            * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+    }
+
+    /// Radiance of the sky towards `w` above the horizon.
+    fn radiance(&self, w: Vector3, sun_direction: Vector3) -> Spectrum {
+        let cos_theta = w.y.max(1e-3);
+        let gamma = w.dot(sun_direction).clamp(-1.0, 1.0).acos();
+
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * Self::perez(&self.perez[i], cos_theta, gamma)
+                / Self::perez(&self.perez[i], 1.0, self.sun_zenith)
+        });
+
+        // xyY to XYZ to linear sRGB
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+        Spectrum::from_rgb(
+            (3.2404542 * big_x - 1.5371385 * luminance - 0.4985314 * big_z).max(0.0),
+            (-0.969266 * big_x + 1.8760108 * luminance + 0.041556 * big_z).max(0.0),
+            (0.0556434 * big_x - 0.2040259 * luminance + 1.0572252 * big_z).max(0.0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sun_follows_the_day() {
+        // spring equinox in london
+        let location = |hour| SunLocation {
+            latitude: 51.5,
+            longitude: 0.0,
+            utc_offset: 0.0,
+            month: 3,
+            day: 20,
+            hour,
+        };
+
+        let noon = location(12.0).sun_direction();
+        assert!(
+            noon.z > 0.0 && noon.x.abs() < 0.05,
+            "not south at noon: {noon}"
+        );
+        let elevation = noon.y.asin().to_degrees();
+        assert!(
+            (elevation - 38.5).abs() < 1.5,
+            "{elevation} degrees at noon"
+        );
+
+        let morning = location(8.0).sun_direction();
+        assert!(
+            morning.x > 0.0 && morning.y > 0.0,
+            "not rising in the east: {morning}"
+        );
+        let evening = location(16.0).sun_direction();
+        assert!(
+            evening.x < 0.0 && evening.y > 0.0,
+            "not setting in the west: {evening}"
+        );
+
+        let night = location(0.0).sun_direction();
+        assert!(night.y < 0.0, "up at midnight: {night}");
+    }
+
+    #[test]
+    fn sun_is_a_separate_light() {
+        use crate::lights::LightT;
+        use crate::primitive::Interaction;
+
+        let sun_direction = Vector3::new(0.3, 0.8, -0.2).normalize();
+        let sky = Sky::new(sun_direction).with_resolution(UExtent2::new(512, 256));
+        let sun = sky.sun().unwrap();
+
+        // the map only has the sky, which is much darker than the sun
+        let environment = sky.build();
+        assert!(environment.l_e(sun_direction).y() < 1e-3 * sky.sun_radiance().y());
+
+        // the light delivers the irradiance of the disk of the sun to a surface facing it
+        let interaction = Interaction {
+            p: Point3::ZERO,
+            n: sun_direction,
+            wi: sun_direction,
+            time: 0.0,
+        };
+        let n = 64;
+        let mut irradiance = 0.0;
+        for i in 0..n {
+            let u = Point2::new((i as f32 + 0.5) / n as f32, (i * 7 % n) as f32 / n as f32);
+            let sample = sun.sample_li(&interaction, u);
+            assert!((sun.pdf_li(&interaction, sample.wo) - sample.pdf).abs() < 1e-3 * sample.pdf);
+            assert!(sample.wo.dot(sun_direction) >= SUN_ANGULAR_RADIUS.cos() - 1e-6);
+            irradiance += sample.li.y() * sample.wo.dot(sun_direction) / n as f32;
+        }
+
+        let expected = sky.sun_radiance().y() * PI * SUN_ANGULAR_RADIUS * SUN_ANGULAR_RADIUS;
+        assert!(
+            (irradiance - expected).abs() < 1e-2 * expected,
+            "{irradiance} != {expected}"
+        );
+
+        // rays that miss the scene see the disk
+        assert!(sun.is_environment());
+        assert!(sun.l_e(sun_direction).y() > 0.0);
+        assert!(sun.l_e(Vector3::Y).is_black());
+
+        assert!(Sky::new(-Vector3::Y).sun().is_none());
+    }
+}
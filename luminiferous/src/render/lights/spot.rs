@@ -1,6 +1,10 @@
-use crate::{prelude::*, primitive::Interaction, spectra::Spectrum};
+use crate::{
+    prelude::*,
+    primitive::{Interaction, SurfaceInteraction},
+    spectra::{Spectrum, SpectrumT},
+};
 
-use super::{LightSample, LightT, Visibility};
+use super::{IesProfile, LightSample, LightT, Visibility};
 
 pub struct Spotlight {
     p: Point3,
@@ -8,6 +12,8 @@ pub struct Spotlight {
     cos_width: f32,
     cos_falloff_start: f32,
     world_to_light: Option<Transform3>,
+    /// Measured distribution with its nadir along the axis of the cone, applied on top of the falloff.
+    profile: Option<IesProfile>,
 }
 
 impl Spotlight {
@@ -25,20 +31,17 @@ impl Spotlight {
             cos_width: width.to_radians().cos(),
             cos_falloff_start: falloff_start.to_radians().cos(),
             world_to_light,
+            profile: None,
         }
     }
-}
 
-impl LightT for Spotlight {
-    fn is_environment(&self) -> bool {
-        false
+    pub fn with_profile(mut self, profile: IesProfile) -> Self {
+        self.profile = Some(profile);
+        self
     }
 
-    fn l_e(&self, wi: Vector3) -> Spectrum {
-        let w_l = self
-            .world_to_light
-            .map(|t| t.transform_vector(wi))
-            .unwrap_or(wi);
+    /// The falloff and the profile towards `w_l` in the space of the light.
+    fn emission(&self, w_l: Vector3) -> f32 {
         let cos_theta = Frame3::cos_theta(w_l);
         let falloff = {
             if cos_theta < self.cos_width {
@@ -51,7 +54,32 @@ impl LightT for Spotlight {
             }
         };
 
-        self.radiance * falloff
+        let profile = self
+            .profile
+            .as_ref()
+            .map(|profile| profile.normalized(w_l.normalize()))
+            .unwrap_or(1.0);
+
+        falloff * profile
+    }
+}
+
+impl LightT for Spotlight {
+    fn is_environment(&self) -> bool {
+        false
+    }
+
+    fn l_e(&self, wi: Vector3) -> Spectrum {
+        let w_l = self
+            .world_to_light
+            .map(|t| t.transform_vector(wi))
+            .unwrap_or(wi);
+
+        self.radiance * self.emission(w_l)
+    }
+
+    fn l(&self, _si: &SurfaceInteraction, _w: Vector3) -> Spectrum {
+        Spectrum::zero()
     }
 
     fn sample_li(&self, interaction: &Interaction, _u: Point2) -> LightSample {
@@ -60,10 +88,68 @@ impl LightT for Spotlight {
         LightSample {
             wo,
             li: self.l_e(-wo) / self.p.distance_squared(interaction.p),
+            pdf: 0.0,
             visibility: Visibility {
                 ray: interaction.spawn_ray(wo),
                 end: self.p,
             },
         }
     }
+
+    fn pdf_li(&self, _interaction: &Interaction, _wi: Vector3) -> f32 {
+        0.0
+    }
+
+    fn power(&self, _scene_radius: f32) -> Spectrum {
+        if self.profile.is_none() {
+            return self.radiance
+                * core::f32::consts::TAU
+                * (1.0 - 0.5 * (self.cos_falloff_start + self.cos_width));
+        }
+
+        // integrate the falloff and the profile over the cone
+        const N: usize = 64;
+        let one_minus_cos_width = 1.0 - self.cos_width;
+        let mut sum = 0.0;
+        for y in 0..N {
+            for x in 0..N {
+                let u = Point2::new((x as f32 + 0.5) / N as f32, (y as f32 + 0.5) / N as f32);
+                sum += self.emission(warp::square_to_uniform_cone(u, one_minus_cos_width));
+            }
+        }
+
+        self.radiance * (sum / (N * N) as f32 * core::f32::consts::TAU * one_minus_cos_width)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Even up to `cutoff` degrees from the nadir and dark beyond.
+    fn profile(cutoff: f32) -> IesProfile {
+        IesProfile::parse(&format!(
+            "IESNA:LM-63-2002\nTILT=NONE\n1 1000 1.0 4 1 1 2 0.1 0.1 0.0\n1.0 1.0 20\n0 {} {} 90\n0\n100 100 0 0\n",
+            cutoff,
+            cutoff + 0.01
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn power_includes_the_profile() {
+        let spot = || Spotlight::new(Point3::ZERO, Spectrum::splat(1.0), 40.0, 40.0, None);
+        let cone = |degrees: f32| core::f32::consts::TAU * (1.0 - degrees.to_radians().cos());
+
+        let power = spot().power(0.0).y();
+        assert!((power - cone(40.0)).abs() < 1e-3 * cone(40.0));
+
+        // a profile wider than the cone changes nothing
+        let power = spot().with_profile(profile(60.0)).power(0.0).y();
+        assert!((power - cone(40.0)).abs() < 1e-2 * cone(40.0), "{power}");
+
+        // the hard edge of the profile falls between the rows of the integration
+        let power = spot().with_profile(profile(20.0)).power(0.0).y();
+        assert!((power - cone(20.0)).abs() < 5e-2 * cone(20.0), "{power}");
+    }
 }
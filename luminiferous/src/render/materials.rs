@@ -6,30 +6,212 @@ pub use direct::*;
 mod mix;
 pub use mix::*;
 
+mod blend;
+pub use blend::*;
+
+mod two_sided;
+pub use two_sided::*;
+
+mod subsurface;
+pub use subsurface::*;
+
+mod emissive;
+pub use emissive::*;
+
 use crate::prelude::*;
 use crate::{
-    bsdfs::{BsdfFlags, BsdfSample},
+    bsdfs::{Bsdf, BsdfFlags, BsdfSample, BsdfT},
     primitive::SurfaceInteraction,
-    spectra::Spectrum,
+    spectra::{Spectrum, SpectrumT},
+    textures::{SpectralTexture, Texture, TextureT},
 };
 
 pub(crate) fn make_frame(interaction: &SurfaceInteraction) -> Frame3 {
+    make_frame_from(interaction.n, interaction.dp_du)
+}
+
+fn make_frame_from(n: Normal3, dp_du: Vector3) -> Frame3 {
     // currently using Gram-Schmidt orthogonalization but maybe deal with mesh tangents?
 
-    if interaction.dp_du.x == 0.0 && interaction.dp_du.y == 0.0 && interaction.dp_du.z == 0.0 {
+    if dp_du.x == 0.0 && dp_du.y == 0.0 && dp_du.z == 0.0 {
         // handle singularity
-        return Frame3::new(interaction.n);
+        return Frame3::new(n);
     }
 
-    let s =
-        -(interaction.n * -interaction.n.dot(interaction.dp_du) + interaction.dp_du).normalize();
+    let s = -(n * -n.dot(dp_du) + dp_du).normalize();
     Frame3 {
-        n: interaction.n,
+        n,
         s,
-        t: interaction.n.cross(s),
+        t: n.cross(s),
+    }
+}
+
+/// Perturbs the shading normal of a material.
+#[derive(Debug, Clone, Default)]
+pub enum NormalModifier {
+    #[default]
+    None,
+    /// Tangent space normal map, rgb in [0, 1] is remapped to [-1, 1].
+    NormalMap(SpectralTexture),
+    /// Height field, the surface is offset along the normal by the texture value in scene units.
+    Bump(Texture<f32>),
+}
+
+impl NormalModifier {
+    /// Builds the shading frame with the perturbed normal, the tangent stays aligned with `dp_du`.
+    pub(crate) fn make_frame(&self, si: &SurfaceInteraction) -> Frame3 {
+        match self {
+            NormalModifier::None => make_frame(si),
+            NormalModifier::NormalMap(texture) => {
+                let c = texture.eval(si).to_rgb();
+                let v = Vector3::new(2.0 * c[0] - 1.0, 2.0 * c[1] - 1.0, 2.0 * c[2] - 1.0);
+                if v.length_squared() == 0.0 {
+                    return make_frame(si);
+                }
+
+                // the tangent space of normal maps has x along dp_du and y along n x dp_du
+                let n = si.n;
+                let tangent = (si.dp_du - n * n.dot(si.dp_du))
+                    .try_normalize()
+                    .unwrap_or_else(|| Frame3::coordinate_system(n).0);
+                let bitangent = n.cross(tangent);
+                let ns = v.x * tangent + v.y * bitangent + v.z * n;
+
+                make_frame_from(ns.normalize(), si.dp_du)
+            }
+            NormalModifier::Bump(texture) => {
+                // finite differences of roughly a texel, no ray differentials to go off of
+                let extent = texture.extent();
+                let du = (extent.x.max(1) as f32).recip().min(1e-2);
+                let dv = (extent.y.max(1) as f32).recip().min(1e-2);
+
+                let displace = texture.eval_uv(si.uv);
+                let u_displace = texture.eval_uv(si.uv + Vector2::new(du, 0.0));
+                let v_displace = texture.eval_uv(si.uv + Vector2::new(0.0, dv));
+
+                let dp_du = si.dp_du + si.n * ((u_displace - displace) / du);
+                let dp_dv = si.dp_dv + si.n * ((v_displace - displace) / dv);
+
+                let ns = dp_du.cross(dp_dv);
+                if ns.length_squared() == 0.0 || ns.is_nan() {
+                    return make_frame(si);
+                }
+
+                make_frame_from(face_forward(ns.normalize(), si.n), dp_du)
+            }
+        }
+    }
+
+    /// Shadowing term from "Taming the Shadow Terminator" (Chiang et al. 2019), hides the faceted look of
+    /// bumped surfaces near the terminator where the shading normal points towards light the geometry can't see.
+    pub(crate) fn shadowing(
+        &self,
+        si: &SurfaceInteraction,
+        frame: &Frame3,
+        wo_world: Vector3,
+    ) -> f32 {
+        if matches!(self, NormalModifier::None) {
+            return 1.0;
+        }
+
+        let ns = frame.n;
+        let ng = face_forward(si.n, ns);
+        let cos_s = ns.dot(wo_world);
+        let cos_g = ng.dot(wo_world);
+        if cos_s <= 0.0 {
+            // transmission isn't shadowed
+            return 1.0;
+        }
+        if cos_g <= 0.0 {
+            // the light is below the geometry even though the shading normal faces it
+            return 0.0;
+        }
+
+        let g = (cos_g / (cos_s * ng.dot(ns))).min(1.0);
+        -g * g * g + g * g + g
     }
 }
 
+/// Samples a weighted sum of bsdfs by picking one of them proportionally to its weight, the returned
+/// spectrum is for the whole mixture. `weights` must sum to one.
+pub(crate) fn sample_mixture(
+    layers: &[(&Bsdf, f32)],
+    wi: Vector3,
+    si: &SurfaceInteraction,
+    u1: f32,
+    u2: Point2,
+) -> BsdfSample {
+    let mut u1 = u1;
+    let mut selected = layers.len() - 1;
+    for (i, (_, weight)) in layers.iter().enumerate() {
+        if u1 < *weight {
+            selected = i;
+            break;
+        }
+        u1 -= weight;
+    }
+    let (bsdf, weight) = layers[selected];
+    let u1 = (u1 / weight).clamp(0.0, 1.0 - f32::EPSILON);
+
+    let mut sample = bsdf.sample(wi, si, u1, u2);
+    if weight >= 1.0
+        || sample
+            .sampled
+            .intersects(BsdfFlags::Delta | BsdfFlags::Null)
+    {
+        // the selection probability cancels with the weight
+        return sample;
+    }
+
+    let selected_pdf = bsdf.pdf(si, wi, sample.wo);
+    if selected_pdf <= 0.0 {
+        return sample;
+    }
+
+    // one sample mis over the layers, the selected layer reuses its own estimate of f * cos
+    let cos_theta = Frame3::cos_theta(sample.wo).abs();
+    let mut pdf = weight * selected_pdf;
+    let mut f = sample.spectrum * (weight * selected_pdf);
+    for (i, (other, other_weight)) in layers.iter().enumerate() {
+        if i == selected || *other_weight <= 0.0 {
+            continue;
+        }
+
+        pdf += other_weight * other.pdf(si, wi, sample.wo);
+        f += other.eval(si, wi, sample.wo) * (other_weight * cos_theta);
+    }
+
+    sample.spectrum = f / pdf;
+    sample
+}
+
+pub(crate) fn eval_mixture(
+    layers: &[(&Bsdf, f32)],
+    si: &SurfaceInteraction,
+    wi: Vector3,
+    wo: Vector3,
+) -> Spectrum {
+    layers
+        .iter()
+        .filter(|(_, weight)| *weight > 0.0)
+        .fold(Spectrum::zero(), |f, (bsdf, weight)| {
+            f + bsdf.eval(si, wi, wo) * *weight
+        })
+}
+
+pub(crate) fn pdf_mixture(
+    layers: &[(&Bsdf, f32)],
+    si: &SurfaceInteraction,
+    wi: Vector3,
+    wo: Vector3,
+) -> f32 {
+    layers
+        .iter()
+        .filter(|(_, weight)| *weight > 0.0)
+        .map(|(bsdf, weight)| bsdf.pdf(si, wi, wo) * weight)
+        .sum()
+}
+
 #[enum_dispatch]
 pub trait MaterialT {
     fn sample(
@@ -42,6 +224,9 @@ pub trait MaterialT {
 
     fn eval(&self, si: &SurfaceInteraction, wi_world: Vector3, wo_world: Vector3) -> Spectrum;
 
+    /// Density of `sample` choosing `wo_world`, zero for directions only delta lobes can reach.
+    fn pdf(&self, si: &SurfaceInteraction, wi_world: Vector3, wo_world: Vector3) -> f32;
+
     fn bsdf_flags(&self) -> BsdfFlags;
 }
 
@@ -50,4 +235,21 @@ pub trait MaterialT {
 pub enum Material {
     Direct(DirectMaterial),
     Mix(MixMaterial),
+    Blend(BlendMaterial),
+    TwoSided(TwoSidedMaterial),
+    Subsurface(SubsurfaceMaterial),
+    Emissive(EmissiveMaterial),
+}
+
+impl Material {
+    /// Sets the normal modifier of materials that support one.
+    pub fn with_normal(self, normal: NormalModifier) -> Self {
+        match self {
+            Material::Direct(material) => Material::Direct(material.with_normal(normal)),
+            Material::Mix(material) => Material::Mix(material.with_normal(normal)),
+            Material::Blend(material) => Material::Blend(material.with_normal(normal)),
+            Material::TwoSided(material) => Material::TwoSided(material.with_normal(normal)),
+            material => material,
+        }
+    }
 }
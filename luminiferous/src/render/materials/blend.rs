@@ -0,0 +1,104 @@
+use crate::prelude::*;
+use crate::textures::{Texture, TextureT};
+use crate::{
+    bsdfs::{Bsdf, BsdfFlags, BsdfSample, BsdfT},
+    primitive::SurfaceInteraction,
+    spectra::{Spectrum, SpectrumT},
+};
+
+use super::{eval_mixture, pdf_mixture, sample_mixture, MaterialT, NormalModifier};
+
+/// Blends any number of bsdfs, each layer is weighted by its own texture and the weights are normalized
+/// at every point so they don't have to sum to one.
+#[derive(Debug, Clone)]
+pub struct BlendMaterial {
+    layers: Vec<(Bsdf, Texture<f32>)>,
+    normal: NormalModifier,
+}
+
+impl BlendMaterial {
+    pub fn new(layers: Vec<(Bsdf, Texture<f32>)>) -> Self {
+        STATS.materials_created.inc();
+
+        Self {
+            layers,
+            normal: NormalModifier::None,
+        }
+    }
+
+    pub fn with_normal(mut self, normal: NormalModifier) -> Self {
+        self.normal = normal;
+        self
+    }
+
+    /// Returns each layer with its normalized weight, empty if no layer has a positive weight.
+    fn weighted_layers(&self, si: &SurfaceInteraction) -> Vec<(&Bsdf, f32)> {
+        let mut layers = self
+            .layers
+            .iter()
+            .map(|(bsdf, weight)| (bsdf, weight.eval(si).max(0.0)))
+            .collect::<Vec<_>>();
+
+        let sum = layers.iter().map(|(_, weight)| weight).sum::<f32>();
+        if sum <= 0.0 {
+            return vec![];
+        }
+        for (_, weight) in layers.iter_mut() {
+            *weight /= sum;
+        }
+
+        layers
+    }
+}
+
+impl MaterialT for BlendMaterial {
+    fn sample(
+        &self,
+        wi_world: Vector3,
+        si: &SurfaceInteraction,
+        u1: f32,
+        u2: Point2,
+    ) -> BsdfSample {
+        let frame = self.normal.make_frame(si);
+        let wi = frame.to_local(wi_world);
+
+        let layers = self.weighted_layers(si);
+        if layers.is_empty() {
+            return BsdfSample {
+                wo: wi_world,
+                sampled: BsdfFlags::None,
+                spectrum: Spectrum::zero(),
+            };
+        }
+
+        let mut sample = sample_mixture(&layers, wi, si, u1, u2);
+
+        sample.wo = frame.to_world(sample.wo);
+        sample.spectrum *= self.normal.shadowing(si, &frame, sample.wo);
+        sample
+    }
+
+    fn eval(&self, si: &SurfaceInteraction, wi_world: Vector3, wo_world: Vector3) -> Spectrum {
+        let frame = self.normal.make_frame(si);
+        let wi = frame.to_local(wi_world);
+        let wo = frame.to_local(wo_world);
+
+        let f = eval_mixture(&self.weighted_layers(si), si, wi, wo);
+
+        f * self.normal.shadowing(si, &frame, wo_world)
+    }
+
+    fn pdf(&self, si: &SurfaceInteraction, wi_world: Vector3, wo_world: Vector3) -> f32 {
+        let frame = self.normal.make_frame(si);
+        let wi = frame.to_local(wi_world);
+        let wo = frame.to_local(wo_world);
+
+        pdf_mixture(&self.weighted_layers(si), si, wi, wo)
+    }
+
+    fn bsdf_flags(&self) -> BsdfFlags {
+        self.layers
+            .iter()
+            .fold(BsdfFlags::empty(), |flags, (bsdf, _)| flags | bsdf.flags())
+    }
+}
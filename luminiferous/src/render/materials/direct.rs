@@ -6,18 +6,31 @@ use crate::{
     spectra::Spectrum,
 };
 
-use super::{make_frame, MaterialT};
+use super::{MaterialT, NormalModifier};
 
 #[derive(Debug, Clone)]
 pub struct DirectMaterial {
     bsdf: Bsdf,
+    normal: NormalModifier,
 }
 
 impl DirectMaterial {
     pub fn new(bsdf: Bsdf) -> Self {
         STATS.materials_created.inc();
 
-        Self { bsdf }
+        Self {
+            bsdf,
+            normal: NormalModifier::None,
+        }
+    }
+
+    pub fn with_normal(mut self, normal: NormalModifier) -> Self {
+        self.normal = normal;
+        self
+    }
+
+    pub fn bsdf(&self) -> &Bsdf {
+        &self.bsdf
     }
 }
 
@@ -29,20 +42,28 @@ impl MaterialT for DirectMaterial {
         u1: f32,
         u2: Point2,
     ) -> BsdfSample {
-        let frame = make_frame(si);
+        let frame = self.normal.make_frame(si);
         let wi = frame.to_local(wi_world).normalize();
 
         let mut sample = self.bsdf.sample(wi, si, u1, u2);
         sample.wo = frame.to_world(sample.wo).normalize();
+        sample.spectrum *= self.normal.shadowing(si, &frame, sample.wo);
         sample
     }
 
     fn eval(&self, si: &SurfaceInteraction, wi_world: Vector3, wo_world: Vector3) -> Spectrum {
-        let frame = make_frame(si);
+        let frame = self.normal.make_frame(si);
+
+        let wi = frame.to_local(wi_world).normalize();
+        let wo = frame.to_local(wo_world).normalize();
+        self.bsdf.eval(si, wi, wo) * self.normal.shadowing(si, &frame, wo_world)
+    }
 
+    fn pdf(&self, si: &SurfaceInteraction, wi_world: Vector3, wo_world: Vector3) -> f32 {
+        let frame = self.normal.make_frame(si);
         let wi = frame.to_local(wi_world).normalize();
         let wo = frame.to_local(wo_world).normalize();
-        self.bsdf.eval(si, wi, wo)
+        self.bsdf.pdf(si, wi, wo)
     }
 
     fn bsdf_flags(&self) -> BsdfFlags {
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use crate::prelude::*;
+use crate::{
+    bsdfs::{BsdfFlags, BsdfSample},
+    lights::IesProfile,
+    primitive::SurfaceInteraction,
+    spectra::{Spectrum, SpectrumT},
+    textures::SpectralTexture,
+};
+
+use super::MaterialT;
+
+/// A surface that only emits light. The scene builder turns every shape with this material into its own
+/// area light, so meshes get a light per triangle that is picked according to its power.
+#[derive(Debug, Clone)]
+pub struct EmissiveMaterial {
+    pub(crate) radiance: SpectralTexture,
+    pub(crate) two_sided: bool,
+    /// Measured distribution the lights of the shapes are scaled by, see [`AreaLight`].
+    ///
+    /// [`AreaLight`]: crate::lights::AreaLight
+    pub(crate) profile: Option<Arc<IesProfile>>,
+}
+
+impl EmissiveMaterial {
+    pub fn new(radiance: SpectralTexture) -> Self {
+        STATS.materials_created.inc();
+
+        Self {
+            radiance,
+            two_sided: false,
+            profile: None,
+        }
+    }
+
+    pub fn with_two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+
+    pub fn with_profile(mut self, profile: IesProfile) -> Self {
+        self.profile = Some(Arc::new(profile));
+        self
+    }
+}
+
+impl MaterialT for EmissiveMaterial {
+    fn sample(
+        &self,
+        wi_world: Vector3,
+        _si: &SurfaceInteraction,
+        _u1: f32,
+        _u2: Point2,
+    ) -> BsdfSample {
+        BsdfSample {
+            wo: -wi_world,
+            sampled: BsdfFlags::None,
+            spectrum: Spectrum::zero(),
+        }
+    }
+
+    fn eval(&self, _si: &SurfaceInteraction, _wi_world: Vector3, _wo_world: Vector3) -> Spectrum {
+        Spectrum::zero()
+    }
+
+    fn pdf(&self, _si: &SurfaceInteraction, _wi_world: Vector3, _wo_world: Vector3) -> f32 {
+        0.0
+    }
+
+    fn bsdf_flags(&self) -> BsdfFlags {
+        BsdfFlags::None
+    }
+}
@@ -6,20 +6,32 @@ use crate::{
     spectra::Spectrum,
 };
 
-use super::{make_frame, MaterialT};
+use super::{eval_mixture, pdf_mixture, sample_mixture, MaterialT, NormalModifier};
 
+/// Blends two bsdfs by a mask, zero is entirely `a` and one is entirely `b`.
 #[derive(Debug, Clone)]
 pub struct MixMaterial {
     a: Bsdf,
     b: Bsdf,
     mask: Texture<f32>,
+    normal: NormalModifier,
 }
 
 impl MixMaterial {
     pub fn new(a: Bsdf, b: Bsdf, mask: Texture<f32>) -> Self {
         STATS.materials_created.inc();
 
-        Self { a, b, mask }
+        Self {
+            a,
+            b,
+            mask,
+            normal: NormalModifier::None,
+        }
+    }
+
+    pub fn with_normal(mut self, normal: NormalModifier) -> Self {
+        self.normal = normal;
+        self
     }
 }
 
@@ -31,48 +43,39 @@ impl MaterialT for MixMaterial {
         u1: f32,
         u2: Point2,
     ) -> BsdfSample {
-        let frame = make_frame(si);
+        let frame = self.normal.make_frame(si);
 
         let wi = frame.to_local(wi_world);
 
         let t = self.mask.eval(si).clamp(0.0, 1.0);
-
-        let mut sample = if t == 0.0 {
-            self.a.sample(wi, si, u1, u2)
-        } else if t == 1.0 {
-            self.b.sample(wi, si, u1, u2)
-        } else {
-            let a = self.a.sample(wi, si, u1, u2);
-            let b = self.b.sample(wi, si, u1, u2);
-            BsdfSample {
-                wo: (a.wo * (1.0 - t) + b.wo * t).normalize(),
-                sampled: a.sampled | b.sampled,
-                spectrum: a.spectrum * (1.0 - t) + b.spectrum * t,
-            }
-        };
+        let mut sample = sample_mixture(&[(&self.a, 1.0 - t), (&self.b, t)], wi, si, u1, u2);
 
         sample.wo = frame.to_world(sample.wo);
+        sample.spectrum *= self.normal.shadowing(si, &frame, sample.wo);
         sample
     }
 
     fn eval(&self, si: &SurfaceInteraction, wi_world: Vector3, wo_world: Vector3) -> Spectrum {
-        let frame = make_frame(si);
+        let frame = self.normal.make_frame(si);
         let wi = frame.to_local(wi_world);
         let wo = frame.to_local(wo_world);
 
         let t = self.mask.eval(si).clamp(0.0, 1.0);
-        if t == 0.0 {
-            self.a.eval(si, wi, wo)
-        } else if t == 1.0 {
-            self.b.eval(si, wi, wo)
-        } else {
-            let a = self.a.eval(si, wi, wo);
-            let b = self.b.eval(si, wi, wo);
-            a * (1.0 - t) + b * t
-        }
+        let f = eval_mixture(&[(&self.a, 1.0 - t), (&self.b, t)], si, wi, wo);
+
+        f * self.normal.shadowing(si, &frame, wo_world)
+    }
+
+    fn pdf(&self, si: &SurfaceInteraction, wi_world: Vector3, wo_world: Vector3) -> f32 {
+        let frame = self.normal.make_frame(si);
+        let wi = frame.to_local(wi_world);
+        let wo = frame.to_local(wo_world);
+
+        let t = self.mask.eval(si).clamp(0.0, 1.0);
+        pdf_mixture(&[(&self.a, 1.0 - t), (&self.b, t)], si, wi, wo)
     }
 
     fn bsdf_flags(&self) -> BsdfFlags {
-        self.a.flags() & self.b.flags()
+        self.a.flags() | self.b.flags()
     }
 }
@@ -0,0 +1,189 @@
+// Parameterization from "Practical and Controllable Subsurface Scattering for Production Path Tracing"
+// (Chiang et al. 2016) https://doi.org/10.1145/2897839.2927433
+
+use crate::prelude::*;
+use crate::{
+    bsdfs::{fresnel_dielectric, reflect, reflect_across, BsdfFlags, BsdfSample},
+    primitive::SurfaceInteraction,
+    samplers::{Sampler, SamplerT},
+    scene::Scene,
+    spectra::{Spectrum, SpectrumT},
+    textures::{SpectralTexture, TextureT},
+};
+
+use super::{make_frame, MaterialT};
+
+/// Maximum number of scattering events inside of the surface before the path is terminated.
+const MAX_WALK_LENGTH: u32 = 256;
+
+/// A dielectric boundary over a homogeneous scattering interior, the interior is integrated with a volumetric
+/// random walk. The walk is started by the integrator when a sample has the `Subsurface` flag.
+#[derive(Debug, Clone)]
+pub struct SubsurfaceMaterial {
+    albedo: SpectralTexture,
+    mfp: Spectrum,
+    eta: f32,
+}
+
+impl SubsurfaceMaterial {
+    /// `albedo` is the observed multiple scattering color of the surface and `mfp` is the mean free path, the
+    /// average distance light travels inside of the surface, per channel in scene units.
+    pub fn new(albedo: SpectralTexture, mfp: Spectrum, eta: f32) -> Self {
+        STATS.materials_created.inc();
+
+        Self { albedo, mfp, eta }
+    }
+
+    /// Inverts the multiple scattering albedo of a semi infinite slab to find the single scattering albedo and
+    /// extinction coefficient of the medium, returns (single scattering albedo, sigma_t).
+    fn medium_coefficients(&self, si: &SurfaceInteraction) -> (Spectrum, Spectrum) {
+        let albedo = self.albedo.eval(si).to_rgb().map(|a| a.clamp(0.0, 0.999));
+        let mfp = self.mfp.to_rgb().map(|d| d.max(1e-6));
+
+        let single_scattering_albedo = albedo.map(|a| {
+            let x = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+            1.0 - x * x
+        });
+        let sigma_t = [0, 1, 2].map(|i| {
+            let a = albedo[i];
+            let s = 1.9 - a + 3.5 * (a - 0.8) * (a - 0.8);
+            (mfp[i] * s).recip()
+        });
+
+        (
+            Spectrum::from_rgb(
+                single_scattering_albedo[0],
+                single_scattering_albedo[1],
+                single_scattering_albedo[2],
+            ),
+            Spectrum::from_rgb(sigma_t[0], sigma_t[1], sigma_t[2]),
+        )
+    }
+
+    /// Walks from `si` into the surface along `wo` until the path leaves it again. Returns the interaction
+    /// where the path exited, the direction it exited in, and the throughput of the walk. Returns `None` if
+    /// the path was absorbed or escaped the surface through a hole.
+    ///
+    /// Reaching the boundary, the walk is reflected back inside with the fresnel reflectance and otherwise
+    /// leaves through [`SubsurfaceMaterial::exit_lobe`].
+    pub fn random_walk<'a>(
+        &self,
+        scene: &'a Scene,
+        si: &SurfaceInteraction,
+        wo: Vector3,
+        sampler: &mut Sampler,
+    ) -> Option<(SurfaceInteraction<'a>, Vector3, Spectrum)> {
+        let (single_scattering_albedo, sigma_t) = self.medium_coefficients(si);
+        let sigma_s = single_scattering_albedo * sigma_t;
+
+        let mut throughput = Spectrum::splat(1.0);
+        let mut ray = si.as_interaction().spawn_ray(wo);
+
+        for _ in 0..MAX_WALK_LENGTH {
+            // pick a channel to sample the distance with, and weight by the average pdf of all of them
+            let channel = ((sampler.next_1d() * 3.0) as usize).min(2);
+            let t = -(1.0 - sampler.next_1d()).ln() / sigma_t.to_rgb()[channel];
+
+            let (interaction, _) = scene.intersect(ray);
+            let exit = interaction?;
+
+            if t < exit.t {
+                let tr = (-sigma_t * t).exp();
+                let pdf = (sigma_t * tr).to_rgb().iter().sum::<f32>() / 3.0;
+                throughput *= sigma_s * tr / pdf;
+
+                if throughput.is_black() {
+                    return None;
+                }
+
+                // isotropic scattering, anisotropy is folded into the mean free path
+                let p = ray.at(t);
+                ray = Ray::new(p, warp::square_to_uniform_sphere(sampler.next_2d()))
+                    .with_time(ray.time);
+            } else {
+                let tr = (-sigma_t * exit.t).exp();
+                let pdf = tr.to_rgb().iter().sum::<f32>() / 3.0;
+                throughput *= tr / pdf;
+
+                // the boundary reflects part of the light back inside, by the fresnel term seen from inside
+                let d = ray.d.normalize();
+                let n = face_forward(exit.n, d);
+                if sampler.next_1d() < fresnel_dielectric(-d.dot(n), self.eta) {
+                    ray = exit.as_interaction().spawn_ray(reflect_across(-d, n));
+                    continue;
+                }
+
+                // the rest is transmitted diffusely, see `exit_lobe`
+                let wo = Frame3::new(n)
+                    .to_world(warp::square_to_cosine_hemisphere(sampler.next_2d()))
+                    .normalize();
+
+                return Some((exit, wo, throughput));
+            }
+        }
+
+        None
+    }
+
+    /// The lobe a walk leaves through at a boundary with the normal `n`, on the side of `wo` the walk left
+    /// in. It is a diffuse transmission that carries all of the light, returns its value and its density
+    /// for the direction `w`.
+    pub fn exit_lobe(n: Normal3, wo: Vector3, w: Vector3) -> (Spectrum, f32) {
+        let cos_theta = w.dot(face_forward(n, wo));
+        if cos_theta <= 0.0 {
+            return (Spectrum::zero(), 0.0);
+        }
+
+        (
+            Spectrum::splat(core::f32::consts::FRAC_1_PI),
+            cos_theta * core::f32::consts::FRAC_1_PI,
+        )
+    }
+}
+
+impl MaterialT for SubsurfaceMaterial {
+    fn sample(
+        &self,
+        wi_world: Vector3,
+        si: &SurfaceInteraction,
+        u1: f32,
+        u2: Point2,
+    ) -> BsdfSample {
+        let frame = make_frame(si);
+        let wi = frame.to_local(wi_world).normalize();
+
+        // always entering, the walk takes care of leaving the surface
+        let f = fresnel_dielectric(Frame3::cos_theta(wi).abs(), self.eta);
+        if u1 < f {
+            BsdfSample {
+                wo: frame.to_world(reflect(wi)).normalize(),
+                sampled: BsdfFlags::DeltaReflection,
+                spectrum: Spectrum::splat(1.0),
+            }
+        } else {
+            // enter diffusely, on the opposite side of wi
+            let mut wo = warp::square_to_cosine_hemisphere(u2);
+            if Frame3::cos_theta(wi) > 0.0 {
+                wo.z = -wo.z;
+            }
+
+            BsdfSample {
+                wo: frame.to_world(wo).normalize(),
+                sampled: BsdfFlags::Subsurface,
+                spectrum: Spectrum::splat(1.0),
+            }
+        }
+    }
+
+    fn eval(&self, _si: &SurfaceInteraction, _wi_world: Vector3, _wo_world: Vector3) -> Spectrum {
+        Spectrum::zero()
+    }
+
+    fn pdf(&self, _si: &SurfaceInteraction, _wi_world: Vector3, _wo_world: Vector3) -> f32 {
+        0.0
+    }
+
+    fn bsdf_flags(&self) -> BsdfFlags {
+        BsdfFlags::DeltaReflection | BsdfFlags::Subsurface
+    }
+}
@@ -0,0 +1,114 @@
+use crate::prelude::*;
+use crate::{
+    bsdfs::{Bsdf, BsdfFlags, BsdfSample, BsdfT},
+    primitive::SurfaceInteraction,
+    spectra::Spectrum,
+};
+
+use super::{MaterialT, NormalModifier};
+
+/// Makes single sided geometry look the same from both sides by mirroring directions below the surface
+/// into the upper hemisphere, optionally with a different bsdf for the back.
+#[derive(Debug, Clone)]
+pub struct TwoSidedMaterial {
+    front: Bsdf,
+    back: Option<Bsdf>,
+    normal: NormalModifier,
+}
+
+impl TwoSidedMaterial {
+    pub fn new(front: Bsdf) -> Self {
+        STATS.materials_created.inc();
+
+        Self {
+            front,
+            back: None,
+            normal: NormalModifier::None,
+        }
+    }
+
+    pub fn with_back(mut self, back: Bsdf) -> Self {
+        self.back = Some(back);
+        self
+    }
+
+    pub fn with_normal(mut self, normal: NormalModifier) -> Self {
+        self.normal = normal;
+        self
+    }
+
+    #[inline]
+    fn side(&self, wi: Vector3) -> (&Bsdf, bool) {
+        if Frame3::cos_theta(wi) >= 0.0 {
+            (&self.front, false)
+        } else {
+            (self.back.as_ref().unwrap_or(&self.front), true)
+        }
+    }
+}
+
+#[inline]
+fn flip(v: Vector3) -> Vector3 {
+    Vector3::new(v.x, v.y, -v.z)
+}
+
+impl MaterialT for TwoSidedMaterial {
+    fn sample(
+        &self,
+        wi_world: Vector3,
+        si: &SurfaceInteraction,
+        u1: f32,
+        u2: Point2,
+    ) -> BsdfSample {
+        let frame = self.normal.make_frame(si);
+        let wi = frame.to_local(wi_world).normalize();
+
+        let (bsdf, flipped) = self.side(wi);
+        let mut sample = if flipped {
+            let mut sample = bsdf.sample(flip(wi), si, u1, u2);
+            sample.wo = flip(sample.wo);
+            sample
+        } else {
+            bsdf.sample(wi, si, u1, u2)
+        };
+
+        sample.wo = frame.to_world(sample.wo).normalize();
+        sample.spectrum *= self.normal.shadowing(si, &frame, sample.wo);
+        sample
+    }
+
+    fn eval(&self, si: &SurfaceInteraction, wi_world: Vector3, wo_world: Vector3) -> Spectrum {
+        let frame = self.normal.make_frame(si);
+        let wi = frame.to_local(wi_world).normalize();
+        let wo = frame.to_local(wo_world).normalize();
+
+        let (bsdf, flipped) = self.side(wi);
+        let f = if flipped {
+            bsdf.eval(si, flip(wi), flip(wo))
+        } else {
+            bsdf.eval(si, wi, wo)
+        };
+
+        f * self.normal.shadowing(si, &frame, wo_world)
+    }
+
+    fn pdf(&self, si: &SurfaceInteraction, wi_world: Vector3, wo_world: Vector3) -> f32 {
+        let frame = self.normal.make_frame(si);
+        let wi = frame.to_local(wi_world).normalize();
+        let wo = frame.to_local(wo_world).normalize();
+
+        let (bsdf, flipped) = self.side(wi);
+        if flipped {
+            bsdf.pdf(si, flip(wi), flip(wo))
+        } else {
+            bsdf.pdf(si, wi, wo)
+        }
+    }
+
+    fn bsdf_flags(&self) -> BsdfFlags {
+        match &self.back {
+            Some(back) => self.front.flags() | back.flags(),
+            None => self.front.flags(),
+        }
+    }
+}
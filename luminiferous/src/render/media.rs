@@ -10,6 +10,7 @@ pub struct MediumInteraction<'a> {
     pub wi: Vector3,
     pub medium: Option<&'a Medium>,
     pub phase_function: Option<PhaseFunction>,
+    pub time: f32,
 }
 
 impl MediumInteraction<'_> {
@@ -22,6 +23,7 @@ impl MediumInteraction<'_> {
             p: self.p,
             n: self.wi,
             wi: self.wi,
+            time: self.time,
         }
     }
 }
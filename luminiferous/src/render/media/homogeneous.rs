@@ -48,6 +48,7 @@ impl MediumT for HomogeneousMedium {
                     wi: -ray.d,
                     medium: None,
                     phase_function: Some(self.phase_function.clone()),
+                    time: ray.time,
                 },
                 tr,
             ))
@@ -27,3 +27,47 @@ pub enum PhaseFunction {
     Isotropic(IsotropicPhaseFunction),
     HenyeyGreenstein(HenyeyGreensteinPhaseFunction),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::ChiSquareTest;
+
+    #[test]
+    fn chi2_sample_matches_eval() {
+        let phase_functions = [
+            PhaseFunction::Isotropic(IsotropicPhaseFunction::new()),
+            PhaseFunction::HenyeyGreenstein(HenyeyGreensteinPhaseFunction::new(0.3)),
+            PhaseFunction::HenyeyGreenstein(HenyeyGreensteinPhaseFunction::new(-0.7)),
+            PhaseFunction::HenyeyGreenstein(HenyeyGreensteinPhaseFunction::new(0.9)),
+        ];
+        let directions = [
+            Vector3::Z,
+            Vector3::new(0.3, -0.5, 0.2).normalize(),
+            Vector3::new(-0.1, 0.2, -0.9).normalize(),
+        ];
+        let test_count = phase_functions.len() * directions.len();
+
+        for pf in &phase_functions {
+            for wi in directions {
+                let mi = MediumInteraction {
+                    p: Point3::ZERO,
+                    wi,
+                    medium: None,
+                    phase_function: None,
+                    time: 0.0,
+                };
+
+                // `wi` points back along the ray, the integrator evaluates with the direction of travel
+                let result = ChiSquareTest::default().run(
+                    |_, u| Some(pf.sample(&mi, u).wo),
+                    |wo| pf.eval(&mi, -mi.wi, wo),
+                    test_count,
+                );
+                if let Err(e) = result {
+                    panic!("{pf:?} with wi = {wi}: {e}");
+                }
+            }
+        }
+    }
+}
@@ -22,6 +22,6 @@ impl PhaseFunctionT for IsotropicPhaseFunction {
     }
 
     fn eval(&self, _mi: &MediumInteraction, _wi: Vector3, _wo: Vector3) -> f32 {
-        core::f32::consts::FRAC_PI_4
+        (4.0 * core::f32::consts::PI).recip()
     }
 }
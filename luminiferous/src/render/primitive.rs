@@ -1,9 +1,12 @@
+use std::sync::Arc;
+
 use crate::prelude::*;
 
 use crate::shapes::ShapeSample;
 use crate::{
     media::{Medium, MediumInterface},
     shapes::{Shape, ShapeIntersection, ShapeT},
+    textures::{Texture, TextureT},
 };
 
 #[derive(Debug, Clone)]
@@ -12,12 +15,61 @@ pub struct Primitive {
     pub material_index: usize,
     pub area_light_index: Option<usize>,
     pub world_to_object: Option<Transform3>,
+    /// Moves the primitive over time, on top of `world_to_object`. Only the intersections follow the
+    /// motion, primitives of area lights are sampled where they are without it.
+    pub motion: Option<AnimatedTransform>,
     pub medium_interface: MediumInterface,
+    /// The lights that illuminate the primitive.
+    pub light_link: LightLink,
+    /// The lights the primitive casts shadows for.
+    pub shadow_link: LightLink,
+    /// Cuts out the parts of the shape where it is zero, rays pass through partially transparent parts
+    /// at random. Shared by all the primitives of a shape.
+    pub alpha: Option<Arc<Texture<f32>>>,
+}
+
+/// A set of lights, by their index in the scene, that a primitive is linked to.
+#[derive(Debug, Clone, Default)]
+pub enum LightLink {
+    #[default]
+    All,
+    /// Only the lights in the sorted list.
+    Only(Arc<[usize]>),
+    /// All lights except the ones in the sorted list.
+    Except(Arc<[usize]>),
+}
+
+impl LightLink {
+    pub fn only(lights: impl IntoIterator<Item = usize>) -> Self {
+        Self::Only(Self::sorted(lights))
+    }
+
+    pub fn except(lights: impl IntoIterator<Item = usize>) -> Self {
+        Self::Except(Self::sorted(lights))
+    }
+
+    fn sorted(lights: impl IntoIterator<Item = usize>) -> Arc<[usize]> {
+        let mut lights = lights.into_iter().collect::<Vec<_>>();
+        lights.sort_unstable();
+        lights.dedup();
+        lights.into()
+    }
+
+    #[inline]
+    pub fn contains(&self, light_index: usize) -> bool {
+        match self {
+            Self::All => true,
+            Self::Only(lights) => lights.binary_search(&light_index).is_ok(),
+            Self::Except(lights) => lights.binary_search(&light_index).is_err(),
+        }
+    }
 }
 
 pub struct Intersection<'a> {
     pub primitive: &'a Primitive,
     pub shape_intersection: ShapeIntersection,
+    /// The transform of the instance the primitive was hit in, if it is in one.
+    pub instance: Option<&'a Transform3>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -25,12 +77,13 @@ pub struct Interaction {
     pub p: Point3,
     pub n: Normal3,
     pub wi: Vector3,
+    pub time: f32,
 }
 
 impl Interaction {
     #[inline]
     pub fn spawn_ray(&self, d: Vector3) -> Ray {
-        Ray::new(self.p + face_forward(self.n, d) * 1e-4, d)
+        Ray::new(self.p + face_forward(self.n, d) * 1e-4, d).with_time(self.time)
     }
 
     #[inline]
@@ -49,6 +102,7 @@ pub struct SurfaceInteraction<'a> {
     pub uv: Point2,
     pub dp_du: Vector3,
     pub dp_dv: Vector3,
+    pub time: f32,
     // pub shading_frame: Frame3,
 }
 
@@ -67,10 +121,29 @@ impl<'a> SurfaceInteraction<'a> {
             p: self.p,
             n: self.n,
             wi: self.wi,
+            time: self.time,
         }
     }
 }
 
+/// How far past a cut out hit the search for the next one starts.
+const ALPHA_SKIP_EPSILON: f32 = 1e-4;
+
+/// A number in `[0, 1)` that only depends on the ray, so partially transparent parts are hit by the same
+/// rays every time.
+fn hash_ray(ray: Ray) -> f32 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for x in [ray.o.x, ray.o.y, ray.o.z, ray.d.x, ray.d.y, ray.d.z] {
+        hash = (hash ^ x.to_bits() as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    // mix the high bits down, the multiplications only carry upwards
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+
+    (hash >> 40) as f32 / (1 << 24) as f32
+}
+
 impl<'a> Primitive {
     pub fn new(
         mut shape: Shape,
@@ -90,23 +163,85 @@ impl<'a> Primitive {
             material_index,
             area_light_index,
             world_to_object,
+            motion: None,
             medium_interface,
+            light_link: LightLink::All,
+            shadow_link: LightLink::All,
+            alpha: None,
+        }
+    }
+
+    pub fn with_motion(mut self, motion: Option<AnimatedTransform>) -> Self {
+        self.motion = motion.filter(|m| m.is_animated());
+        self
+    }
+
+    pub fn with_light_link(mut self, light_link: LightLink) -> Self {
+        self.light_link = light_link;
+        self
+    }
+
+    pub fn with_shadow_link(mut self, shadow_link: LightLink) -> Self {
+        self.shadow_link = shadow_link;
+        self
+    }
+
+    pub fn with_alpha(mut self, alpha: Option<Arc<Texture<f32>>>) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// The alpha at `uv` on the shape, 1 if it has no alpha texture.
+    pub fn alpha(&self, uv: Point2) -> f32 {
+        self.alpha
+            .as_ref()
+            .map_or(1.0, |alpha| alpha.eval_uv(uv).clamp(0.0, 1.0))
+    }
+
+    /// Whether the hit on the shape is kept by the alpha texture.
+    fn alpha_test(&self, ray: Ray, shape_intersection: ShapeIntersection) -> bool {
+        if self.alpha.is_none() {
+            return true;
+        }
+
+        let a = self.alpha(self.shape.uv(ray, shape_intersection));
+        if a >= 1.0 {
+            true
+        } else if a <= 0.0 {
+            false
+        } else {
+            hash_ray(ray) < a
         }
     }
 
     pub fn intersect(&'a self, mut ray: Ray) -> Option<Intersection<'a>> {
+        if let Some(motion) = &self.motion {
+            ray = motion.at(ray.time).transform_ray_inv(ray);
+        }
         if let Some(world_to_object) = self.world_to_object {
             ray = world_to_object.transform_ray_inv(ray);
         }
 
-        let shape_intersection = self.shape.intersect(ray);
-        if shape_intersection.t > 0.0 {
-            Some(Intersection {
-                primitive: self,
-                shape_intersection,
-            })
-        } else {
-            None
+        // keep looking behind the parts that are cut out, the distance is along the original ray
+        let mut t_offset = 0.0;
+        loop {
+            let mut shape_intersection = self.shape.intersect(ray);
+            if shape_intersection.t <= 0.0 {
+                return None;
+            }
+
+            if self.alpha_test(ray, shape_intersection) {
+                shape_intersection.t += t_offset;
+                return Some(Intersection {
+                    primitive: self,
+                    shape_intersection,
+                    instance: None,
+                });
+            }
+
+            let t = shape_intersection.t + ALPHA_SKIP_EPSILON;
+            t_offset += t;
+            ray = Ray::new(ray.at(t), ray.d).with_time(ray.time);
         }
     }
 
@@ -115,12 +250,35 @@ impl<'a> Primitive {
         if let Some(transform) = self.world_to_object {
             bounds = transform.transform_bounds(bounds);
         }
+        if let Some(motion) = &self.motion {
+            bounds = motion.motion_bounds(bounds);
+        }
         bounds
     }
 
+    /// The area of the shape in world space. Exact for flat shapes and for transforms that only rotate,
+    /// translate and scale uniformly, an estimate for curved shapes that are stretched.
     pub fn area(&self) -> f32 {
-        //TODO: transforms?
-        self.shape.area()
+        let area = self.shape.area();
+        let Some(world_to_object) = self.world_to_object else {
+            return area;
+        };
+
+        // a linear map scales the area around a normal n by |det m| |m^-T n|
+        let m = Matrix3::from_mat4(world_to_object.matrix());
+        let n = self.shape.sample(Point2::splat(0.5)).n;
+        area * m.determinant().abs() * (m.inverse().transpose() * n).length()
+    }
+
+    /// Returns the normal on the outside of the shape at `p`, see [`ShapeT::normal`].
+    pub fn normal(&self, p: Point3) -> Normal3 {
+        if let Some(world_to_object) = self.world_to_object {
+            world_to_object
+                .transform_normal(self.shape.normal(world_to_object.transform_point_inv(p)))
+                .normalize()
+        } else {
+            self.shape.normal(p)
+        }
     }
 
     pub fn sample(&self, u: Point2) -> ShapeSample {
@@ -132,14 +290,62 @@ impl<'a> Primitive {
 
         s
     }
+
+    /// Samples a point on the shape by the solid angle it covers as seen from `p`, see
+    /// [`ShapeT::sample_solid_angle`]. The solid angle of the shape is only the same in world space if
+    /// the transform keeps angles, otherwise it is `None` so the shape is sampled by area.
+    pub fn sample_solid_angle(&self, p: Point3, u: Point2) -> Option<(ShapeSample, f32)> {
+        let Some(world_to_object) = self.world_to_object else {
+            return self.shape.sample_solid_angle(p, u);
+        };
+        if !world_to_object.keeps_angles() {
+            return None;
+        }
+
+        let (mut s, pdf) = self
+            .shape
+            .sample_solid_angle(world_to_object.transform_point_inv(p), u)?;
+        s.p = world_to_object.transform_point(s.p);
+        s.n = world_to_object.transform_normal(s.n).normalize();
+
+        Some((s, pdf))
+    }
+
+    /// The density of [`Primitive::sample_solid_angle`], see [`ShapeT::pdf_solid_angle`].
+    pub fn pdf_solid_angle(&self, p: Point3, wi: Vector3) -> Option<f32> {
+        if let Some(world_to_object) = self.world_to_object {
+            if !world_to_object.keeps_angles() {
+                return None;
+            }
+
+            self.shape.pdf_solid_angle(
+                world_to_object.transform_point_inv(p),
+                world_to_object.transform_vector_inv(wi).normalize(),
+            )
+        } else {
+            self.shape.pdf_solid_angle(p, wi)
+        }
+    }
 }
 
 impl<'a> Intersection<'a> {
     pub fn get_surface_interaction(&self, ray: Ray) -> SurfaceInteraction<'a> {
+        if let Some(instance) = self.instance {
+            let si = Intersection {
+                instance: None,
+                ..*self
+            }
+            .get_surface_interaction(instance.transform_ray_inv(ray));
+
+            return instance.transform_surface_interaction(ray, si);
+        }
+
+        let motion = self.primitive.motion.map(|m| m.at(ray.time));
+        let unmoved = motion.map_or(ray, |m| m.transform_ray_inv(ray));
         let r = if let Some(world_to_object) = self.primitive.world_to_object {
-            world_to_object.transform_ray_inv(ray)
+            world_to_object.transform_ray_inv(unmoved)
         } else {
-            ray
+            unmoved
         };
 
         let shape_interaction = self
@@ -156,12 +362,178 @@ impl<'a> Intersection<'a> {
             uv: shape_interaction.uv,
             dp_du: shape_interaction.dp_du,
             dp_dv: shape_interaction.dp_dv,
+            time: ray.time,
         };
 
         if let Some(transform) = self.primitive.world_to_object {
-            si = transform.transform_surface_interaction(ray, si);
+            si = transform.transform_surface_interaction(unmoved, si);
+        }
+        if let Some(motion) = motion {
+            si = motion.transform_surface_interaction(ray, si);
         }
 
         si
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::aggregates::{AggregateT, Bvh};
+    use crate::core::Array2d;
+    use crate::shapes::{Quad, Sphere, Triangle, TriangleMesh};
+    use crate::textures::{ConstantTexture, ImageTexture};
+
+    /// The unit quad at `z`, cut out by `alpha`.
+    fn quad(z: f32, alpha: Texture<f32>) -> Primitive {
+        Primitive::new(
+            Shape::Quad(Quad::unit()),
+            0,
+            None,
+            Some(Transform3::translate(Vector3::new(0.0, 0.0, z))),
+            MediumInterface::none(),
+        )
+        .with_alpha(Some(Arc::new(alpha)))
+    }
+
+    /// A ray straight down through the xy plane at `(x, y)`.
+    fn ray(x: f32, y: f32) -> Ray {
+        Ray::new(Point3::new(x, y, 5.0), Vector3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn fully_cut_out() {
+        let quad = quad(0.0, Texture::Constant(ConstantTexture::new(0.0)));
+
+        for i in 0..100 {
+            let x = i as f32 / 50.0 - 0.99;
+            assert!(quad.intersect(ray(x, 0.3)).is_none(), "hit at {x}");
+        }
+    }
+
+    #[test]
+    fn partially_cut_out() {
+        // the left half of the quad is cut out
+        let alpha = Array2d::from_1d(UExtent2::new(3, 1), vec![0.0, 1.0, 1.0]);
+        let quad = quad(0.0, Texture::Image(ImageTexture::new(alpha)));
+
+        assert!(quad.intersect(ray(-0.5, 0.2)).is_none());
+        let hit = quad.intersect(ray(0.5, 0.2)).unwrap();
+        assert!((hit.shape_intersection.t - 5.0).abs() < 1e-4);
+
+        // half transparent parts are hit by about half the rays, always at the right distance
+        let quad = self::quad(0.0, Texture::Constant(ConstantTexture::new(0.5)));
+        let n = 10_000;
+        let hits = (0..n)
+            .filter_map(|i| {
+                let x = (i % 100) as f32 / 50.0 - 0.99;
+                let y = (i / 100) as f32 / 50.0 - 0.99;
+                quad.intersect(ray(x, y))
+            })
+            .inspect(|hit| assert!((hit.shape_intersection.t - 5.0).abs() < 1e-4))
+            .count();
+        let fraction = hits as f32 / n as f32;
+        assert!((fraction - 0.5).abs() < 0.03, "{fraction} of the rays hit");
+    }
+
+    #[test]
+    fn shadow_ray_through_cut_out() {
+        // the shadow ray passes through the cut out quad in front and is blocked by the one behind it
+        let front = quad(1.0, Texture::Constant(ConstantTexture::new(0.0)));
+        let back = quad(-1.0, Texture::Constant(ConstantTexture::new(1.0)));
+        let bvh = Bvh::new(vec![front.clone(), back]);
+
+        let hit = bvh.intersect_p(ray(0.2, 0.4)).0.unwrap();
+        assert!((hit.shape_intersection.t - 6.0).abs() < 1e-4);
+
+        let bvh = Bvh::new(vec![front]);
+        assert!(bvh.intersect_p(ray(0.2, 0.4)).0.is_none());
+    }
+
+    #[test]
+    fn area_of_transformed_shapes() {
+        let scale = Transform3::scale(Vector3::new(2.0, 3.0, 4.0));
+        let primitive =
+            |shape| Primitive::new(shape, 0, None, Some(scale), MediumInterface::none());
+
+        // the triangles of a shared mesh keep their transform
+        let mesh = TriangleMesh::new(
+            vec![
+                Point3::ZERO,
+                Point3::X,
+                Point3::Y,
+                Point3::new(1.0, 1.0, 0.0),
+            ],
+            vec![],
+            vec![],
+            vec![0, 1, 2, 1, 3, 2],
+        );
+        let triangles = mesh.triangles();
+        for triangle in triangles.clone() {
+            let triangle = primitive(triangle);
+            assert!(triangle.world_to_object.is_some());
+            assert!((triangle.area() - 3.0).abs() < 1e-5, "{}", triangle.area());
+        }
+
+        // standalone triangles are transformed instead
+        let v = [Point3::ZERO, Point3::Z, Point3::Y];
+        let triangle = primitive(Shape::Triangle(Triangle::new(
+            v,
+            [Normal3::X; 3],
+            [Point2::ZERO; 3],
+        )));
+        assert!(triangle.world_to_object.is_none());
+        assert!((triangle.area() - 6.0).abs() < 1e-5, "{}", triangle.area());
+
+        let sphere = Primitive::new(
+            Shape::Sphere(Sphere::new(1.0)),
+            0,
+            None,
+            Some(Transform3::scale(Vector3::splat(2.0))),
+            MediumInterface::none(),
+        );
+        let area = 16.0 * core::f32::consts::PI;
+        assert!((sphere.area() - area).abs() < 1e-3, "{}", sphere.area());
+    }
+
+    #[test]
+    fn stretched_shapes_are_sampled_by_area() {
+        let sphere = |transform| {
+            Primitive::new(
+                Shape::Sphere(Sphere::new(1.0)),
+                0,
+                None,
+                Some(transform),
+                MediumInterface::none(),
+            )
+        };
+        let p = Point3::new(0.0, 0.0, 10.0);
+
+        let similar = sphere(
+            Transform3::translate(Vector3::X)
+                * Transform3::rotate(Point3::new(0.3, 0.0, 1.0))
+                * Transform3::scale(Vector3::splat(2.0)),
+        );
+        assert!(similar.sample_solid_angle(p, Point2::splat(0.5)).is_some());
+        assert!(similar.pdf_solid_angle(p, Vector3::NEG_Z).is_some());
+
+        let stretched = sphere(Transform3::scale(Vector3::new(1.0, 1.0, 3.0)));
+        assert!(stretched
+            .sample_solid_angle(p, Point2::splat(0.5))
+            .is_none());
+        assert!(stretched.pdf_solid_angle(p, Vector3::NEG_Z).is_none());
+    }
+
+    #[test]
+    fn light_links() {
+        let all = LightLink::All;
+        let only = LightLink::only([4, 1, 4]);
+        let except = LightLink::except([3, 0, 3]);
+
+        for light_index in 0..6 {
+            assert!(all.contains(light_index));
+            assert_eq!(only.contains(light_index), matches!(light_index, 1 | 4));
+            assert_eq!(except.contains(light_index), !matches!(light_index, 0 | 3));
+        }
+    }
+}
@@ -1,23 +1,41 @@
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
 
 use crate::lights::AreaLight;
 use crate::prelude::*;
 use crate::{
-    aggregates::{Aggregate, AggregateT, Bvh},
-    cameras::Camera,
-    lights::{Light, Visibility},
+    aggregates::{Aggregate, AggregateT, Bvh, Instance},
+    cameras::{Camera, CameraT},
+    lights::{Light, LightT, Visibility},
     loaders::{Loader, SceneCreationParams},
     materials::Material,
     media::MediumInterface,
-    primitive::{Primitive, SurfaceInteraction},
+    primitive::{LightLink, Primitive, SurfaceInteraction},
     shapes::Shape,
+    spectra::SpectrumT,
+    textures::Texture,
 };
 
+/// What is in the way of a shadow ray towards a light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Occlusion {
+    Blocked,
+    Clear,
+    /// Only primitives that don't cast shadows for the light are in the way. Sampling the light is the only
+    /// way to reach it through them.
+    Unlinked,
+}
+
 pub struct Scene {
     pub lights: Vec<Light>,
     pub aggregate: Aggregate,
     pub camera: Camera,
     pub materials: Vec<Material>,
+    /// The index of the light group of every light, if it is in one.
+    pub light_groups: Vec<Option<usize>>,
+    /// Distribution of the lights proportional to their power.
+    light_distribution: Distribution1d,
 }
 
 impl Scene {
@@ -26,26 +44,69 @@ impl Scene {
         aggregate: Aggregate,
         camera: Camera,
         materials: Vec<Material>,
+        light_groups: Vec<Option<usize>>,
     ) -> Self {
+        assert_eq!(lights.len(), light_groups.len());
+
+        let scene_radius = aggregate.bounds().diagonal().length() * 0.5;
+        let light_distribution = Distribution1d::new(
+            &lights
+                .iter()
+                .map(|l| l.power(scene_radius).y())
+                .collect::<Vec<_>>(),
+        );
+
         Self {
             lights,
             aggregate,
             camera,
             materials,
+            light_groups,
+            light_distribution,
+        }
+    }
+
+    /// Picks a light proportionally to its power, returns the index of the light and the probability it was
+    /// picked with.
+    pub fn sample_light(&self, u: f32) -> Option<(usize, f32)> {
+        if self.lights.is_empty() {
+            return None;
         }
+
+        Some(self.light_distribution.sample_discrete(u))
+    }
+
+    /// Probability of `sample_light` picking the light at `index`.
+    pub fn light_pmf(&self, index: usize) -> f32 {
+        self.light_distribution.discrete_pmf(index)
     }
 
-    pub fn unoccluded(&self, visibility: Visibility) -> bool {
+    /// Whether the light at `light_index` can be seen along `visibility`, see [`Scene::occlusion`].
+    pub fn unoccluded(&self, visibility: Visibility, light_index: usize) -> bool {
+        self.occlusion(visibility, light_index) != Occlusion::Blocked
+    }
+
+    /// Finds what is in the way of the light at `light_index` along `visibility`, passing through the
+    /// primitives that don't cast shadows for the light.
+    pub fn occlusion(&self, visibility: Visibility, light_index: usize) -> Occlusion {
         STATS.shadow_intersection_tests.inc();
 
-        if let Some(intersection) = self.aggregate.intersect_p(visibility.ray).0 {
-            if intersection.shape_intersection.t < visibility.end.distance(visibility.ray.o)
-                && intersection.shape_intersection.t > 0.0
-            {
-                return false;
+        let mut ray = visibility.ray;
+        let mut occlusion = Occlusion::Clear;
+        while let Some(intersection) = self.aggregate.intersect_p(ray).0 {
+            let t = intersection.shape_intersection.t;
+            if t >= visibility.end.distance(ray.o) || t <= 0.0 {
+                break;
+            }
+            if intersection.primitive.shadow_link.contains(light_index) {
+                return Occlusion::Blocked;
             }
+
+            occlusion = Occlusion::Unlinked;
+            ray = Ray::new(ray.at(t + 1e-4), ray.d).with_time(ray.time);
         }
-        true
+
+        occlusion
     }
 
     pub fn intersect(&self, ray: Ray) -> (Option<SurfaceInteraction>, usize) {
@@ -63,7 +124,22 @@ impl Scene {
 #[derive(Default)]
 pub struct SceneBuilder {
     lights: Vec<Light>,
+    light_groups: Vec<Option<usize>>,
+    light_group_names: Vec<String>,
+    /// The group lights are put in as they are added.
+    current_light_group: Option<usize>,
+    /// The links primitives get as they are added.
+    light_link: LightLink,
+    shadow_link: LightLink,
+    motion: Option<AnimatedTransform>,
+    alpha: Option<Arc<Texture<f32>>>,
     primitives: Vec<Primitive>,
+    /// Objects that can be instanced, by name.
+    objects: HashMap<String, Arc<Bvh>>,
+    /// The name of the object being defined and the primitives outside of it, which are put back once it
+    /// is done.
+    current_object: Option<(String, Vec<Primitive>)>,
+    instances: Vec<Instance>,
     camera: Option<Camera>,
     materials: Vec<Material>,
 }
@@ -72,7 +148,17 @@ impl SceneBuilder {
     pub fn new() -> Self {
         Self {
             lights: vec![],
+            light_groups: vec![],
+            light_group_names: vec![],
+            current_light_group: None,
+            light_link: LightLink::All,
+            shadow_link: LightLink::All,
+            motion: None,
+            alpha: None,
             primitives: vec![],
+            objects: HashMap::new(),
+            current_object: None,
+            instances: vec![],
             camera: None,
             materials: vec![],
         }
@@ -80,6 +166,101 @@ impl SceneBuilder {
 
     pub fn light(&mut self, light: Light) -> &mut Self {
         self.lights.push(light);
+        self.light_groups.push(self.current_light_group);
+
+        self
+    }
+
+    /// Puts the lights added after this in the light group `name`, or in no group for `None`. The film
+    /// gets a layer with the contributions of every group.
+    pub fn light_group(&mut self, name: Option<&str>) -> &mut Self {
+        self.current_light_group = name.map(|name| {
+            self.light_group_names
+                .iter()
+                .position(|n| n == name)
+                .unwrap_or_else(|| {
+                    self.light_group_names.push(name.to_owned());
+                    self.light_group_names.len() - 1
+                })
+        });
+
+        self
+    }
+
+    /// The number of lights added so far, lights are linked to primitives by the order they were added in.
+    #[inline]
+    pub fn light_count(&self) -> usize {
+        self.lights.len()
+    }
+
+    /// Makes the primitives added after this only be lit by the lights in `link`.
+    pub fn light_link(&mut self, link: LightLink) -> &mut Self {
+        self.light_link = link;
+        self
+    }
+
+    /// Makes the primitives added after this only cast shadows for the lights in `link`.
+    pub fn shadow_link(&mut self, link: LightLink) -> &mut Self {
+        self.shadow_link = link;
+        self
+    }
+
+    /// Moves the primitives added after this by `motion` while the shutter is open.
+    pub fn motion(&mut self, motion: Option<AnimatedTransform>) -> &mut Self {
+        self.motion = motion;
+        self
+    }
+
+    /// Cuts out the parts of the primitives added after this where `alpha` is zero.
+    pub fn alpha(&mut self, alpha: Option<Texture<f32>>) -> &mut Self {
+        self.alpha = alpha.map(Arc::new);
+        self
+    }
+
+    /// Puts the primitives added after this in the object `name` until [`SceneBuilder::end_object`],
+    /// instead of in the scene.
+    pub fn begin_object(&mut self, name: &str) -> &mut Self {
+        if let Some((current, _)) = &self.current_object {
+            warnln!("objects can't be nested, ending object `{current}` to begin `{name}`.");
+            self.end_object();
+        }
+
+        self.current_object = Some((name.to_owned(), std::mem::take(&mut self.primitives)));
+        self
+    }
+
+    pub fn end_object(&mut self) -> &mut Self {
+        let Some((name, primitives)) = self.current_object.take() else {
+            warnln!("ending an object without beginning one.");
+            return self;
+        };
+
+        let object = std::mem::replace(&mut self.primitives, primitives);
+        if object.is_empty() {
+            warnln!("object `{name}` has no geometry, skipping it.");
+            self.objects.remove(&name);
+            return self;
+        }
+
+        if self
+            .objects
+            .insert(name.clone(), Arc::new(Bvh::new(object)))
+            .is_some()
+        {
+            warnln!("redefining object `{name}`.");
+        }
+
+        self
+    }
+
+    /// Adds a copy of the object `name` transformed by `world_to_object`.
+    pub fn instance(&mut self, name: &str, world_to_object: Transform3) -> &mut Self {
+        if let Some(object) = self.objects.get(name) {
+            self.instances
+                .push(Instance::new(object.clone(), world_to_object));
+        } else {
+            warnln!("instancing unknown object `{name}`.");
+        }
 
         self
     }
@@ -101,15 +282,25 @@ impl SceneBuilder {
         world_to_object: Option<Transform3>,
         medium_interface: MediumInterface,
     ) -> &mut Self {
+        if let Material::Emissive(_) = material {
+            return self.primitives(vec![shape], material, world_to_object, medium_interface);
+        }
+
         // TODO: material reuse/real material ids
         self.materials.push(material);
-        self.primitives.push(Primitive::new(
-            shape,
-            self.materials.len() - 1,
-            None,
-            world_to_object,
-            medium_interface,
-        ));
+        self.primitives.push(
+            Primitive::new(
+                shape,
+                self.materials.len() - 1,
+                None,
+                world_to_object,
+                medium_interface,
+            )
+            .with_light_link(self.light_link.clone())
+            .with_shadow_link(self.shadow_link.clone())
+            .with_motion(self.motion)
+            .with_alpha(self.alpha.clone()),
+        );
 
         self
     }
@@ -126,15 +317,60 @@ impl SceneBuilder {
         }
 
         self.materials.push(material);
-        self.primitives.extend(shapes.into_iter().map(|s| {
+        let material_index = self.materials.len() - 1;
+        let primitive = |s, world_to_object| {
             Primitive::new(
                 s,
-                self.materials.len() - 1,
+                material_index,
                 None,
                 world_to_object,
                 medium_interface.clone(),
             )
-        }));
+            .with_light_link(self.light_link.clone())
+            .with_shadow_link(self.shadow_link.clone())
+            .with_alpha(self.alpha.clone())
+        };
+
+        // every shape with an emissive material becomes its own light
+        if let Material::Emissive(emissive) = &self.materials[material_index] {
+            if let Some((name, _)) = &self.current_object {
+                warnln!("area lights in objects are not supported, skipping them in `{name}`.");
+                return self;
+            }
+
+            // the light is placed where its motion starts, on top of its own transform
+            let world_to_object = match self.motion {
+                Some(motion) => {
+                    warnln!(
+                        "moving area lights are not supported, they stay where their motion starts."
+                    );
+                    Some(motion.start() * world_to_object.unwrap_or_else(Transform3::identity))
+                }
+                None => world_to_object,
+            };
+
+            let lights = AreaLight::multi_new(
+                shapes
+                    .into_iter()
+                    .map(|s| primitive(s, world_to_object))
+                    .collect(),
+                emissive.radiance.clone(),
+                emissive.two_sided,
+            );
+            let lights = match &emissive.profile {
+                Some(profile) => lights
+                    .into_iter()
+                    .map(|l| l.with_profile(profile.clone()))
+                    .collect(),
+                None => lights,
+            };
+            return self.area_lights(lights);
+        }
+
+        let primitives = shapes
+            .into_iter()
+            .map(|s| primitive(s, world_to_object).with_motion(self.motion));
+        self.primitives.extend(primitives);
 
         self
     }
@@ -143,7 +379,8 @@ impl SceneBuilder {
         light.primitive.area_light_index = Some(self.lights.len());
         self.primitives.push(light.primitive.clone());
 
-        self.lights.push(Light::Area(light));
+        self.lights.push(Light::Area(Box::new(light)));
+        self.light_groups.push(self.current_light_group);
         self
     }
 
@@ -153,8 +390,10 @@ impl SceneBuilder {
             .extend(lights.into_iter().enumerate().map(|(i, mut light)| {
                 light.primitive.area_light_index = Some(old_len + i);
                 self.primitives.push(light.primitive.clone());
-                Light::Area(light)
+                Light::Area(Box::new(light))
             }));
+        self.light_groups
+            .resize(self.lights.len(), self.current_light_group);
         self
     }
 
@@ -164,16 +403,203 @@ impl SceneBuilder {
     }
 
     //FIXME: turn this into a result
-    pub fn build(self) -> Option<Scene> {
-        let aggregate = Aggregate::Bvh(Bvh::new(self.primitives));
+    pub fn build(mut self) -> Option<Scene> {
+        if self.current_object.is_some() {
+            warnln!("building scene while an object is being defined, ending it.");
+            self.end_object();
+        }
+
+        let aggregate = if self.instances.is_empty() {
+            Aggregate::Bvh(Bvh::new(self.primitives))
+        } else {
+            // the primitives that aren't in objects are one more instance
+            if !self.primitives.is_empty() {
+                self.instances.push(Instance::new(
+                    Arc::new(Bvh::new(self.primitives)),
+                    Transform3::identity(),
+                ));
+            }
+            Aggregate::Instanced(Bvh::new(self.instances))
+        };
         if self.camera.is_none() {
             warnln!("attempting to build scene without camera.");
         }
+
+        let mut camera = self.camera?;
+        camera
+            .get_film_mut()
+            .set_light_groups(self.light_group_names);
+
         Some(Scene::new(
             self.lights,
             aggregate,
-            self.camera?,
+            camera,
             self.materials,
+            self.light_groups,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bsdfs::{Bsdf, Lambertian};
+    use crate::cameras::PerspectiveCamera;
+    use crate::film::Film;
+    use crate::lights::PointLight;
+    use crate::materials::DirectMaterial;
+    use crate::rfilters::TentFilter;
+    use crate::shapes::Quad;
+    use crate::spectra::Spectrum;
+    use crate::textures::{ConstantTexture, SpectralTexture};
+
+    use super::*;
+
+    /// The unit quad at `z`, casting shadows for the lights in `shadow_link`.
+    fn quad(z: f32, shadow_link: LightLink) -> Primitive {
+        Primitive::new(
+            Shape::Quad(Quad::unit()),
+            0,
+            None,
+            Some(Transform3::translate(Vector3::new(0.0, 0.0, z))),
+            MediumInterface::none(),
+        )
+        .with_shadow_link(shadow_link)
+    }
+
+    fn camera() -> Camera {
+        Camera::Projective(PerspectiveCamera::new_perspective(
+            Film::new(UExtent2::new(1, 1), TentFilter::new(Vector2::splat(1.0))),
+            Transform3::identity(),
+            core::f32::consts::FRAC_PI_2,
+            0.0,
+            1.0,
+            None,
         ))
     }
+
+    /// Three lights with quads in the way at z = 2, which doesn't cast shadows for lights 0 and 2, and at
+    /// z = 4, which only casts shadows for light 2.
+    fn scene() -> Scene {
+        let lights = (0..3)
+            .map(|_| {
+                Light::Point(PointLight::new(
+                    Point3::new(0.0, 0.0, 5.0),
+                    Spectrum::splat(1.0),
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        Scene::new(
+            lights,
+            Aggregate::Bvh(Bvh::new(vec![
+                quad(2.0, LightLink::except([0, 2])),
+                quad(4.0, LightLink::only([2])),
+            ])),
+            camera(),
+            vec![],
+            vec![None; 3],
+        )
+    }
+
+    fn visibility(o: Point3, end: Point3) -> Visibility {
+        Visibility {
+            ray: Ray::new(o, (end - o).normalize()),
+            end,
+        }
+    }
+
+    #[test]
+    fn occlusion() {
+        let scene = scene();
+        let occlusion = |end: f32, light_index| {
+            scene.occlusion(
+                visibility(Point3::ZERO, Point3::new(0.0, 0.0, end)),
+                light_index,
+            )
+        };
+
+        for light_index in 0..3 {
+            // nothing in the way
+            let beside = visibility(Point3::new(3.0, 0.0, 0.0), Point3::new(3.0, 0.0, 5.0));
+            assert_eq!(scene.occlusion(beside, light_index), Occlusion::Clear);
+            assert_eq!(occlusion(1.0, light_index), Occlusion::Clear);
+        }
+
+        // passes through both quads
+        assert_eq!(occlusion(5.0, 0), Occlusion::Unlinked);
+        assert!(scene.unoccluded(visibility(Point3::ZERO, Point3::new(0.0, 0.0, 5.0)), 0));
+        // stopped by the first quad
+        assert_eq!(occlusion(5.0, 1), Occlusion::Blocked);
+        assert!(!scene.unoccluded(visibility(Point3::ZERO, Point3::new(0.0, 0.0, 5.0)), 1));
+        // passes through the first quad but is stopped by the second one
+        assert_eq!(occlusion(5.0, 2), Occlusion::Blocked);
+        // only the first quad is in the way
+        assert_eq!(occlusion(3.0, 2), Occlusion::Unlinked);
+    }
+
+    #[test]
+    fn instances() {
+        let material = || {
+            Material::Direct(DirectMaterial::new(Bsdf::Lambertian(Lambertian::new(
+                SpectralTexture::Constant(ConstantTexture::new(Spectrum::splat(0.5))),
+            ))))
+        };
+
+        let mut sb = SceneBuilder::new();
+        sb.camera(camera())
+            .light(Light::Point(PointLight::new(
+                Point3::new(0.0, 0.0, 5.0),
+                Spectrum::splat(1.0),
+            )))
+            .begin_object("quad")
+            .primitive(
+                Shape::Quad(Quad::unit()),
+                material(),
+                None,
+                MediumInterface::none(),
+            )
+            .end_object()
+            .instance("quad", Transform3::translate(Vector3::new(0.0, 0.0, 2.0)))
+            // stands in the xz plane
+            .instance(
+                "quad",
+                Transform3::translate(Vector3::new(5.0, 0.0, 0.0))
+                    * Transform3::rotate(Point3::new(core::f32::consts::FRAC_PI_2, 0.0, 0.0)),
+            )
+            .primitive(
+                Shape::Quad(Quad::unit()),
+                material(),
+                Some(Transform3::translate(Vector3::new(0.0, 0.0, -3.0))),
+                MediumInterface::none(),
+            );
+        let scene = sb.build().unwrap();
+        assert!(matches!(scene.aggregate, Aggregate::Instanced(_)));
+
+        let bounds = scene.bounds();
+        let expected = Bounds3::new(Point3::new(-1.0, -1.0, -3.0), Point3::new(6.0, 1.0, 2.0));
+        assert!(
+            bounds.min.distance(expected.min) < 1e-4 && bounds.max.distance(expected.max) < 1e-4,
+            "{bounds:?} != {expected:?}"
+        );
+
+        let hit = |o: Point3, d: Vector3| scene.intersect(Ray::new(o, d)).0;
+        let close = |a: Point3, b: Point3| a.distance(b) < 1e-4;
+
+        let si = hit(Point3::new(0.3, 0.2, 5.0), Vector3::NEG_Z).unwrap();
+        assert!((si.t - 3.0).abs() < 1e-4, "{}", si.t);
+        assert!(close(si.p, Point3::new(0.3, 0.2, 2.0)), "{}", si.p);
+        assert!((si.n.z.abs() - 1.0).abs() < 1e-4, "{}", si.n);
+
+        // the primitive outside of the object is hit from below
+        let si = hit(Point3::new(0.3, 0.2, -5.0), Vector3::Z).unwrap();
+        assert!(close(si.p, Point3::new(0.3, 0.2, -3.0)), "{}", si.p);
+
+        let si = hit(Point3::new(5.5, -4.0, 0.5), Vector3::Y).unwrap();
+        assert!((si.t - 4.0).abs() < 1e-4, "{}", si.t);
+        assert!(close(si.p, Point3::new(5.5, 0.0, 0.5)), "{}", si.p);
+        assert!((si.n.y.abs() - 1.0).abs() < 1e-4, "{}", si.n);
+
+        assert!(hit(Point3::new(5.5, -4.0, 1.5), Vector3::Y).is_none());
+        assert!(hit(Point3::new(2.5, 0.0, 5.0), Vector3::NEG_Z).is_none());
+    }
 }
@@ -6,6 +6,27 @@ pub use sphere::*;
 mod triangle;
 pub use triangle::*;
 
+mod triangle_mesh;
+pub use triangle_mesh::*;
+
+mod subdivision;
+pub use subdivision::*;
+
+mod curve;
+pub use curve::*;
+
+mod quad;
+pub use quad::*;
+
+mod disk;
+pub use disk::*;
+
+mod cylinder;
+pub use cylinder::*;
+
+mod cone;
+pub use cone::*;
+
 use crate::prelude::*;
 
 #[derive(Debug, Copy, Clone)]
@@ -27,6 +48,7 @@ pub struct ShapeInteraction {
 pub struct ShapeSample {
     pub p: Point3,
     pub n: Normal3,
+    pub uv: Point2,
 }
 
 #[enum_dispatch]
@@ -39,6 +61,12 @@ pub trait ShapeT {
         intersection: ShapeIntersection,
     ) -> ShapeInteraction;
 
+    /// The uv coordinates of the intersection, for shapes that can find them without the rest of the
+    /// interaction.
+    fn uv(&self, ray: Ray, intersection: ShapeIntersection) -> Point2 {
+        self.get_surface_interaction(ray, intersection).uv
+    }
+
     fn make_bounds(&self) -> Bounds3;
 
     /// Returns the surface area of the shape.
@@ -46,6 +74,22 @@ pub trait ShapeT {
 
     fn sample(&self, u: Point2) -> ShapeSample;
 
+    /// Samples a point on the shape by the solid angle it covers as seen from `p`, returns the point along
+    /// with the density over solid angle. `None` if the shape should be sampled by area instead.
+    fn sample_solid_angle(&self, _p: Point3, _u: Point2) -> Option<(ShapeSample, f32)> {
+        None
+    }
+
+    /// The density of [`ShapeT::sample_solid_angle`] for the direction `wi` from `p`, `None` if the shape is
+    /// sampled by area from there.
+    fn pdf_solid_angle(&self, _p: Point3, _wi: Vector3) -> Option<f32> {
+        None
+    }
+
+    /// Returns the normal on the outside of the shape at the point `p` on its surface, unlike the normal of
+    /// an interaction it is never flipped towards the viewer.
+    fn normal(&self, p: Point3) -> Normal3;
+
     /// If possible transforms the shape by the specified transform and returns true.
     /// Otherwise does not transform the shape and returns false.
     fn transform(&mut self, _transform: &Transform3) -> bool {
@@ -58,4 +102,50 @@ pub trait ShapeT {
 pub enum Shape {
     Sphere(Sphere),
     Triangle(Triangle),
+    Curve(Curve),
+    Quad(Quad),
+    Disk(Disk),
+    Cylinder(Cylinder),
+    Cone(Cone),
+}
+
+/// The roots of `a t^2 + b t + c`, smallest first, `None` if there are no real roots.
+fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    let discriminant = b as f64 * b as f64 - 4.0 * a as f64 * c as f64;
+    if discriminant < 0.0 || a == 0.0 {
+        return None;
+    }
+
+    // avoids the cancellation of the textbook formula
+    let q = if b < 0.0 {
+        -0.5 * (b as f64 - discriminant.sqrt())
+    } else {
+        -0.5 * (b as f64 + discriminant.sqrt())
+    };
+    let t0 = (q / a as f64) as f32;
+    let t1 = if q != 0.0 { (c as f64 / q) as f32 } else { t0 };
+
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+/// The angle of `p` around the z axis, in `[0, 2pi)`.
+fn azimuth(p: Point3) -> f32 {
+    let phi = p.y.atan2(p.x);
+    if phi < 0.0 {
+        phi + core::f32::consts::TAU
+    } else {
+        phi
+    }
+}
+
+/// The uniform scale and the shift along the z axis that `transform` is made of, `None` if it also
+/// rotates, shears, scales unevenly or moves off the z axis. Shapes around the z axis keep their form
+/// under these, any other transform has to stay with the primitive.
+fn axial_scale_and_shift(transform: &Transform3) -> Option<(f32, f32)> {
+    let m = transform.matrix();
+    let (s, z) = (m.x_axis.x, m.w_axis.z);
+    let expected = Matrix4::from_translation(Vector3::new(0.0, 0.0, z))
+        * Matrix4::from_scale(Vector3::splat(s));
+
+    (s > 0.0 && m.abs_diff_eq(expected, 1e-5)).then_some((s, z))
 }
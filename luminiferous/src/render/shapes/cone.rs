@@ -0,0 +1,177 @@
+use crate::prelude::*;
+
+use super::{
+    axial_scale_and_shift, azimuth, solve_quadratic, ShapeInteraction, ShapeIntersection,
+    ShapeSample, ShapeT,
+};
+
+/// An open cone around the z axis with its base of `radius` at the origin and its apex at `height`, cut
+/// off at the angle `phi_max` around the axis.
+#[derive(Debug, Clone)]
+pub struct Cone {
+    height: f32,
+    radius: f32,
+    phi_max: f32,
+}
+
+impl Cone {
+    pub fn new(height: f32, radius: f32, phi_max: f32) -> Self {
+        STATS.shapes_created.inc();
+
+        Self {
+            height,
+            radius,
+            phi_max: phi_max.clamp(0.0, core::f32::consts::TAU),
+        }
+    }
+
+    /// Whether the point `p` on the infinite double cone is on the part that is kept.
+    fn contains(&self, p: Point3) -> bool {
+        p.z >= self.height.min(0.0) && p.z <= self.height.max(0.0) && azimuth(p) <= self.phi_max
+    }
+}
+
+impl ShapeT for Cone {
+    fn intersect(&self, ray: Ray) -> ShapeIntersection {
+        let k = (self.radius / self.height) * (self.radius / self.height);
+        let oz = ray.o.z - self.height;
+        let a = ray.d.x * ray.d.x + ray.d.y * ray.d.y - k * ray.d.z * ray.d.z;
+        let b = 2.0 * (ray.d.x * ray.o.x + ray.d.y * ray.o.y - k * ray.d.z * oz);
+        let c = ray.o.x * ray.o.x + ray.o.y * ray.o.y - k * oz * oz;
+
+        // the nearest of the two hits that isn't cut off
+        let t = solve_quadratic(a, b, c)
+            .and_then(|(t0, t1)| {
+                [t0, t1]
+                    .into_iter()
+                    .find(|&t| t > 0.0 && self.contains(ray.at(t)))
+            })
+            .unwrap_or(-1.0);
+
+        ShapeIntersection { t }
+    }
+
+    fn get_surface_interaction(
+        &self,
+        ray: Ray,
+        intersection: ShapeIntersection,
+    ) -> ShapeInteraction {
+        let p = ray.at(intersection.t);
+        let v = p.z / self.height;
+        // at the apex the sides along v meet, any direction towards the base will do
+        let towards_apex = if v < 1.0 {
+            Vector3::new(-p.x / (1.0 - v), -p.y / (1.0 - v), self.height)
+        } else {
+            Vector3::new(-self.radius, 0.0, self.height)
+        };
+
+        ShapeInteraction {
+            intersection,
+            p,
+            n: self.normal(p),
+            uv: Point2::new(azimuth(p) / self.phi_max, v),
+            dp_du: Vector3::new(-self.phi_max * p.y, self.phi_max * p.x, 0.0),
+            dp_dv: towards_apex,
+        }
+    }
+
+    fn make_bounds(&self) -> Bounds3 {
+        Bounds3::new(
+            Point3::new(-self.radius, -self.radius, 0.0),
+            Point3::new(self.radius, self.radius, self.height),
+        )
+        .pad(1e-5) // deal with the case that the bounds have zero volume
+    }
+
+    fn area(&self) -> f32 {
+        self.radius * (self.height * self.height + self.radius * self.radius).sqrt() * self.phi_max
+            / 2.0
+    }
+
+    fn sample(&self, u: Point2) -> ShapeSample {
+        // uniform in area, the circumference shrinks linearly towards the apex
+        let v = 1.0 - (1.0 - u.x).sqrt();
+        let r = self.radius * (1.0 - v);
+        let (sin_phi, cos_phi) = (u.y * self.phi_max).sin_cos();
+        let p = Point3::new(r * cos_phi, r * sin_phi, v * self.height);
+
+        ShapeSample {
+            p,
+            n: self.normal(p),
+            uv: Point2::new(u.y, v),
+        }
+    }
+
+    fn normal(&self, p: Point3) -> Normal3 {
+        let r = (p.x * p.x + p.y * p.y).sqrt();
+        if r == 0.0 {
+            return Normal3::new(0.0, 0.0, self.height.signum());
+        }
+
+        // the gradient of x^2 + y^2 - (r / h)^2 (z - h)^2, which points away from the axis
+        Normal3::new(p.x, p.y, r * self.radius / self.height).normalize()
+    }
+
+    fn transform(&mut self, transform: &Transform3) -> bool {
+        // the base stays at the origin, so the cone can only be scaled
+        match axial_scale_and_shift(transform) {
+            Some((scale, shift)) if shift.abs() < 1e-5 => {
+                self.height *= scale;
+                self.radius *= scale;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::f32::consts::{PI, SQRT_2, TAU};
+
+    use super::*;
+
+    #[test]
+    fn intersects_the_side() {
+        let cone = Cone::new(1.0, 1.0, TAU);
+
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 0.5), Vector3::X);
+        let intersection = cone.intersect(ray);
+        assert!((intersection.t - 4.5).abs() < 1e-5);
+        let interaction = cone.get_surface_interaction(ray, intersection);
+        let n = Normal3::new(-1.0, 0.0, 1.0) / SQRT_2;
+        assert!(interaction.n.abs_diff_eq(n, 1e-5));
+        assert!(interaction.uv.abs_diff_eq(Point2::new(0.5, 0.5), 1e-5));
+
+        // the other nappe of the double cone isn't part of the shape
+        let above = Ray::new(Point3::new(-5.0, 0.0, 1.5), Vector3::X);
+        assert!(cone.intersect(above).t < 0.0);
+    }
+
+    #[test]
+    fn samples_lie_on_the_cone() {
+        let cone = Cone::new(2.0, 1.0, PI);
+        assert!((cone.area() - 5.0f32.sqrt() * PI / 2.0).abs() < 1e-5);
+
+        for u in [Point2::new(0.1, 0.2), Point2::new(0.9, 0.7)] {
+            let sample = cone.sample(u);
+            let r = sample.p.truncate().length();
+            assert!((r - (1.0 - sample.p.z / 2.0)).abs() < 1e-5);
+
+            let ray = Ray::new(sample.p + sample.n * 0.1, -sample.n);
+            assert!((cone.intersect(ray).t - 0.1).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn transforms_by_a_scale() {
+        let mut cone = Cone::new(1.0, 1.0, TAU);
+        assert!(cone.transform(&Transform3::scale(Vector3::splat(2.0))));
+
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 1.0), Vector3::X);
+        assert!((cone.intersect(ray).t - 4.0).abs() < 1e-5);
+
+        let shift = Transform3::translate(Vector3::new(0.0, 0.0, 1.0));
+        assert!(!cone.transform(&shift));
+    }
+}
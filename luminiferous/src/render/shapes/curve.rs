@@ -0,0 +1,612 @@
+use std::sync::Arc;
+
+use crate::prelude::*;
+
+use super::{ShapeInteraction, ShapeIntersection, ShapeSample, ShapeT};
+
+/// How the width of a curve is turned into a surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurveKind {
+    /// A flat ribbon that always faces the ray.
+    Flat,
+    /// A flat ribbon that always faces the ray, shaded as if it were a cylinder.
+    Cylinder,
+    /// A ribbon that is oriented by the normals at the start and end of the curve.
+    Ribbon([Normal3; 2]),
+}
+
+/// What the parts of a curve share.
+#[derive(Debug)]
+struct CurveCommon {
+    control_points: [Point3; 4],
+    width: [f32; 2],
+    kind: CurveKind,
+    /// The angle between the normals of a ribbon, they are slerped along the curve.
+    normal_angle: f32,
+    inv_sin_normal_angle: f32,
+}
+
+/// The part from `u_min` to `u_max` of a cubic Bézier curve whose width changes linearly along it, for hair,
+/// fur and grass. Curves are intersected by recursively subdividing them until they are close to straight,
+/// as in pbrt.
+#[derive(Debug, Clone)]
+pub struct Curve {
+    common: Arc<CurveCommon>,
+    u_min: f32,
+    u_max: f32,
+}
+
+/// The deepest curves are subdivided to when intersecting them.
+const MAX_DEPTH: i32 = 10;
+
+/// Where a ray hits a curve, `z` is the distance along the ray.
+struct CurveHit {
+    z: f32,
+    uv: Point2,
+    n: Normal3,
+    dp_du: Vector3,
+    dp_dv: Vector3,
+}
+
+impl Curve {
+    /// Splits the curve through `control_points` into `2^split_depth` parts, which the bvh can bound
+    /// tighter than the whole curve.
+    pub fn split(
+        control_points: [Point3; 4],
+        width: [f32; 2],
+        kind: CurveKind,
+        split_depth: u32,
+    ) -> Vec<Self> {
+        let kind = match kind {
+            CurveKind::Ribbon([n0, n1]) => CurveKind::Ribbon([n0.normalize(), n1.normalize()]),
+            kind => kind,
+        };
+        let normal_angle = match kind {
+            CurveKind::Ribbon([n0, n1]) => n0.dot(n1).clamp(-1.0, 1.0).acos(),
+            _ => 0.0,
+        };
+
+        let common = Arc::new(CurveCommon {
+            control_points,
+            width,
+            kind,
+            normal_angle,
+            inv_sin_normal_angle: normal_angle.sin().recip(),
+        });
+
+        let parts = 1 << split_depth;
+        (0..parts)
+            .map(|i| {
+                STATS.shapes_created.inc();
+                Self {
+                    common: common.clone(),
+                    u_min: i as f32 / parts as f32,
+                    u_max: (i + 1) as f32 / parts as f32,
+                }
+            })
+            .collect()
+    }
+
+    /// Creates curves through all the points of a polyline with a width at every point, the tangents at
+    /// the points are those of a Catmull-Rom spline.
+    pub fn from_strand(
+        points: &[Point3],
+        widths: &[f32],
+        kind: CurveKind,
+        split_depth: u32,
+    ) -> Vec<Self> {
+        assert_eq!(points.len(), widths.len());
+        if points.len() < 2 {
+            return vec![];
+        }
+
+        let last = points.len() as isize - 1;
+        let p = |i: isize| points[i.clamp(0, last) as usize];
+        (0..last)
+            .flat_map(|i| {
+                let control_points = [
+                    p(i),
+                    p(i) + (p(i + 1) - p(i - 1)) / 6.0,
+                    p(i + 1) - (p(i + 2) - p(i)) / 6.0,
+                    p(i + 1),
+                ];
+                let width = [widths[i as usize], widths[i as usize + 1]];
+
+                Self::split(control_points, width, kind, split_depth)
+            })
+            .collect()
+    }
+
+    /// The control points of the part of the curve.
+    fn control_points(&self) -> [Point3; 4] {
+        let cp = &self.common.control_points;
+        let (u0, u1) = (self.u_min, self.u_max);
+
+        [
+            blossom_bezier(cp, u0, u0, u0),
+            blossom_bezier(cp, u0, u0, u1),
+            blossom_bezier(cp, u0, u1, u1),
+            blossom_bezier(cp, u1, u1, u1),
+        ]
+    }
+
+    #[inline]
+    fn width(&self, u: f32) -> f32 {
+        let [w0, w1] = self.common.width;
+        w0 + (w1 - w0) * u
+    }
+
+    /// The normal of a ribbon at `u`, `None` for the other kinds.
+    fn ribbon_normal(&self, u: f32) -> Option<Normal3> {
+        let CurveKind::Ribbon([n0, n1]) = self.common.kind else {
+            return None;
+        };
+
+        if self.common.normal_angle < 1e-4 {
+            return Some(n0);
+        }
+
+        let angle = self.common.normal_angle;
+        let inv_sin = self.common.inv_sin_normal_angle;
+        Some(((1.0 - u) * angle).sin() * inv_sin * n0 + (u * angle).sin() * inv_sin * n1)
+    }
+
+    /// The `u` of the point on the part of the curve that is closest to `p`, found coarsely.
+    fn closest_u(&self, p: Point3) -> f32 {
+        const STEPS: usize = 16;
+
+        (0..=STEPS)
+            .map(|i| self.u_min + (self.u_max - self.u_min) * i as f32 / STEPS as f32)
+            .min_by(|&u0, &u1| {
+                let distance = |u| {
+                    eval_bezier(&self.common.control_points, u)
+                        .0
+                        .distance_squared(p)
+                };
+                distance(u0).total_cmp(&distance(u1))
+            })
+            .unwrap()
+    }
+
+    fn intersect_curve(&self, ray: Ray) -> Option<CurveHit> {
+        let cp = self.control_points();
+        let d = ray.d.normalize();
+
+        // look at the curve along the ray, with the curve running roughly along x
+        let mut up = d.cross(cp[3] - cp[0]);
+        if up.length_squared() == 0.0 {
+            up = Frame3::coordinate_system(d).0;
+        }
+        let up = up.normalize();
+        let frame = Frame3 {
+            n: d,
+            s: up.cross(d),
+            t: up,
+        };
+        let cp = cp.map(|p| frame.to_local(p - ray.o));
+
+        let max_width = self.width(self.u_min).max(self.width(self.u_max));
+        if !overlaps_ray(&cp, 0.5 * max_width, f32::INFINITY) {
+            return None;
+        }
+
+        // subdivide until the curve is within a fraction of its width of being straight
+        let l0 = (0..2)
+            .map(|i| (cp[i] - 2.0 * cp[i + 1] + cp[i + 2]).abs().max_element())
+            .fold(0.0, f32::max);
+        let eps = self.common.width[0].max(self.common.width[1]) * 0.05;
+        let log2 = (core::f32::consts::SQRT_2 * 6.0 * l0 / (8.0 * eps))
+            .log2()
+            .round();
+        let max_depth = (log2 as i32 / 2).clamp(0, MAX_DEPTH);
+
+        let mut hit = None;
+        self.intersect_recursive(&cp, &frame, self.u_min, self.u_max, max_depth, &mut hit);
+
+        hit
+    }
+
+    /// Intersects the ray along `frame.n` from the origin with the part of the curve from `u0` to `u1`
+    /// whose control points `cp` are in the space of the `frame`. Keeps the closest hit in `hit`.
+    fn intersect_recursive(
+        &self,
+        cp: &[Point3; 4],
+        frame: &Frame3,
+        u0: f32,
+        u1: f32,
+        depth: i32,
+        hit: &mut Option<CurveHit>,
+    ) {
+        if depth > 0 {
+            let split = subdivide_bezier(cp);
+            let u = [u0, 0.5 * (u0 + u1), u1];
+            for segment in 0..2 {
+                let cp = [
+                    split[3 * segment],
+                    split[3 * segment + 1],
+                    split[3 * segment + 2],
+                    split[3 * segment + 3],
+                ];
+                let max_width = self.width(u[segment]).max(self.width(u[segment + 1]));
+                let z_max = hit.as_ref().map_or(f32::INFINITY, |h| h.z);
+                if overlaps_ray(&cp, 0.5 * max_width, z_max) {
+                    self.intersect_recursive(
+                        &cp,
+                        frame,
+                        u[segment],
+                        u[segment + 1],
+                        depth - 1,
+                        hit,
+                    );
+                }
+            }
+            return;
+        }
+
+        // the ray has to pass between the lines perpendicular to the curve at both of its ends
+        let edge = (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x);
+        if edge < 0.0 {
+            return;
+        }
+        let edge = (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x);
+        if edge < 0.0 {
+            return;
+        }
+
+        // the curve is close to straight here, find where along it the ray is closest to it
+        let segment = Vector2::new(cp[3].x - cp[0].x, cp[3].y - cp[0].y);
+        let denominator = segment.length_squared();
+        if denominator == 0.0 {
+            return;
+        }
+        let w = -Vector2::new(cp[0].x, cp[0].y).dot(segment) / denominator;
+
+        let u = (u0 + (u1 - u0) * w).clamp(u0, u1);
+        let ribbon_normal = self.ribbon_normal(u);
+        let mut hit_width = self.width(u);
+        if let Some(n) = ribbon_normal {
+            // ribbons get thinner as they turn away from the ray
+            hit_width *= n.dot(frame.n).abs();
+        }
+
+        let (pc, dpc_dw) = eval_bezier(cp, w.clamp(0.0, 1.0));
+        let distance_squared = pc.x * pc.x + pc.y * pc.y;
+        let z_max = hit.as_ref().map_or(f32::INFINITY, |h| h.z);
+        if distance_squared > hit_width * hit_width * 0.25 || pc.z < 0.0 || pc.z > z_max {
+            return;
+        }
+
+        // v goes across the curve, on the side of dp_dv above a half
+        let distance = distance_squared.sqrt();
+        let edge = dpc_dw.x * -pc.y + pc.x * dpc_dw.y;
+        let v = if edge > 0.0 {
+            0.5 + distance / hit_width
+        } else {
+            0.5 - distance / hit_width
+        };
+
+        let (_, dp_du) = eval_bezier(&self.common.control_points, u);
+        let (n, dp_dv) = if let Some(n) = ribbon_normal {
+            (
+                face_forward(n, -frame.n),
+                n.cross(dp_du).normalize() * hit_width,
+            )
+        } else {
+            let dp_du_plane = frame.to_local(dp_du);
+            let dp_dv_plane = Vector3::new(-dp_du_plane.y, dp_du_plane.x, 0.0).normalize();
+            let dp_dv = frame.to_world(dp_dv_plane);
+            let n = face_forward(dp_du.cross(dp_dv).normalize(), -frame.n);
+
+            let n = if self.common.kind == CurveKind::Cylinder {
+                // bend the normal around the curve like on a cylinder
+                let sin_theta = (2.0 * (v - 0.5)).clamp(-1.0, 1.0);
+                let cos_theta = (1.0 - sin_theta * sin_theta).sqrt();
+                (n * cos_theta + dp_dv * sin_theta).normalize()
+            } else {
+                n
+            };
+
+            (n, dp_dv * hit_width)
+        };
+
+        *hit = Some(CurveHit {
+            z: pc.z,
+            uv: Point2::new(u, v),
+            n,
+            dp_du,
+            dp_dv,
+        });
+    }
+}
+
+/// Whether the bounds of the control points `cp`, padded by `half_width`, overlap the ray along z from the
+/// origin up to `z_max`.
+fn overlaps_ray(cp: &[Point3; 4], half_width: f32, z_max: f32) -> bool {
+    let min = cp[0].min(cp[1]).min(cp[2].min(cp[3])) - half_width;
+    let max = cp[0].max(cp[1]).max(cp[2].max(cp[3])) + half_width;
+
+    min.x <= 0.0 && max.x >= 0.0 && min.y <= 0.0 && max.y >= 0.0 && max.z >= 0.0 && min.z <= z_max
+}
+
+/// The point at `u` on the cubic Bézier curve with the control points `cp` and the derivative there.
+fn eval_bezier(cp: &[Point3; 4], u: f32) -> (Point3, Vector3) {
+    let cp1 = [
+        cp[0].lerp(cp[1], u),
+        cp[1].lerp(cp[2], u),
+        cp[2].lerp(cp[3], u),
+    ];
+    let cp2 = [cp1[0].lerp(cp1[1], u), cp1[1].lerp(cp1[2], u)];
+
+    let derivative = if (cp2[1] - cp2[0]).length_squared() > 0.0 {
+        3.0 * (cp2[1] - cp2[0])
+    } else {
+        // the first two or last two control points are the same at the ends
+        cp[3] - cp[0]
+    };
+
+    (cp2[0].lerp(cp2[1], u), derivative)
+}
+
+/// Splits the cubic Bézier curve at its middle, the two halves share the middle control point.
+fn subdivide_bezier(cp: &[Point3; 4]) -> [Point3; 7] {
+    [
+        cp[0],
+        (cp[0] + cp[1]) / 2.0,
+        (cp[0] + 2.0 * cp[1] + cp[2]) / 4.0,
+        (cp[0] + 3.0 * cp[1] + 3.0 * cp[2] + cp[3]) / 8.0,
+        (cp[1] + 2.0 * cp[2] + cp[3]) / 4.0,
+        (cp[2] + cp[3]) / 2.0,
+        cp[3],
+    ]
+}
+
+/// The blossom of the cubic Bézier curve, `blossom(u, u, u)` is the point at `u` and the blossoms of the
+/// ends of a part are its control points.
+fn blossom_bezier(cp: &[Point3; 4], u0: f32, u1: f32, u2: f32) -> Point3 {
+    let a = [
+        cp[0].lerp(cp[1], u0),
+        cp[1].lerp(cp[2], u0),
+        cp[2].lerp(cp[3], u0),
+    ];
+    let b = [a[0].lerp(a[1], u1), a[1].lerp(a[2], u1)];
+
+    b[0].lerp(b[1], u2)
+}
+
+impl ShapeT for Curve {
+    fn intersect(&self, ray: Ray) -> ShapeIntersection {
+        let t = self
+            .intersect_curve(ray)
+            .map_or(-1.0, |hit| hit.z / ray.d.length());
+
+        ShapeIntersection { t }
+    }
+
+    fn get_surface_interaction(
+        &self,
+        ray: Ray,
+        intersection: ShapeIntersection,
+    ) -> ShapeInteraction {
+        let p = ray.at(intersection.t);
+        let hit = self.intersect_curve(ray).unwrap_or_else(|| {
+            // only happens if the ray barely grazed the curve, use the closest point on it
+            let u = self.closest_u(p);
+            let (_, dp_du) = eval_bezier(&self.common.control_points, u);
+            let (s, _) = Frame3::coordinate_system(dp_du.normalize());
+            CurveHit {
+                z: 0.0,
+                uv: Point2::new(u, 0.5),
+                n: face_forward(dp_du.cross(s).normalize(), -ray.d),
+                dp_du,
+                dp_dv: s * self.width(u),
+            }
+        });
+
+        ShapeInteraction {
+            intersection,
+            p,
+            n: hit.n,
+            uv: hit.uv,
+            dp_du: hit.dp_du,
+            dp_dv: hit.dp_dv,
+        }
+    }
+
+    fn make_bounds(&self) -> Bounds3 {
+        let cp = self.control_points();
+        let max_width = self.width(self.u_min).max(self.width(self.u_max));
+
+        Bounds3::new(cp[0], cp[1])
+            .union(Bounds3::new(cp[2], cp[3]))
+            .pad(0.5 * max_width)
+    }
+
+    fn area(&self) -> f32 {
+        // the width along the length of the part, by the midpoint rule
+        const STEPS: usize = 16;
+        let du = (self.u_max - self.u_min) / STEPS as f32;
+        let area = (0..STEPS)
+            .map(|i| {
+                let u = self.u_min + (i as f32 + 0.5) * du;
+                let (_, dp_du) = eval_bezier(&self.common.control_points, u);
+                dp_du.length() * self.width(u) * du
+            })
+            .sum::<f32>();
+
+        // cylinders are sampled all around, not only on the side facing the ray
+        if self.common.kind == CurveKind::Cylinder {
+            area * core::f32::consts::PI
+        } else {
+            area
+        }
+    }
+
+    /// Samples the part uniformly in `u`, which is only uniform by area where the curve doesn't speed up
+    /// or change width, so lights on curves are approximate.
+    fn sample(&self, u: Point2) -> ShapeSample {
+        // flat curves turn towards every ray, so for them any side will do
+        let curve_u = self.u_min + (self.u_max - self.u_min) * u.x;
+        let (p, dp_du) = eval_bezier(&self.common.control_points, curve_u);
+        let width = self.width(curve_u);
+
+        let (p, n) = if let Some(n) = self.ribbon_normal(curve_u) {
+            (p + n.cross(dp_du).normalize() * width * (u.y - 0.5), n)
+        } else {
+            let (s, t) = Frame3::coordinate_system(dp_du.normalize());
+            if self.common.kind == CurveKind::Cylinder {
+                let (sin_phi, cos_phi) = (core::f32::consts::TAU * u.y).sin_cos();
+                let n = s * cos_phi + t * sin_phi;
+                (p + n * 0.5 * width, n)
+            } else {
+                (p + s * width * (u.y - 0.5), t)
+            }
+        };
+
+        ShapeSample {
+            p,
+            n,
+            uv: Point2::new(curve_u, u.y),
+        }
+    }
+
+    fn normal(&self, p: Point3) -> Normal3 {
+        let u = self.closest_u(p);
+        if let Some(n) = self.ribbon_normal(u) {
+            return n;
+        }
+
+        let (c, dp_du) = eval_bezier(&self.common.control_points, u);
+        let tangent = dp_du.normalize();
+        let (s, t) = Frame3::coordinate_system(tangent);
+        if self.common.kind == CurveKind::Cylinder {
+            let offset = (p - c) - tangent * (p - c).dot(tangent);
+            if offset.length_squared() > 0.0 {
+                return offset.normalize();
+            }
+            return s;
+        }
+
+        t
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A curve through `cp` that isn't split.
+    fn curve(cp: [Point3; 4], width: f32, kind: CurveKind) -> Curve {
+        Curve::split(cp, [width; 2], kind, 0).pop().unwrap()
+    }
+
+    /// A straight curve along x from -1 to 1, with `u` going along it at a constant speed.
+    fn straight(kind: CurveKind) -> Curve {
+        let cp = [-3.0, -1.0, 1.0, 3.0].map(|x| Point3::new(x / 3.0, 0.0, 0.0));
+        curve(cp, 0.2, kind)
+    }
+
+    fn ray(x: f32, y: f32) -> Ray {
+        Ray::new(Point3::new(x, y, 5.0), Vector3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn hits_every_kind() {
+        let kinds = [
+            CurveKind::Flat,
+            CurveKind::Cylinder,
+            CurveKind::Ribbon([Normal3::Z; 2]),
+        ];
+        for kind in kinds {
+            let curve = straight(kind);
+            for x in [-0.8, 0.0, 0.5] {
+                for y in [-0.09, 0.0, 0.09] {
+                    let hit = curve.intersect(ray(x, y));
+                    assert!((hit.t - 5.0).abs() < 1e-3, "{kind:?} missed at {x} {y}");
+
+                    let si = curve.get_surface_interaction(ray(x, y), hit);
+                    // v goes across the curve, from either side
+                    let uv = Point2::new((x + 1.0) / 2.0, 0.5 + y.abs() / 0.2);
+                    let v = 0.5 + (si.uv.y - 0.5).abs();
+                    assert!(
+                        Point2::new(si.uv.x, v).distance(uv) < 1e-2,
+                        "{kind:?} {}",
+                        si.uv
+                    );
+                    assert!(si.n.dot(Vector3::Z) > 0.0);
+                }
+            }
+
+            // beside the curve and past its ends
+            for (x, y) in [(0.0, 0.11), (0.3, -0.12), (1.2, 0.0)] {
+                assert!(
+                    curve.intersect(ray(x, y)).t < 0.0,
+                    "{kind:?} hit at {x} {y}"
+                );
+            }
+        }
+
+        // ribbons seen edge on are too thin to hit
+        let ribbon = straight(CurveKind::Ribbon([Normal3::Y; 2]));
+        assert!(ribbon.intersect(ray(0.0, 0.01)).t < 0.0);
+    }
+
+    #[test]
+    fn bounds_contain_the_curve() {
+        let cp = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 2.0, -1.0),
+            Point3::new(2.0, -1.0, 1.0),
+            Point3::new(3.0, 1.0, 0.5),
+        ];
+        let kinds = [
+            CurveKind::Flat,
+            CurveKind::Cylinder,
+            CurveKind::Ribbon([Normal3::Z, Normal3::X]),
+        ];
+        for kind in kinds {
+            for part in Curve::split(cp, [0.3, 0.1], kind, 2) {
+                let bounds = part.make_bounds();
+                for i in 0..=20 {
+                    for j in 0..=4 {
+                        let u = Point2::new(i as f32 / 20.0, j as f32 / 4.0);
+                        let p = part.sample(u).p;
+                        assert!(
+                            p.cmpge(bounds.min).all() && p.cmple(bounds.max).all(),
+                            "{kind:?}: {p} is outside of {bounds:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn area_follows_the_arc() {
+        let cp = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+        ];
+        let width = 0.1;
+
+        // the length of a fine polyline along the curve
+        let length = (0..1000)
+            .map(|i| {
+                let (a, _) = eval_bezier(&cp, i as f32 / 1000.0);
+                let (b, _) = eval_bezier(&cp, (i + 1) as f32 / 1000.0);
+                a.distance(b)
+            })
+            .sum::<f32>();
+
+        let area = Curve::split(cp, [width; 2], CurveKind::Flat, 1)
+            .iter()
+            .map(|c| c.area())
+            .sum::<f32>();
+        assert!(
+            (area - length * width).abs() < 1e-3 * area,
+            "{area} != {}",
+            length * width
+        );
+    }
+}
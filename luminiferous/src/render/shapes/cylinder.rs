@@ -0,0 +1,181 @@
+use crate::prelude::*;
+
+use super::{
+    axial_scale_and_shift, azimuth, solve_quadratic, ShapeInteraction, ShapeIntersection,
+    ShapeSample, ShapeT,
+};
+
+/// An open cylinder around the z axis from `z_min` to `z_max`, cut off at the angle `phi_max` around the
+/// axis.
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+    radius: f32,
+    z_min: f32,
+    z_max: f32,
+    phi_max: f32,
+}
+
+impl Cylinder {
+    pub fn new(radius: f32, z_min: f32, z_max: f32, phi_max: f32) -> Self {
+        STATS.shapes_created.inc();
+
+        Self {
+            radius,
+            z_min: z_min.min(z_max),
+            z_max: z_min.max(z_max),
+            phi_max: phi_max.clamp(0.0, core::f32::consts::TAU),
+        }
+    }
+
+    /// Whether the point `p` on the infinite cylinder is on the part that is kept.
+    fn contains(&self, p: Point3) -> bool {
+        (self.z_min..=self.z_max).contains(&p.z) && azimuth(p) <= self.phi_max
+    }
+}
+
+impl ShapeT for Cylinder {
+    fn intersect(&self, ray: Ray) -> ShapeIntersection {
+        let a = ray.d.x * ray.d.x + ray.d.y * ray.d.y;
+        let b = 2.0 * (ray.d.x * ray.o.x + ray.d.y * ray.o.y);
+        let c = ray.o.x * ray.o.x + ray.o.y * ray.o.y - self.radius * self.radius;
+
+        // the nearest of the two hits that isn't cut off
+        let t = solve_quadratic(a, b, c)
+            .and_then(|(t0, t1)| {
+                [t0, t1]
+                    .into_iter()
+                    .find(|&t| t > 0.0 && self.contains(ray.at(t)))
+            })
+            .unwrap_or(-1.0);
+
+        ShapeIntersection { t }
+    }
+
+    fn get_surface_interaction(
+        &self,
+        ray: Ray,
+        intersection: ShapeIntersection,
+    ) -> ShapeInteraction {
+        let mut p = ray.at(intersection.t);
+        // move the point back onto the surface
+        let scale = self.radius / (p.x * p.x + p.y * p.y).sqrt();
+        p.x *= scale;
+        p.y *= scale;
+
+        ShapeInteraction {
+            intersection,
+            p,
+            n: self.normal(p),
+            uv: Point2::new(
+                azimuth(p) / self.phi_max,
+                (p.z - self.z_min) / (self.z_max - self.z_min),
+            ),
+            dp_du: Vector3::new(-self.phi_max * p.y, self.phi_max * p.x, 0.0),
+            dp_dv: Vector3::new(0.0, 0.0, self.z_max - self.z_min),
+        }
+    }
+
+    fn make_bounds(&self) -> Bounds3 {
+        Bounds3::new(
+            Point3::new(-self.radius, -self.radius, self.z_min),
+            Point3::new(self.radius, self.radius, self.z_max),
+        )
+        .pad(1e-5) // deal with the case that the bounds have zero volume
+    }
+
+    fn area(&self) -> f32 {
+        (self.z_max - self.z_min) * self.radius * self.phi_max
+    }
+
+    fn sample(&self, u: Point2) -> ShapeSample {
+        let z = self.z_min + u.x * (self.z_max - self.z_min);
+        let (sin_phi, cos_phi) = (u.y * self.phi_max).sin_cos();
+        let p = Point3::new(self.radius * cos_phi, self.radius * sin_phi, z);
+
+        ShapeSample {
+            p,
+            n: self.normal(p),
+            uv: Point2::new(u.y, u.x),
+        }
+    }
+
+    fn normal(&self, p: Point3) -> Normal3 {
+        Normal3::new(p.x, p.y, 0.0).normalize()
+    }
+
+    fn transform(&mut self, transform: &Transform3) -> bool {
+        let Some((scale, shift)) = axial_scale_and_shift(transform) else {
+            return false;
+        };
+
+        self.radius *= scale;
+        self.z_min = self.z_min * scale + shift;
+        self.z_max = self.z_max * scale + shift;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::f32::consts::{PI, TAU};
+
+    use super::*;
+
+    #[test]
+    fn intersects_from_outside_and_inside() {
+        let cylinder = Cylinder::new(1.0, -1.0, 1.0, TAU);
+
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 0.5), Vector3::X);
+        let intersection = cylinder.intersect(ray);
+        assert!((intersection.t - 4.0).abs() < 1e-5);
+        let interaction = cylinder.get_surface_interaction(ray, intersection);
+        assert!(interaction.n.abs_diff_eq(Normal3::NEG_X, 1e-5));
+        assert!(interaction.uv.abs_diff_eq(Point2::new(0.5, 0.75), 1e-5));
+
+        let inside = Ray::new(Point3::ZERO, Vector3::Y);
+        assert!((cylinder.intersect(inside).t - 1.0).abs() < 1e-5);
+
+        let above = Ray::new(Point3::new(-5.0, 0.0, 2.0), Vector3::X);
+        assert!(cylinder.intersect(above).t < 0.0);
+    }
+
+    #[test]
+    fn cuts_off_at_phi_max() {
+        let cylinder = Cylinder::new(1.0, -1.0, 1.0, PI);
+
+        // the near side is cut off, so the ray goes through to the far one
+        let ray = Ray::new(Point3::new(0.0, -5.0, 0.0), Vector3::Y);
+        assert!((cylinder.intersect(ray).t - 6.0).abs() < 1e-5);
+        assert!((cylinder.area() - 2.0 * PI).abs() < 1e-5);
+    }
+
+    #[test]
+    fn samples_lie_on_the_cylinder() {
+        let cylinder = Cylinder::new(2.0, -1.0, 1.0, TAU);
+
+        for u in [Point2::new(0.1, 0.2), Point2::new(0.9, 0.7)] {
+            let sample = cylinder.sample(u);
+            assert!((sample.p.truncate().length() - 2.0).abs() < 1e-5);
+            assert!((-1.0..=1.0).contains(&sample.p.z));
+
+            let ray = Ray::new(sample.p + sample.n * 0.5, -sample.n);
+            assert!((cylinder.intersect(ray).t - 0.5).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn transforms_along_the_axis() {
+        let mut cylinder = Cylinder::new(1.0, 0.0, 1.0, TAU);
+        let transform = Transform3::translate(Vector3::new(0.0, 0.0, -1.0))
+            * Transform3::scale(Vector3::splat(2.0));
+        assert!(cylinder.transform(&transform));
+
+        let ray = Ray::new(Point3::new(-5.0, 0.0, -0.5), Vector3::X);
+        assert!((cylinder.intersect(ray).t - 3.0).abs() < 1e-5);
+        assert!((cylinder.area() - 8.0 * PI).abs() < 1e-4);
+
+        let shift = Transform3::translate(Vector3::new(1.0, 0.0, 0.0));
+        assert!(!cylinder.transform(&shift));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::prelude::*;
+
+use super::{
+    axial_scale_and_shift, azimuth, ShapeInteraction, ShapeIntersection, ShapeSample, ShapeT,
+};
+
+/// A disk around the z axis at `height`, facing `+z`. With an inner radius it is an annulus, and it is cut
+/// off at the angle `phi_max` around the axis.
+#[derive(Debug, Clone)]
+pub struct Disk {
+    height: f32,
+    radius: f32,
+    inner_radius: f32,
+    phi_max: f32,
+}
+
+impl Disk {
+    pub fn new(height: f32, radius: f32, inner_radius: f32, phi_max: f32) -> Self {
+        STATS.shapes_created.inc();
+
+        Self {
+            height,
+            radius,
+            inner_radius,
+            phi_max: phi_max.clamp(0.0, core::f32::consts::TAU),
+        }
+    }
+}
+
+impl ShapeT for Disk {
+    fn intersect(&self, ray: Ray) -> ShapeIntersection {
+        if ray.d.z == 0.0 {
+            return ShapeIntersection { t: -1.0 };
+        }
+
+        let t = (self.height - ray.o.z) / ray.d.z;
+        let p = ray.at(t);
+        let distance_squared = p.x * p.x + p.y * p.y;
+
+        ShapeIntersection {
+            t: if distance_squared <= self.radius * self.radius
+                && distance_squared >= self.inner_radius * self.inner_radius
+                && azimuth(p) <= self.phi_max
+            {
+                t
+            } else {
+                -1.0
+            },
+        }
+    }
+
+    fn get_surface_interaction(
+        &self,
+        ray: Ray,
+        intersection: ShapeIntersection,
+    ) -> ShapeInteraction {
+        let mut p = ray.at(intersection.t);
+        p.z = self.height;
+        let r = (p.x * p.x + p.y * p.y).sqrt();
+
+        ShapeInteraction {
+            intersection,
+            p,
+            n: Normal3::Z,
+            uv: Point2::new(
+                azimuth(p) / self.phi_max,
+                (self.radius - r) / (self.radius - self.inner_radius),
+            ),
+            dp_du: Vector3::new(-self.phi_max * p.y, self.phi_max * p.x, 0.0),
+            dp_dv: Vector3::new(p.x, p.y, 0.0) * (self.inner_radius - self.radius) / r.max(1e-7),
+        }
+    }
+
+    fn make_bounds(&self) -> Bounds3 {
+        Bounds3::new(
+            Point3::new(-self.radius, -self.radius, self.height),
+            Point3::new(self.radius, self.radius, self.height),
+        )
+        .pad(1e-5) // deal with the case that the bounds have zero volume
+    }
+
+    fn area(&self) -> f32 {
+        self.phi_max * 0.5 * (self.radius * self.radius - self.inner_radius * self.inner_radius)
+    }
+
+    fn sample(&self, u: Point2) -> ShapeSample {
+        // uniform in area, the area inside a radius grows with its square
+        let inner_squared = self.inner_radius * self.inner_radius;
+        let r = (inner_squared + u.x * (self.radius * self.radius - inner_squared)).sqrt();
+        let (sin_phi, cos_phi) = (u.y * self.phi_max).sin_cos();
+
+        ShapeSample {
+            p: Point3::new(r * cos_phi, r * sin_phi, self.height),
+            n: Normal3::Z,
+            uv: Point2::new(u.y, (self.radius - r) / (self.radius - self.inner_radius)),
+        }
+    }
+
+    fn normal(&self, _p: Point3) -> Normal3 {
+        Normal3::Z
+    }
+
+    fn transform(&mut self, transform: &Transform3) -> bool {
+        let Some((scale, shift)) = axial_scale_and_shift(transform) else {
+            return false;
+        };
+
+        self.height = self.height * scale + shift;
+        self.radius *= scale;
+        self.inner_radius *= scale;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::f32::consts::{PI, TAU};
+
+    use super::*;
+
+    fn down(x: f32, y: f32) -> Ray {
+        Ray::new(Point3::new(x, y, 5.0), Vector3::NEG_Z)
+    }
+
+    #[test]
+    fn intersects_the_annulus() {
+        let disk = Disk::new(1.0, 1.0, 0.5, TAU);
+
+        let intersection = disk.intersect(down(0.0, 0.75));
+        assert!((intersection.t - 4.0).abs() < 1e-5);
+        let interaction = disk.get_surface_interaction(down(0.0, 0.75), intersection);
+        assert!(interaction.uv.abs_diff_eq(Point2::new(0.25, 0.5), 1e-5));
+
+        assert!(disk.intersect(down(0.25, 0.0)).t < 0.0);
+        assert!(disk.intersect(down(1.25, 0.0)).t < 0.0);
+    }
+
+    #[test]
+    fn cuts_off_at_phi_max() {
+        let disk = Disk::new(0.0, 1.0, 0.0, PI);
+
+        assert!(disk.intersect(down(0.0, 0.5)).t > 0.0);
+        assert!(disk.intersect(down(0.0, -0.5)).t < 0.0);
+        assert!((disk.area() - PI / 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn samples_lie_on_the_disk() {
+        let disk = Disk::new(1.0, 1.0, 0.5, PI);
+
+        for u in [Point2::new(0.1, 0.2), Point2::new(0.9, 0.7)] {
+            let sample = disk.sample(u);
+            let r = sample.p.truncate().length();
+            assert!((sample.p.z - 1.0).abs() < 1e-5);
+            assert!((0.5..=1.0).contains(&r));
+            assert!(disk.intersect(down(sample.p.x, sample.p.y)).t > 0.0);
+        }
+    }
+
+    #[test]
+    fn transforms_along_the_axis() {
+        let mut disk = Disk::new(1.0, 1.0, 0.0, TAU);
+        let transform = Transform3::translate(Vector3::new(0.0, 0.0, 1.0))
+            * Transform3::scale(Vector3::splat(2.0));
+        assert!(disk.transform(&transform));
+
+        assert!((disk.intersect(down(1.5, 0.0)).t - 2.0).abs() < 1e-5);
+        assert!((disk.area() - 4.0 * PI).abs() < 1e-4);
+
+        let rotation = Transform3::rotate(Point3::new(1.0, 0.0, 0.0));
+        assert!(!disk.transform(&rotation));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::prelude::*;
+
+use super::{ShapeInteraction, ShapeIntersection, ShapeSample, ShapeT};
+
+/// A parallelogram with a corner at `p` and the sides `u` and `v`, facing `u x v`.
+#[derive(Debug, Clone)]
+pub struct Quad {
+    p: Point3,
+    u: Vector3,
+    v: Vector3,
+}
+
+impl Quad {
+    pub fn new(p: Point3, u: Vector3, v: Vector3) -> Self {
+        STATS.shapes_created.inc();
+
+        Self { p, u, v }
+    }
+
+    /// The quad in the xy plane from `(-1, -1)` to `(1, 1)`, facing `+z`.
+    pub fn unit() -> Self {
+        Self::new(
+            Point3::new(-1.0, -1.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+        )
+    }
+
+    /// The coordinates of `p` along the sides.
+    fn uv_at(&self, p: Point3) -> Point2 {
+        let n = self.u.cross(self.v);
+        let offset = p - self.p;
+
+        Point2::new(
+            offset.cross(self.v).dot(n) / n.length_squared(),
+            self.u.cross(offset).dot(n) / n.length_squared(),
+        )
+    }
+}
+
+impl ShapeT for Quad {
+    fn intersect(&self, ray: Ray) -> ShapeIntersection {
+        let n = self.u.cross(self.v);
+        let d_n = ray.d.dot(n);
+        if d_n == 0.0 {
+            return ShapeIntersection { t: -1.0 };
+        }
+
+        let t = (self.p - ray.o).dot(n) / d_n;
+        let offset = ray.at(t) - self.p;
+        let a = offset.cross(self.v).dot(n) / n.length_squared();
+        let b = self.u.cross(offset).dot(n) / n.length_squared();
+
+        ShapeIntersection {
+            t: if (0.0..=1.0).contains(&a) && (0.0..=1.0).contains(&b) {
+                t
+            } else {
+                -1.0
+            },
+        }
+    }
+
+    fn get_surface_interaction(
+        &self,
+        ray: Ray,
+        intersection: ShapeIntersection,
+    ) -> ShapeInteraction {
+        let p = ray.at(intersection.t);
+
+        ShapeInteraction {
+            intersection,
+            p,
+            n: self.u.cross(self.v).normalize(),
+            uv: self.uv_at(p),
+            dp_du: self.u,
+            dp_dv: self.v,
+        }
+    }
+
+    fn uv(&self, ray: Ray, intersection: ShapeIntersection) -> Point2 {
+        self.uv_at(ray.at(intersection.t))
+    }
+
+    fn make_bounds(&self) -> Bounds3 {
+        Bounds3::new(self.p, self.p + self.u)
+            .expand(self.p + self.v)
+            .expand(self.p + self.u + self.v)
+            .pad(1e-5) // deal with the case that the bounds have zero volume
+    }
+
+    fn area(&self) -> f32 {
+        self.u.cross(self.v).length()
+    }
+
+    fn sample(&self, u: Point2) -> ShapeSample {
+        ShapeSample {
+            p: self.p + u.x * self.u + u.y * self.v,
+            n: self.u.cross(self.v).normalize(),
+            uv: u,
+        }
+    }
+
+    fn normal(&self, _p: Point3) -> Normal3 {
+        self.u.cross(self.v).normalize()
+    }
+
+    fn transform(&mut self, transform: &Transform3) -> bool {
+        self.p = transform.transform_point(self.p);
+        self.u = transform.transform_vector(self.u);
+        self.v = transform.transform_vector(self.v);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn down(x: f32, y: f32) -> Ray {
+        Ray::new(Point3::new(x, y, 1.0), Vector3::NEG_Z)
+    }
+
+    #[test]
+    fn intersects_inside_the_sides() {
+        let quad = Quad::unit();
+
+        let intersection = quad.intersect(down(0.5, 0.5));
+        assert!((intersection.t - 1.0).abs() < 1e-5);
+        let interaction = quad.get_surface_interaction(down(0.5, 0.5), intersection);
+        assert!(interaction.uv.abs_diff_eq(Point2::new(0.75, 0.75), 1e-5));
+        assert!(interaction.n.abs_diff_eq(Normal3::Z, 1e-5));
+
+        assert!(quad.intersect(down(1.5, 0.0)).t < 0.0);
+        assert!(quad.intersect(down(0.0, -1.5)).t < 0.0);
+    }
+
+    #[test]
+    fn samples_lie_on_the_quad() {
+        let quad = Quad::unit();
+        assert!((quad.area() - 4.0).abs() < 1e-5);
+
+        for u in [Point2::new(0.1, 0.2), Point2::new(0.9, 0.5)] {
+            let sample = quad.sample(u);
+            assert!(sample.p.z.abs() < 1e-5);
+            assert!(sample.uv.abs_diff_eq(u, 1e-5));
+        }
+    }
+
+    #[test]
+    fn transforms() {
+        let mut quad = Quad::unit();
+        let transform = Transform3::translate(Vector3::new(3.0, 0.0, 0.0))
+            * Transform3::scale(Vector3::new(2.0, 1.0, 1.0));
+        assert!(quad.transform(&transform));
+
+        assert!((quad.area() - 8.0).abs() < 1e-5);
+        assert!(quad.intersect(down(4.5, 0.5)).t > 0.0);
+        assert!(quad.intersect(down(0.5, 0.5)).t < 0.0);
+    }
+}
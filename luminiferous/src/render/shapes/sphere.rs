@@ -13,6 +13,18 @@ impl Sphere {
 
         Self { radius }
     }
+
+    /// The squared sine and the cosine of the half angle of the cone the sphere covers as seen from `p`,
+    /// `None` from inside the sphere.
+    fn cone(&self, p: Point3) -> Option<(f32, f32)> {
+        let distance_squared = p.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return None;
+        }
+
+        let sin2_theta_max = self.radius * self.radius / distance_squared;
+        Some((sin2_theta_max, (1.0 - sin2_theta_max).max(0.0).sqrt()))
+    }
 }
 
 impl ShapeT for Sphere {
@@ -79,6 +91,68 @@ impl ShapeT for Sphere {
         let p = self.radius * s;
         let n = s;
 
-        ShapeSample { p, n }
+        ShapeSample {
+            p,
+            n,
+            uv: Point2::new(n.x.atan2(n.z) / (core::f32::consts::PI * 2.0) + 0.5, n.y * 0.5 + 0.5),
+        }
+    }
+
+    fn sample_solid_angle(&self, p: Point3, u: Point2) -> Option<(ShapeSample, f32)> {
+        // samples the cone of directions towards the sphere, from inside it the whole sphere is visible
+        let (sin2_theta_max, cos_theta_max) = self.cone(p)?;
+
+        let (sin2_theta, cos_theta, one_minus_cos_theta_max) = if sin2_theta_max < 0.00068523 {
+            // the cone is too narrow for the cosines to be precise, use the taylor expansion
+            let sin2_theta = sin2_theta_max * u.x;
+            (sin2_theta, (1.0 - sin2_theta).sqrt(), sin2_theta_max / 2.0)
+        } else {
+            let cos_theta = (cos_theta_max - 1.0) * u.x + 1.0;
+            (1.0 - cos_theta * cos_theta, cos_theta, 1.0 - cos_theta_max)
+        };
+
+        // find the angle from the centre of the sphere to the point that is seen along the sampled direction
+        let cos_alpha = sin2_theta / sin2_theta_max.sqrt()
+            + cos_theta * (1.0 - sin2_theta / sin2_theta_max).max(0.0).sqrt();
+        let sin_alpha = (1.0 - cos_alpha * cos_alpha).max(0.0).sqrt();
+        let (sin_phi, cos_phi) = (u.y * core::f32::consts::TAU).sin_cos();
+
+        let frame = Frame3::new(-p.normalize());
+        let n = frame.to_world(-Vector3::new(
+            sin_alpha * cos_phi,
+            sin_alpha * sin_phi,
+            cos_alpha,
+        ));
+
+        Some((
+            ShapeSample {
+                p: self.radius * n,
+                n,
+                uv: Point2::new(
+                    n.x.atan2(n.z) / (core::f32::consts::PI * 2.0) + 0.5,
+                    n.y * 0.5 + 0.5,
+                ),
+            },
+            1.0 / (core::f32::consts::TAU * one_minus_cos_theta_max),
+        ))
+    }
+
+    fn pdf_solid_angle(&self, p: Point3, wi: Vector3) -> Option<f32> {
+        let (sin2_theta_max, cos_theta_max) = self.cone(p)?;
+        let one_minus_cos_theta_max = if sin2_theta_max < 0.00068523 {
+            sin2_theta_max / 2.0
+        } else {
+            1.0 - cos_theta_max
+        };
+
+        Some(if wi.normalize().dot(-p.normalize()) >= cos_theta_max {
+            1.0 / (core::f32::consts::TAU * one_minus_cos_theta_max)
+        } else {
+            0.0
+        })
+    }
+
+    fn normal(&self, p: Point3) -> Normal3 {
+        p.normalize()
     }
 }
@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+
+use crate::prelude::*;
+use crate::textures::{Texture, TextureT};
+
+use super::TriangleMesh;
+
+/// How a [`SubdivisionMesh`] is refined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubdivisionScheme {
+    /// Loop subdivision, for control meshes made of triangles. Other polygons are split into triangles
+    /// first.
+    Loop,
+    /// Catmull-Clark subdivision, for control meshes made of quads. Any polygon works, after the first
+    /// level everything is a quad.
+    CatmullClark,
+}
+
+/// A control mesh of polygons that is subdivided into a smooth [`TriangleMesh`] when it is loaded, and
+/// optionally displaced along its normals.
+#[derive(Debug, Clone)]
+pub struct SubdivisionMesh {
+    positions: Vec<Point3>,
+    /// Either empty or one for every position, they are interpolated linearly.
+    uvs: Vec<Point2>,
+    /// The indices of the vertices of every polygon.
+    faces: Vec<Vec<u32>>,
+    scheme: SubdivisionScheme,
+    levels: u32,
+    /// The texture and scale of the distance every vertex is moved along its normal.
+    displacement: Option<(Texture<f32>, f32)>,
+}
+
+/// An edge between two vertices, with the faces on either side, or the vertices opposite of it for
+/// triangles.
+struct Edge {
+    v: [u32; 2],
+    neighbours: Vec<u32>,
+}
+
+/// Finds all the edges of `faces` and the indices of the edges of every face, in the order of its vertices.
+/// The neighbours of an edge are found by `neighbour(face index, vertex index in the face)`.
+fn find_edges(
+    faces: &[Vec<u32>],
+    neighbour: impl Fn(usize, usize) -> u32,
+) -> (Vec<Edge>, Vec<Vec<usize>>) {
+    let mut edges = Vec::<Edge>::new();
+    let mut edge_indices = HashMap::new();
+
+    let face_edges = faces
+        .iter()
+        .enumerate()
+        .map(|(f, face)| {
+            (0..face.len())
+                .map(|i| {
+                    let (a, b) = (face[i], face[(i + 1) % face.len()]);
+                    let index = *edge_indices.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                        edges.push(Edge {
+                            v: [a, b],
+                            neighbours: vec![],
+                        });
+                        edges.len() - 1
+                    });
+                    edges[index].neighbours.push(neighbour(f, i));
+                    index
+                })
+                .collect()
+        })
+        .collect();
+
+    (edges, face_edges)
+}
+
+/// The new position of a vertex on the boundary, `None` if it isn't on one.
+fn boundary_vertex(
+    positions: &[Point3],
+    edges: &[Edge],
+    incident: &[usize],
+    v: u32,
+) -> Option<Point3> {
+    let boundary = incident
+        .iter()
+        .filter(|&&e| edges[e].neighbours.len() == 1)
+        .map(|&e| edges[e].v[0] + edges[e].v[1] - v)
+        .collect::<Vec<_>>();
+
+    match boundary.len() {
+        0 => None,
+        // the boundary is a curve of its own that is smoothed the same way
+        2 => Some(
+            0.75 * positions[v as usize]
+                + 0.125 * (positions[boundary[0] as usize] + positions[boundary[1] as usize]),
+        ),
+        // corners and non manifold vertices stay where they are
+        _ => Some(positions[v as usize]),
+    }
+}
+
+/// The edges that touch every vertex.
+fn incident_edges(vertex_count: usize, edges: &[Edge]) -> Vec<Vec<usize>> {
+    let mut incident = vec![vec![]; vertex_count];
+    for (i, edge) in edges.iter().enumerate() {
+        incident[edge.v[0] as usize].push(i);
+        incident[edge.v[1] as usize].push(i);
+    }
+    incident
+}
+
+impl SubdivisionMesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        uvs: Vec<Point2>,
+        faces: Vec<Vec<u32>>,
+        scheme: SubdivisionScheme,
+    ) -> Self {
+        assert!(uvs.is_empty() || uvs.len() == positions.len());
+        assert!(faces
+            .iter()
+            .flatten()
+            .all(|&i| (i as usize) < positions.len()));
+
+        let faces = match scheme {
+            SubdivisionScheme::Loop => faces
+                .iter()
+                .flat_map(|face| {
+                    (1..face.len().saturating_sub(1)).map(|i| vec![face[0], face[i], face[i + 1]])
+                })
+                .collect(),
+            SubdivisionScheme::CatmullClark => faces.into_iter().filter(|f| f.len() >= 3).collect(),
+        };
+
+        Self {
+            positions,
+            uvs,
+            faces,
+            scheme,
+            levels: 3,
+            displacement: None,
+        }
+    }
+
+    /// How many times the control mesh is subdivided, every level splits every face into four.
+    pub fn with_levels(mut self, levels: u32) -> Self {
+        self.levels = levels;
+        self
+    }
+
+    /// Moves the subdivided vertices along their normals by `scale` times the `texture` at their uvs.
+    pub fn with_displacement(mut self, texture: Texture<f32>, scale: f32) -> Self {
+        self.displacement = Some((texture, scale));
+        self
+    }
+
+    /// Subdivides and displaces the mesh into triangles.
+    pub fn build(mut self) -> TriangleMesh {
+        for _ in 0..self.levels {
+            match self.scheme {
+                SubdivisionScheme::Loop => self.loop_level(),
+                SubdivisionScheme::CatmullClark => self.catmull_clark_level(),
+            }
+        }
+
+        let indices = self
+            .faces
+            .iter()
+            .flat_map(|face| (1..face.len() - 1).flat_map(|i| [face[0], face[i], face[i + 1]]))
+            .collect::<Vec<_>>();
+
+        let mut normals = vertex_normals(&self.positions, &indices);
+        if let Some((texture, scale)) = &self.displacement {
+            if self.uvs.is_empty() {
+                warnln!(
+                    "displacing a subdivision mesh without uvs, every vertex is moved the same."
+                );
+            }
+
+            for (i, (p, n)) in self.positions.iter_mut().zip(&normals).enumerate() {
+                let uv = self.uvs.get(i).copied().unwrap_or(Point2::ZERO);
+                *p += *n * *scale * texture.eval_uv(uv);
+            }
+            normals = vertex_normals(&self.positions, &indices);
+        }
+
+        TriangleMesh::new(self.positions, normals, self.uvs, indices)
+    }
+
+    fn loop_level(&mut self) {
+        let (edges, face_edges) = find_edges(&self.faces, |f, i| self.faces[f][(i + 2) % 3]);
+        let incident = incident_edges(self.positions.len(), &edges);
+        let p = |v: u32| self.positions[v as usize];
+
+        // the old vertices are kept in place and moved towards their neighbours
+        let mut positions = (0..self.positions.len() as u32)
+            .map(|v| {
+                let incident = &incident[v as usize];
+                if let Some(p) = boundary_vertex(&self.positions, &edges, incident, v) {
+                    return p;
+                }
+                if incident.is_empty() {
+                    return p(v);
+                }
+
+                let n = incident.len() as f32;
+                let beta = if incident.len() == 3 {
+                    3.0 / 16.0
+                } else {
+                    3.0 / (8.0 * n)
+                };
+                let neighbours = incident
+                    .iter()
+                    .map(|&e| p(edges[e].v[0] + edges[e].v[1] - v))
+                    .sum::<Point3>();
+
+                (1.0 - n * beta) * p(v) + beta * neighbours
+            })
+            .collect::<Vec<_>>();
+
+        // followed by a new vertex on every edge
+        positions.extend(edges.iter().map(|e| {
+            let [a, b] = e.v;
+            if let [c, d] = e.neighbours[..] {
+                0.375 * (p(a) + p(b)) + 0.125 * (p(c) + p(d))
+            } else {
+                0.5 * (p(a) + p(b))
+            }
+        }));
+
+        if !self.uvs.is_empty() {
+            let uv = |v: u32| self.uvs[v as usize];
+            let edge_uvs = edges.iter().map(|e| 0.5 * (uv(e.v[0]) + uv(e.v[1])));
+            self.uvs = self.uvs.iter().copied().chain(edge_uvs).collect();
+        }
+
+        let edge_vertex = |e: usize| (self.positions.len() + e) as u32;
+        self.faces = self
+            .faces
+            .iter()
+            .zip(face_edges)
+            .flat_map(|(face, e)| {
+                let [ab, bc, ca] = [e[0], e[1], e[2]].map(edge_vertex);
+                [
+                    vec![face[0], ab, ca],
+                    vec![ab, face[1], bc],
+                    vec![ca, bc, face[2]],
+                    vec![ab, bc, ca],
+                ]
+            })
+            .collect();
+        self.positions = positions;
+    }
+
+    fn catmull_clark_level(&mut self) {
+        let (edges, face_edges) = find_edges(&self.faces, |f, _| f as u32);
+        let incident = incident_edges(self.positions.len(), &edges);
+        let p = |v: u32| self.positions[v as usize];
+
+        let face_points = self
+            .faces
+            .iter()
+            .map(|face| face.iter().map(|&v| p(v)).sum::<Point3>() / face.len() as f32)
+            .collect::<Vec<_>>();
+
+        // the old vertices move towards the faces and edges around them
+        let mut positions = (0..self.positions.len() as u32)
+            .map(|v| {
+                let incident = &incident[v as usize];
+                if let Some(p) = boundary_vertex(&self.positions, &edges, incident, v) {
+                    return p;
+                }
+                if incident.is_empty() {
+                    return p(v);
+                }
+
+                // every face around an interior vertex is next to two of its edges
+                let n = incident.len() as f32;
+                let faces = incident
+                    .iter()
+                    .flat_map(|&e| &edges[e].neighbours)
+                    .map(|&f| face_points[f as usize])
+                    .sum::<Point3>()
+                    / (2.0 * n);
+                let edge_midpoints = incident
+                    .iter()
+                    .map(|&e| 0.5 * (p(edges[e].v[0]) + p(edges[e].v[1])))
+                    .sum::<Point3>()
+                    / n;
+
+                (faces + 2.0 * edge_midpoints + (n - 3.0) * p(v)) / n
+            })
+            .collect::<Vec<_>>();
+
+        // followed by a new vertex on every edge and one in every face
+        positions.extend(edges.iter().map(|e| {
+            let [a, b] = e.v;
+            if let [f1, f2] = e.neighbours[..] {
+                0.25 * (p(a) + p(b) + face_points[f1 as usize] + face_points[f2 as usize])
+            } else {
+                0.5 * (p(a) + p(b))
+            }
+        }));
+        positions.extend(face_points);
+
+        if !self.uvs.is_empty() {
+            let uv = |v: u32| self.uvs[v as usize];
+            let edge_uvs = edges.iter().map(|e| 0.5 * (uv(e.v[0]) + uv(e.v[1])));
+            let face_uvs = self
+                .faces
+                .iter()
+                .map(|face| face.iter().map(|&v| uv(v)).sum::<Point2>() / face.len() as f32);
+            self.uvs = self
+                .uvs
+                .iter()
+                .copied()
+                .chain(edge_uvs)
+                .chain(face_uvs)
+                .collect();
+        }
+
+        let edge_vertex = |e: usize| (self.positions.len() + e) as u32;
+        let face_vertex = |f: usize| (self.positions.len() + edges.len() + f) as u32;
+        self.faces = self
+            .faces
+            .iter()
+            .zip(face_edges)
+            .enumerate()
+            .flat_map(|(f, (face, e))| {
+                // a quad for every vertex of the face
+                (0..face.len()).map(move |i| {
+                    let previous = (i + face.len() - 1) % face.len();
+                    vec![
+                        face[i],
+                        edge_vertex(e[i]),
+                        face_vertex(f),
+                        edge_vertex(e[previous]),
+                    ]
+                })
+            })
+            .collect();
+        self.positions = positions;
+    }
+}
+
+/// The normals of the vertices, averaged from the faces around them weighted by their area.
+fn vertex_normals(positions: &[Point3], indices: &[u32]) -> Vec<Normal3> {
+    let mut normals = vec![Normal3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+        let n = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        normals[a] += n;
+        normals[b] += n;
+        normals[c] += n;
+    }
+
+    normals
+        .into_iter()
+        .map(|n| n.try_normalize().unwrap_or(Normal3::Z))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The cube from `-1` to `1` as six quads facing outwards.
+    fn cube() -> SubdivisionMesh {
+        let positions = (0..8)
+            .map(|i| Bounds3::new(Point3::splat(-1.0), Point3::ONE).corner(i))
+            .collect();
+        let faces = vec![
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+        ];
+
+        SubdivisionMesh::new(positions, vec![], faces, SubdivisionScheme::CatmullClark)
+    }
+
+    #[test]
+    fn catmull_clark_cube() {
+        let mut mesh = cube();
+        mesh.catmull_clark_level();
+
+        // 8 corners, 12 edges and 6 faces, every face split into four
+        assert_eq!(mesh.positions.len(), 26);
+        assert_eq!(mesh.faces.len(), 24);
+        // the corners move to 5/9 of the way out, (F + 2R + (n - 3)P) / n
+        for p in &mesh.positions[..8] {
+            assert!(
+                (p.abs() - Vector3::splat(5.0 / 9.0)).abs().max_element() < 1e-6,
+                "{p}"
+            );
+        }
+
+        // the surface shrinks towards its limit, which stays inside of the cage
+        let mesh = cube().with_levels(4).build();
+        for i in 0..mesh.triangle_count() as u32 {
+            for p in mesh.positions(i) {
+                assert!(p.abs().max_element() <= 1.0, "{p} is outside of the cage");
+                assert!(p.length() > 0.5, "{p} shrank too far");
+            }
+        }
+    }
+
+    #[test]
+    fn loop_boundary() {
+        let positions = vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(1.0, 1.0, 1.0),
+        ];
+        let mut mesh = SubdivisionMesh::new(
+            positions.clone(),
+            vec![],
+            vec![vec![0, 1, 3, 2]],
+            SubdivisionScheme::Loop,
+        );
+        mesh.loop_level();
+
+        // every vertex is on the boundary, they follow 3/4 of themselves and 1/8 of their neighbours on it
+        let boundary = |v: usize, a: usize, b: usize| {
+            0.75 * positions[v] + 0.125 * (positions[a] + positions[b])
+        };
+        let expected = [
+            boundary(0, 1, 2),
+            boundary(1, 0, 3),
+            boundary(2, 0, 3),
+            boundary(3, 1, 2),
+        ];
+        for (p, expected) in mesh.positions.iter().zip(expected) {
+            assert!(p.distance(expected) < 1e-6, "{p} != {expected}");
+        }
+
+        // the new vertices are at the middle of boundary edges, interior edges are pulled to the sides
+        let interior =
+            0.375 * (positions[0] + positions[3]) + 0.125 * (positions[1] + positions[2]);
+        assert!(mesh.positions.iter().any(|p| p.distance(interior) < 1e-6));
+        assert!(mesh
+            .positions
+            .iter()
+            .any(|p| p.distance(0.5 * (positions[0] + positions[1])) < 1e-6));
+        assert_eq!(mesh.faces.len(), 8);
+    }
+}
@@ -1,29 +1,202 @@
+use std::sync::Arc;
+
 use crate::prelude::*;
 
-use super::{ShapeInteraction, ShapeIntersection, ShapeSample, ShapeT};
+use super::{ShapeInteraction, ShapeIntersection, ShapeSample, ShapeT, TriangleMesh};
 
-#[derive(Debug, Clone, Default)]
+/// A triangle, either of a [`TriangleMesh`] or with vertices of its own.
+#[derive(Debug, Clone)]
 pub struct Triangle {
-    v: [Point3; 3],
-    n: [Normal3; 3],
-    uv: [Point2; 3],
+    vertices: TriangleVertices,
+}
+
+#[derive(Debug, Clone)]
+enum TriangleVertices {
+    /// The triangle at `index` in `mesh`.
+    Mesh { mesh: Arc<TriangleMesh>, index: u32 },
+    /// The positions, normals and uvs of a triangle that doesn't share them.
+    Own(Box<([Point3; 3], [Normal3; 3], [Point2; 3])>),
 }
 
 impl Triangle {
+    /// Creates a triangle with its own vertices, for triangles that don't share them.
     pub fn new(v: [Point3; 3], n: [Normal3; 3], uv: [Point2; 3]) -> Self {
         STATS.shapes_created.inc();
-        Self { v, n, uv }
+        Self {
+            vertices: TriangleVertices::Own(Box::new((v, n, uv))),
+        }
+    }
+
+    /// The triangle at `index` in `mesh`.
+    pub fn from_mesh(mesh: Arc<TriangleMesh>, index: u32) -> Self {
+        STATS.shapes_created.inc();
+        Self {
+            vertices: TriangleVertices::Mesh { mesh, index },
+        }
+    }
+
+    #[inline]
+    fn v(&self) -> [Point3; 3] {
+        match &self.vertices {
+            TriangleVertices::Mesh { mesh, index } => mesh.positions(*index),
+            TriangleVertices::Own(vertices) => vertices.0,
+        }
+    }
+
+    /// The normals of the vertices, `None` for triangles of flat meshes.
+    #[inline]
+    fn n(&self) -> Option<[Normal3; 3]> {
+        match &self.vertices {
+            TriangleVertices::Mesh { mesh, index } => mesh.normals(*index),
+            TriangleVertices::Own(vertices) => Some(vertices.1),
+        }
+    }
+
+    #[inline]
+    fn uv(&self) -> [Point2; 3] {
+        match &self.vertices {
+            TriangleVertices::Mesh { mesh, index } => mesh.uvs(*index),
+            TriangleVertices::Own(vertices) => vertices.2,
+        }
+    }
+
+    /// The barycentric coordinates of the point `p` on the triangle.
+    fn barycentric(&self, p: Point3) -> [f32; 3] {
+        let [v1, v2, v3] = self.v();
+        let f1 = v1 - p;
+        let f2 = v2 - p;
+        let f3 = v3 - p;
+
+        let det = (v1 - v2).cross(v1 - v3).length();
+        [
+            f2.cross(f3).length() / det,
+            f3.cross(f1).length() / det,
+            f1.cross(f2).length() / det,
+        ]
+    }
+
+    /// The normal of the plane of the triangle, on the same side as the vertex normals.
+    fn geometric_normal(&self) -> Normal3 {
+        let v = self.v();
+        let ng = (v[1] - v[0]).cross(v[2] - v[0]).normalize();
+        let Some(n) = self.n() else {
+            return ng;
+        };
+
+        if (n[0] + n[1] + n[2]).dot(ng) < 0.0 {
+            -ng
+        } else {
+            ng
+        }
+    }
+
+    /// The solid angle the triangle covers as seen from `p`.
+    fn solid_angle(&self, p: Point3) -> f32 {
+        let [a, b, c] = self.v().map(|v| (v - p).normalize());
+
+        2.0 * a
+            .dot(b.cross(c))
+            .abs()
+            .atan2(1.0 + a.dot(b) + a.dot(c) + b.dot(c))
     }
 }
 
+/// Triangles that cover less solid angle than this are sampled by area, the spherical sampling is imprecise
+/// for them and area sampling is about as good.
+const MIN_SPHERICAL_SAMPLE_AREA: f32 = 3e-4;
+/// Triangles that cover close to a hemisphere are sampled by area, the spherical sampling is unstable there.
+const MAX_SPHERICAL_SAMPLE_AREA: f32 = 6.22;
+
+/// Uniformly samples the spherical triangle that the triangle `v` projects to around `p`, using the method
+/// of Arvo, "Stratified Sampling of Spherical Triangles". Returns the barycentric coordinates of the point
+/// seen in the sampled direction and the density over solid angle.
+fn sample_spherical_triangle(v: [Point3; 3], p: Point3, u: Point2) -> Option<([f32; 3], f32)> {
+    let [a, b, c] = v.map(|v| (v - p).normalize());
+
+    // normals of the planes through p and each edge
+    let n_ab = a.cross(b);
+    let n_bc = b.cross(c);
+    let n_ca = c.cross(a);
+    if n_ab.length_squared() == 0.0 || n_bc.length_squared() == 0.0 || n_ca.length_squared() == 0.0
+    {
+        return None;
+    }
+    let (n_ab, n_bc, n_ca) = (n_ab.normalize(), n_bc.normalize(), n_ca.normalize());
+
+    // the area of a spherical triangle is the excess of the sum of its angles
+    let alpha = angle_between(n_ab, -n_ca);
+    let beta = angle_between(n_bc, -n_ab);
+    let gamma = angle_between(n_ca, -n_bc);
+    let area = alpha + beta + gamma - core::f32::consts::PI;
+    if area <= 0.0 {
+        return None;
+    }
+
+    // pick the area of the sub triangle that is cut off, which gives the new vertex c' on the arc ac
+    let area_pi = core::f32::consts::PI + u.x * area;
+    let (sin_alpha, cos_alpha) = alpha.sin_cos();
+    let (sin_area_pi, cos_area_pi) = area_pi.sin_cos();
+    let sin_phi = sin_area_pi * cos_alpha - cos_area_pi * sin_alpha;
+    let cos_phi = cos_area_pi * cos_alpha + sin_area_pi * sin_alpha;
+
+    let k1 = cos_phi + cos_alpha;
+    let k2 = sin_phi - sin_alpha * a.dot(b);
+    let cos_b_prime = ((k2 + (k2 * cos_phi - k1 * sin_phi) * cos_alpha)
+        / ((k2 * sin_phi + k1 * cos_phi) * sin_alpha))
+        .clamp(-1.0, 1.0);
+    let sin_b_prime = (1.0 - cos_b_prime * cos_b_prime).max(0.0).sqrt();
+    let c_prime = cos_b_prime * a + sin_b_prime * gram_schmidt(c, a).normalize();
+
+    // then a direction on the arc from b to c'
+    let cos_theta = 1.0 - u.y * (1.0 - c_prime.dot(b));
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let w = cos_theta * b + sin_theta * gram_schmidt(c_prime, b).normalize();
+
+    // intersect the direction with the triangle for the barycentric coordinates
+    let e1 = v[1] - v[0];
+    let e2 = v[2] - v[0];
+    let s1 = w.cross(e2);
+    let divisor = s1.dot(e1);
+    if divisor == 0.0 {
+        // degenerate, any point will do
+        return Some(([1.0 / 3.0; 3], 1.0 / area));
+    }
+
+    let s = p - v[0];
+    let mut b1 = (s.dot(s1) / divisor).clamp(0.0, 1.0);
+    let mut b2 = (w.dot(s.cross(e1)) / divisor).clamp(0.0, 1.0);
+    if b1 + b2 > 1.0 {
+        let sum = b1 + b2;
+        b1 /= sum;
+        b2 /= sum;
+    }
+
+    Some(([1.0 - b1 - b2, b1, b2], 1.0 / area))
+}
+
+/// The angle between two normalized vectors, accurate for nearly parallel vectors too.
+fn angle_between(v1: Vector3, v2: Vector3) -> f32 {
+    if v1.dot(v2) < 0.0 {
+        core::f32::consts::PI - 2.0 * ((v1 + v2).length() / 2.0).clamp(-1.0, 1.0).asin()
+    } else {
+        2.0 * ((v2 - v1).length() / 2.0).clamp(-1.0, 1.0).asin()
+    }
+}
+
+/// The part of `v` orthogonal to the normalized `w`.
+fn gram_schmidt(v: Vector3, w: Vector3) -> Vector3 {
+    v - v.dot(w) * w
+}
+
 impl ShapeT for Triangle {
     fn intersect(&self, ray: Ray) -> ShapeIntersection {
         // uses the Möller Trumbore intersection algorithm
 
         const EPSILON: f32 = 1e-7;
 
-        let edge1 = self.v[1] - self.v[0];
-        let edge2 = self.v[2] - self.v[0];
+        let v = self.v();
+        let edge1 = v[1] - v[0];
+        let edge2 = v[2] - v[0];
         let h = ray.d.cross(edge2);
         let a = edge1.dot(h);
 
@@ -32,7 +205,7 @@ impl ShapeT for Triangle {
         }
 
         let f = 1.0 / a;
-        let s = ray.o - self.v[0];
+        let s = ray.o - v[0];
         let u = f * s.dot(h);
 
         if !(0.0..=1.0).contains(&u) {
@@ -56,22 +229,12 @@ impl ShapeT for Triangle {
         ray: Ray,
         intersection: ShapeIntersection,
     ) -> ShapeInteraction {
-        let p = ray.at(intersection.t);
-        let v1 = self.v[0];
-        let v2 = self.v[1];
-        let v3 = self.v[2];
-
-        let f1 = v1 - p;
-        let f2 = v2 - p;
-        let f3 = v3 - p;
-
-        let det = (v1 - v2).cross(v1 - v3).length();
-        let b0 = f2.cross(f3).length() / det;
-        let b1 = f3.cross(f1).length() / det;
-        let b2 = f1.cross(f2).length() / det;
+        let [v1, v2, v3] = self.v();
+        let uv = self.uv();
+        let [b0, b1, b2] = self.barycentric(ray.at(intersection.t));
 
-        let duv02 = self.uv[1] - self.uv[0];
-        let duv12 = self.uv[2] - self.uv[0];
+        let duv02 = uv[1] - uv[0];
+        let duv12 = uv[2] - uv[0];
         let dp02 = v2 - v1;
         let dp12 = v3 - v1;
 
@@ -87,43 +250,103 @@ impl ShapeT for Triangle {
             )
         };
 
+        let n = match self.n() {
+            Some(n) => b0 * n[0] + b1 * n[1] + b2 * n[2],
+            None => self.geometric_normal(),
+        };
+
         ShapeInteraction {
             intersection,
             p: ray.at(intersection.t),
-            uv: b0 * self.uv[0] + b1 * self.uv[1] + b2 * self.uv[2],
-            n: face_forward(b0 * self.n[0] + b1 * self.n[1] + b2 * self.n[2], -ray.d),
+            uv: b0 * uv[0] + b1 * uv[1] + b2 * uv[2],
+            n: face_forward(n, -ray.d),
 
             dp_du,
             dp_dv,
         }
     }
 
+    fn uv(&self, ray: Ray, intersection: ShapeIntersection) -> Point2 {
+        let uv = self.uv();
+        let [b0, b1, b2] = self.barycentric(ray.at(intersection.t));
+
+        b0 * uv[0] + b1 * uv[1] + b2 * uv[2]
+    }
+
     fn make_bounds(&self) -> Bounds3 {
-        Bounds3::new(self.v[0], self.v[1])
-            .expand(self.v[2])
-            .pad(1e-5) // deal with the case that the bounds have zero volume
+        let v = self.v();
+        Bounds3::new(v[0], v[1]).expand(v[2]).pad(1e-5) // deal with the case that the bounds have zero volume
     }
 
     fn transform(&mut self, transform: &Transform3) -> bool {
-        self.v = self.v.map(|v| transform.transform_point(v));
-        self.n = self.n.map(|n| transform.transform_normal(n));
+        match &mut self.vertices {
+            // a shared mesh is transformed by its owner for all of its triangles at once
+            TriangleVertices::Mesh { mesh, .. } => {
+                let Some(mesh) = Arc::get_mut(mesh) else {
+                    return false;
+                };
+                mesh.transform(transform);
+            }
+            TriangleVertices::Own(vertices) => {
+                let (v, n, _) = vertices.as_mut();
+                *v = v.map(|p| transform.transform_point(p));
+                *n = n.map(|n| transform.transform_normal(n));
+            }
+        }
 
         true
     }
 
     fn area(&self) -> f32 {
-        0.5 * (self.v[1] - self.v[0])
-            .cross(self.v[2] - self.v[0])
-            .length()
+        let v = self.v();
+        0.5 * (v[1] - v[0]).cross(v[2] - v[0]).length()
     }
 
     fn sample(&self, u: Point2) -> ShapeSample {
         let (b0, b1) = warp::square_to_barycentric(u);
         let b2 = 1.0 - b0 - b1;
 
+        let (v, uv) = (self.v(), self.uv());
         ShapeSample {
-            p: b0 * self.v[0] + b1 * self.v[1] + b2 * self.v[2],
-            n: b0 * self.n[0] + b1 * self.n[1] + b2 * self.n[2],
+            p: b0 * v[0] + b1 * v[1] + b2 * v[2],
+            n: self.geometric_normal(),
+            uv: b0 * uv[0] + b1 * uv[1] + b2 * uv[2],
+        }
+    }
+
+    fn sample_solid_angle(&self, p: Point3, u: Point2) -> Option<(ShapeSample, f32)> {
+        let solid_angle = self.solid_angle(p);
+        if !(MIN_SPHERICAL_SAMPLE_AREA..=MAX_SPHERICAL_SAMPLE_AREA).contains(&solid_angle) {
+            return None;
         }
+
+        let (v, uv) = (self.v(), self.uv());
+        let ([b0, b1, b2], pdf) = sample_spherical_triangle(v, p, u)?;
+
+        Some((
+            ShapeSample {
+                p: b0 * v[0] + b1 * v[1] + b2 * v[2],
+                n: self.geometric_normal(),
+                uv: b0 * uv[0] + b1 * uv[1] + b2 * uv[2],
+            },
+            pdf,
+        ))
+    }
+
+    fn pdf_solid_angle(&self, p: Point3, wi: Vector3) -> Option<f32> {
+        let solid_angle = self.solid_angle(p);
+        if !(MIN_SPHERICAL_SAMPLE_AREA..=MAX_SPHERICAL_SAMPLE_AREA).contains(&solid_angle) {
+            return None;
+        }
+
+        Some(if self.intersect(Ray::new(p, wi)).t > 0.0 {
+            1.0 / solid_angle
+        } else {
+            0.0
+        })
+    }
+
+    fn normal(&self, _p: Point3) -> Normal3 {
+        self.geometric_normal()
     }
 }
@@ -0,0 +1,203 @@
+use std::sync::Arc;
+
+use crate::prelude::*;
+
+use super::{Shape, Triangle};
+
+/// Vertices shared by the triangles of a mesh, which only store the mesh and their index into it.
+#[derive(Debug, Clone)]
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    /// Either empty, in which case the triangles are flat, or a normal for every position.
+    normals: Vec<Normal3>,
+    /// Either empty, in which case every triangle has the same default uvs, or one for every position.
+    uvs: Vec<Point2>,
+    /// Three indices into the vertices for every triangle.
+    indices: Vec<u32>,
+}
+
+/// The uvs of the vertices of triangles from meshes without uvs.
+const DEFAULT_UVS: [Point2; 3] = [
+    Point2::new(0.0, 0.0),
+    Point2::new(1.0, 0.0),
+    Point2::new(1.0, 1.0),
+];
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Normal3>,
+        uvs: Vec<Point2>,
+        indices: Vec<u32>,
+    ) -> Self {
+        assert!(normals.is_empty() || normals.len() == positions.len());
+        assert!(uvs.is_empty() || uvs.len() == positions.len());
+        assert_eq!(indices.len() % 3, 0);
+
+        let mesh = Self {
+            positions,
+            normals,
+            uvs,
+            indices,
+        };
+
+        let memory = mesh.memory();
+        STATS.mesh_memory.add(memory);
+        // what the triangles would take if each stored its own vertices
+        let copies = (mesh.triangle_count()
+            * 3
+            * (size_of::<Point3>() + size_of::<Normal3>() + size_of::<Point2>()))
+            as u64;
+        STATS.mesh_memory_saved.add(copies.saturating_sub(memory));
+
+        mesh
+    }
+
+    #[inline]
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Transforms the vertices of the mesh, for all the triangles at once.
+    pub fn transform(&mut self, transform: &Transform3) {
+        for p in &mut self.positions {
+            *p = transform.transform_point(*p);
+        }
+        for n in self.normals.iter_mut().filter(|n| **n != Normal3::ZERO) {
+            *n = transform.transform_normal(*n).normalize();
+        }
+    }
+
+    /// Creates the triangles of the mesh, which all share it.
+    pub fn triangles(self) -> Vec<Shape> {
+        let mesh = Arc::new(self);
+
+        (0..mesh.triangle_count())
+            .map(|i| Shape::Triangle(Triangle::from_mesh(mesh.clone(), i as u32)))
+            .collect()
+    }
+
+    #[inline]
+    fn vertex_indices(&self, triangle: u32) -> [usize; 3] {
+        let i = triangle as usize * 3;
+        [
+            self.indices[i] as usize,
+            self.indices[i + 1] as usize,
+            self.indices[i + 2] as usize,
+        ]
+    }
+
+    #[inline]
+    pub(super) fn positions(&self, triangle: u32) -> [Point3; 3] {
+        self.vertex_indices(triangle).map(|i| self.positions[i])
+    }
+
+    /// The normals of the vertices of `triangle`, `None` for flat meshes and for triangles with a zero
+    /// normal, which marks a vertex without a usable one.
+    #[inline]
+    pub(super) fn normals(&self, triangle: u32) -> Option<[Normal3; 3]> {
+        if self.normals.is_empty() {
+            return None;
+        }
+
+        let normals = self.vertex_indices(triangle).map(|i| self.normals[i]);
+        normals
+            .iter()
+            .all(|n| *n != Normal3::ZERO)
+            .then_some(normals)
+    }
+
+    #[inline]
+    pub(super) fn uvs(&self, triangle: u32) -> [Point2; 3] {
+        if self.uvs.is_empty() {
+            DEFAULT_UVS
+        } else {
+            self.vertex_indices(triangle).map(|i| self.uvs[i])
+        }
+    }
+
+    fn memory(&self) -> u64 {
+        (size_of::<Self>()
+            + self.positions.len() * size_of::<Point3>()
+            + self.normals.len() * size_of::<Normal3>()
+            + self.uvs.len() * size_of::<Point2>()
+            + self.indices.len() * size_of::<u32>()) as u64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shapes::ShapeT;
+
+    #[test]
+    fn triangles_match_standalone_triangles() {
+        let positions = vec![
+            Point3::new(-1.0, -1.0, 0.0),
+            Point3::new(1.0, -1.0, 0.2),
+            Point3::new(-1.0, 1.0, -0.2),
+            Point3::new(1.0, 1.0, 0.0),
+        ];
+        let normals = positions
+            .iter()
+            .map(|p| Normal3::new(p.x, p.y, 4.0).normalize())
+            .collect::<Vec<_>>();
+        let uvs = positions
+            .iter()
+            .map(|p| Point2::new(p.x, p.y) * 0.5 + 0.5)
+            .collect::<Vec<_>>();
+        let indices = vec![0, 1, 2, 1, 3, 2];
+
+        let standalone = indices
+            .chunks_exact(3)
+            .map(|t| {
+                let [a, b, c] = [t[0], t[1], t[2]].map(|i| i as usize);
+                Triangle::new(
+                    [positions[a], positions[b], positions[c]],
+                    [normals[a], normals[b], normals[c]],
+                    [uvs[a], uvs[b], uvs[c]],
+                )
+            })
+            .collect::<Vec<_>>();
+        let mesh = TriangleMesh::new(positions, normals, uvs, indices).triangles();
+        assert_eq!(mesh.len(), 2);
+
+        for i in 0..100 {
+            let p = Point3::new(
+                (i % 10) as f32 / 5.0 - 0.9,
+                (i / 10) as f32 / 5.0 - 0.9,
+                2.0,
+            );
+            let ray = Ray::new(p, Vector3::new(0.1, 0.0, -1.0));
+            for (shared, own) in mesh.iter().zip(&standalone) {
+                let (hit, own_hit) = (shared.intersect(ray), own.intersect(ray));
+                assert_eq!(hit.t, own_hit.t);
+                if hit.t > 0.0 {
+                    let si = shared.get_surface_interaction(ray, hit);
+                    let own_si = own.get_surface_interaction(ray, own_hit);
+                    assert!(si.n.distance(own_si.n) < 1e-6 && si.uv.distance(own_si.uv) < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn zero_normals_make_their_triangles_flat() {
+        let positions = vec![
+            Point3::new(-1.0, -1.0, 0.0),
+            Point3::new(1.0, -1.0, 0.0),
+            Point3::new(-1.0, 1.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+        ];
+        let mut normals = vec![Normal3::Z; 4];
+        normals[0] = Normal3::ZERO;
+        let mut mesh = TriangleMesh::new(positions, normals, vec![], vec![0, 1, 2, 1, 3, 2]);
+        mesh.transform(&Transform3::rotate(Point3::new(0.3, 0.0, 0.0)));
+
+        assert!(mesh.normals(0).is_none());
+        let normals = mesh.normals(1).unwrap();
+        assert!(normals
+            .iter()
+            .all(|n| n.is_finite() && (n.length() - 1.0).abs() < 1e-5));
+    }
+}
@@ -37,6 +37,8 @@ pub trait SpectrumT:
 
     fn to_xyz(&self) -> [f32; 3];
 
+    fn from_xyz(x: f32, y: f32, z: f32) -> Self;
+
     fn y(&self) -> f32;
 
     fn exp(&self) -> Self;
@@ -46,6 +48,22 @@ pub trait SpectrumT:
 
 pub type Spectrum = RgbSpectrum;
 
+/// CIE 1931 2° color matching functions at wavelength `lambda` in nm, using the multi-lobe fit from
+/// "Simple Analytic Approximations to the CIE XYZ Color Matching Functions" (Wyman et al. 2013).
+pub fn cie_xyz(lambda: f32) -> [f32; 3] {
+    let g = |x: f32, mu: f32, sigma_l: f32, sigma_r: f32| {
+        let t = (x - mu) / if x < mu { sigma_l } else { sigma_r };
+        (-0.5 * t * t).exp()
+    };
+
+    [
+        1.056 * g(lambda, 599.8, 37.9, 31.0) + 0.362 * g(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * g(lambda, 501.1, 20.4, 26.2),
+        0.821 * g(lambda, 568.8, 46.9, 40.5) + 0.286 * g(lambda, 530.9, 16.3, 31.1),
+        1.217 * g(lambda, 437.0, 11.8, 36.0) + 0.681 * g(lambda, 459.0, 26.0, 13.8),
+    ]
+}
+
 // #[derive(Debug, Clone)]
 // #[enum_dispatch(SpectrumT)]
 // pub enum Spectrum {
@@ -59,6 +59,16 @@ impl SpectrumT for RgbSpectrum {
         ]
     }
 
+    fn from_xyz(x: f32, y: f32, z: f32) -> Self {
+        Self {
+            c: [
+                3.240479 * x - 1.53715 * y - 0.498535 * z,
+                -0.969256 * x + 1.875991 * y + 0.041556 * z,
+                0.055648 * x - 0.204043 * y + 1.057311 * z,
+            ],
+        }
+    }
+
     fn y(&self) -> f32 {
         0.212671 * self[0] + 0.715160 * self[1] + 0.072169 * self[2]
     }
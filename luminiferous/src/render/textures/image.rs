@@ -7,7 +7,7 @@ use crate::{
     spectra::{Spectrum, SpectrumT},
 };
 
-use super::{SpectralTexture, TextureT};
+use super::{SpectralTexture, Texture, TextureT};
 
 #[derive(Debug, Clone)]
 pub struct ImageTexture<T: Copy> {
@@ -22,8 +22,28 @@ impl<T: Copy> ImageTexture<T> {
 
 impl ImageTexture<Spectrum> {
     pub fn from_path(path: &Path) -> Self {
-        let image = image::open(path).unwrap();
+        Self::try_from_path(path).unwrap()
+    }
+
+    pub fn try_from_path(path: &Path) -> image::ImageResult<Self> {
+        let image = image::open(path)?;
+
+        // mild hack
+        // FIXME: have this be a param
+        let undo_gamma_correct = !matches!(
+            image,
+            image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_)
+        );
+
+        Ok(Self::from_image(image, undo_gamma_correct))
+    }
 
+    /// Loads an image that is already linear regardless of its format, e.g. normal maps.
+    pub fn from_path_linear(path: &Path) -> Self {
+        Self::from_image(image::open(path).unwrap(), false)
+    }
+
+    fn from_image(image: image::DynamicImage, undo_gamma_correct: bool) -> Self {
         let extent = UExtent2::new(image.width(), image.height());
 
         let inverse_gamma = |x: f32| -> f32 {
@@ -34,13 +54,6 @@ impl ImageTexture<Spectrum> {
             }
         };
 
-        // mild hack
-        // FIXME: have this be a param
-        let undo_gamma_correct = !matches!(
-            image,
-            image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_)
-        );
-
         let pixels = image
             .to_rgb32f()
             .chunks(3)
@@ -79,6 +92,53 @@ impl ImageTexture<Spectrum> {
     }
 }
 
+impl ImageTexture<f32> {
+    /// Loads the luminance of an image as a linear scalar texture, for bump maps and masks.
+    pub fn from_path_luminance(path: &Path) -> Self {
+        Self::from_image_luminance(&image::open(path).unwrap())
+    }
+
+    /// Loads the alpha channel of an image if it has one that isn't opaque everywhere, and its luminance
+    /// otherwise. Images with alpha are usually meant as cutouts.
+    pub fn from_path_alpha_or_luminance(path: &Path) -> Self {
+        let image = image::open(path).unwrap();
+        if image.color().has_alpha() {
+            let alpha = image
+                .to_rgba32f()
+                .chunks(4)
+                .map(|p| p[3])
+                .collect::<Vec<_>>();
+            if alpha.iter().any(|&a| a < 1.0) {
+                return Self::from_pixels(UExtent2::new(image.width(), image.height()), alpha);
+            }
+        }
+
+        Self::from_image_luminance(&image)
+    }
+
+    fn from_image_luminance(image: &image::DynamicImage) -> Self {
+        let pixels = image
+            .to_rgb32f()
+            .chunks(3)
+            .map(|p| Spectrum::from_rgb(p[0], p[1], p[2]).y())
+            .collect::<Vec<_>>();
+
+        Self::from_pixels(UExtent2::new(image.width(), image.height()), pixels)
+    }
+
+    fn from_pixels(extent: UExtent2, pixels: Vec<f32>) -> Self {
+        STATS.textures_created.inc();
+        STATS
+            .texture_memory
+            .add(size_of::<f32>() as u64 * pixels.len() as u64);
+        STATS.texture_memory.add(size_of::<Texture<f32>>() as u64);
+
+        Self {
+            pixels: Array2d::from_1d(extent, pixels),
+        }
+    }
+}
+
 impl<T: Copy> TextureT<T> for ImageTexture<T> {
     fn eval(&self, si: &SurfaceInteraction) -> T {
         self.eval_uv(si.uv)
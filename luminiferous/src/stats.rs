@@ -115,6 +115,10 @@ pub struct Statistics {
     pub film_memory: StatCounter,
     pub primitive_memory: StatCounter,
     pub aggregate_memory: StatCounter,
+    pub mesh_memory: StatCounter,
+    /// Memory the triangles of meshes would take if they each stored their own vertices, minus what the
+    /// meshes take.
+    pub mesh_memory_saved: StatCounter,
 }
 
 impl Statistics {
@@ -140,6 +144,8 @@ impl Statistics {
             film_memory: StatCounter::new(),
             primitive_memory: StatCounter::new(),
             aggregate_memory: StatCounter::new(),
+            mesh_memory: StatCounter::new(),
+            mesh_memory_saved: StatCounter::new(),
         }
     }
 
@@ -169,11 +175,13 @@ impl Statistics {
         println!("  memory:");
         let total_memory = (self.texture_memory.get()
             + self.film_memory.get()
-            + self.primitive_memory.get() + self.aggregate_memory.get()) as f32;
+            + self.primitive_memory.get() + self.aggregate_memory.get()
+            + self.mesh_memory.get()) as f32;
         println!("    textures:   {} ({:.2}%)", self.texture_memory.get_as_bytes(), self.texture_memory.get() as f32 / total_memory * 100.0);
         println!("    film:       {} ({:.2}%)", self.film_memory.get_as_bytes(), self.film_memory.get() as f32 / total_memory * 100.0);
         println!("    primitives: {} ({:.2}%)", self.primitive_memory.get_as_bytes(), self.primitive_memory.get() as f32 / total_memory * 100.0);
         println!("    aggregates: {} ({:.2}%)", self.aggregate_memory.get_as_bytes(), self.aggregate_memory.get() as f32 / total_memory * 100.0);
+        println!("    meshes:     {} ({:.2}%), {} saved by sharing vertices", self.mesh_memory.get_as_bytes(), self.mesh_memory.get() as f32 / total_memory * 100.0, self.mesh_memory_saved.get_as_bytes());
 
     }
 }